@@ -0,0 +1,365 @@
+// RCA CDP1802 (COSMAC).
+//
+// The CPU doesn't own memory or devices; they're accessed through a `Bus`, so that the CPU can
+// share the memory of the host system (e.g. the CHIP-8 interpreter RAM, on the COSMAC VIP).
+//
+// Timing is expressed in machine cycles (8 clock cycles each): all the instructions take 2 (fetch
+// and execute), except the long branches/skips, which take 3.
+//
+// DMA is not emulated; devices that need it (e.g. the VIP display) can read the memory directly.
+//
+// Reference: RCA CDP1802 user manual (MPM-201).
+
+/// Memory and I/O, as seen by the CPU.
+///
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+
+    fn write(&mut self, address: u16, value: u8);
+
+    /// INP N (N = 1..7); the value is also stored in memory by the CPU.
+    ///
+    fn input(&mut self, _port: u8) -> u8 {
+        0
+    }
+
+    /// OUT N (N = 1..7).
+    ///
+    fn output(&mut self, _port: u8, _value: u8) {}
+
+    /// EF1..EF4 input flags (`flag` = 1..4); true if asserted.
+    ///
+    fn flag(&mut self, _flag: u8) -> bool {
+        false
+    }
+}
+
+/// Plain memory, without devices; addresses wrap around the slice size.
+///
+impl Bus for [u8] {
+    fn read(&mut self, address: u16) -> u8 {
+        self[address as usize % self.len()]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        let length = self.len();
+        self[address as usize % length] = value;
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Cpu {
+    /// Scratchpad registers; any of them can be the program counter (selected by P) or the data
+    /// pointer (selected by X).
+    ///
+    pub R: [u16; 16],
+    pub D: u8,
+    pub DF: bool,
+    pub P: u8,
+    pub X: u8,
+    /// X and P, saved on interrupt (or MARK).
+    ///
+    pub T: u8,
+    pub IE: bool,
+    pub Q: bool,
+
+    // Set by IDL; cleared by an interrupt.
+    //
+    idle: bool,
+}
+
+impl Cpu {
+    /// State after reset: the registers are cleared (R0 is the program counter), and the
+    /// interrupts are enabled.
+    ///
+    pub fn new() -> Cpu {
+        Cpu {
+            IE: true,
+            ..Cpu::default()
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// Returns false (and does nothing) if the interrupts are disabled.
+    ///
+    pub fn interrupt(&mut self) -> bool {
+        if !self.IE {
+            return false;
+        }
+
+        self.T = (self.X << 4) | self.P;
+        self.P = 1;
+        self.X = 2;
+        self.IE = false;
+        self.idle = false;
+
+        true
+    }
+
+    /// Executes an instruction; returns the machine cycles spent. While idle, a cycle elapses
+    /// without executing instructions.
+    ///
+    pub fn step<B: Bus + ?Sized>(&mut self, bus: &mut B) -> u8 {
+        if self.idle {
+            return 1;
+        }
+
+        let opcode = self.fetch(bus);
+        let (I, N) = (opcode >> 4, opcode & 0x0F);
+
+        match I {
+            0x0 if N == 0 => self.idle = true,
+            0x0 => self.D = bus.read(self.R[N as usize]),
+            0x1 => self.R[N as usize] = self.R[N as usize].wrapping_add(1),
+            0x2 => self.R[N as usize] = self.R[N as usize].wrapping_sub(1),
+            0x3 => {
+                let condition = self.short_branch_condition(N, bus);
+                self.execute_short_branch(condition, bus);
+            }
+            0x4 => {
+                self.D = bus.read(self.R[N as usize]);
+                self.R[N as usize] = self.R[N as usize].wrapping_add(1);
+            }
+            0x5 => bus.write(self.R[N as usize], self.D),
+            0x6 => self.execute_io(N, bus),
+            0x7 => self.execute_group_7(N, bus),
+            0x8 => self.D = self.R[N as usize] as u8,
+            0x9 => self.D = (self.R[N as usize] >> 8) as u8,
+            0xA => self.R[N as usize] = (self.R[N as usize] & 0xFF00) | self.D as u16,
+            0xB => self.R[N as usize] = (self.R[N as usize] & 0x00FF) | ((self.D as u16) << 8),
+            0xC => {
+                self.execute_long_branch_or_skip(N, bus);
+                return 3;
+            }
+            0xD => self.P = N,
+            0xE => self.X = N,
+            _ => self.execute_alu(N, bus),
+        }
+
+        2
+    }
+
+    // INSTRUCTION GROUPS //////////////////////////////////////////////////////////////////////////
+
+    fn short_branch_condition<B: Bus + ?Sized>(&mut self, N: u8, bus: &mut B) -> bool {
+        let condition = match N & 0x07 {
+            0 => true,
+            1 => self.Q,
+            2 => self.D == 0,
+            3 => self.DF,
+            flag => bus.flag(flag - 3),
+        };
+
+        // The upper half inverts the condition (38, the inverse of an unconditional branch, is
+        // SKP).
+        //
+        condition ^ (N >= 0x08)
+    }
+
+    fn execute_short_branch<B: Bus + ?Sized>(&mut self, condition: bool, bus: &mut B) {
+        let P = self.P as usize;
+
+        if condition {
+            let target = bus.read(self.R[P]);
+            self.R[P] = (self.R[P] & 0xFF00) | target as u16;
+        } else {
+            self.R[P] = self.R[P].wrapping_add(1);
+        }
+    }
+
+    fn execute_long_branch_or_skip<B: Bus + ?Sized>(&mut self, N: u8, bus: &mut B) {
+        let P = self.P as usize;
+
+        // C4 (NOP) and C8 (LSKP) are special cases of the skips.
+        //
+        let (is_skip, condition) = match N {
+            0x0 => (false, true),
+            0x1 => (false, self.Q),
+            0x2 => (false, self.D == 0),
+            0x3 => (false, self.DF),
+            0x4 => (true, false),
+            0x5 => (true, !self.Q),
+            0x6 => (true, self.D != 0),
+            0x7 => (true, !self.DF),
+            0x8 => (true, true),
+            0x9 => (false, !self.Q),
+            0xA => (false, self.D != 0),
+            0xB => (false, !self.DF),
+            0xC => (true, self.IE),
+            0xD => (true, self.Q),
+            0xE => (true, self.D == 0),
+            _ => (true, self.DF),
+        };
+
+        if is_skip {
+            if condition {
+                self.R[P] = self.R[P].wrapping_add(2);
+            }
+        } else if condition {
+            let high_byte = bus.read(self.R[P]) as u16;
+            let low_byte = bus.read(self.R[P].wrapping_add(1)) as u16;
+            self.R[P] = (high_byte << 8) | low_byte;
+        } else {
+            self.R[P] = self.R[P].wrapping_add(2);
+        }
+    }
+
+    fn execute_io<B: Bus + ?Sized>(&mut self, N: u8, bus: &mut B) {
+        let X = self.X as usize;
+
+        match N {
+            // IRX
+            //
+            0x0 => self.R[X] = self.R[X].wrapping_add(1),
+            // OUT
+            //
+            0x1..=0x7 => {
+                let value = bus.read(self.R[X]);
+                bus.output(N, value);
+                self.R[X] = self.R[X].wrapping_add(1);
+            }
+            // INP; 68 doesn't select any device.
+            //
+            _ => {
+                let value = bus.input(N - 8);
+                bus.write(self.R[X], value);
+                self.D = value;
+            }
+        }
+    }
+
+    fn execute_group_7<B: Bus + ?Sized>(&mut self, N: u8, bus: &mut B) {
+        let X = self.X as usize;
+
+        match N {
+            // RET/DIS
+            //
+            0x0 | 0x1 => {
+                let value = bus.read(self.R[X]);
+                self.R[X] = self.R[X].wrapping_add(1);
+                self.X = value >> 4;
+                self.P = value & 0x0F;
+                self.IE = N == 0x0;
+            }
+            // LDXA
+            //
+            0x2 => {
+                self.D = bus.read(self.R[X]);
+                self.R[X] = self.R[X].wrapping_add(1);
+            }
+            // STXD
+            //
+            0x3 => {
+                bus.write(self.R[X], self.D);
+                self.R[X] = self.R[X].wrapping_sub(1);
+            }
+            // ADC/SDB/SMB
+            //
+            0x4 => self.add(bus.read(self.R[X]), self.D, self.DF),
+            0x5 => self.add(bus.read(self.R[X]), !self.D, self.DF),
+            0x7 => self.add(self.D, !bus.read(self.R[X]), self.DF),
+            // SHRC
+            //
+            0x6 => {
+                let carry = self.D & 0x01 != 0;
+                self.D = (self.D >> 1) | ((self.DF as u8) << 7);
+                self.DF = carry;
+            }
+            // SAV
+            //
+            0x8 => bus.write(self.R[X], self.T),
+            // MARK
+            //
+            0x9 => {
+                self.T = (self.X << 4) | self.P;
+                bus.write(self.R[2], self.T);
+                self.X = self.P;
+                self.R[2] = self.R[2].wrapping_sub(1);
+            }
+            // REQ/SEQ
+            //
+            0xA => self.Q = false,
+            0xB => self.Q = true,
+            // ADCI/SDBI/SMBI
+            //
+            0xC => {
+                let operand = self.fetch(bus);
+                self.add(operand, self.D, self.DF);
+            }
+            0xD => {
+                let operand = self.fetch(bus);
+                self.add(operand, !self.D, self.DF);
+            }
+            0xF => {
+                let operand = self.fetch(bus);
+                self.add(self.D, !operand, self.DF);
+            }
+            // SHLC
+            //
+            _ => {
+                let carry = self.D & 0x80 != 0;
+                self.D = (self.D << 1) | self.DF as u8;
+                self.DF = carry;
+            }
+        }
+    }
+
+    // F0-F7 take the operand from M(R(X)); F8-FF are the immediate versions. The exceptions are
+    // F6/FE (shifts), which don't have an operand, and F0/F8, which are loads.
+    //
+    fn execute_alu<B: Bus + ?Sized>(&mut self, N: u8, bus: &mut B) {
+        let operation = N & 0x07;
+
+        if operation == 0x6 {
+            if N == 0x6 {
+                self.DF = self.D & 0x01 != 0;
+                self.D >>= 1;
+            } else {
+                self.DF = self.D & 0x80 != 0;
+                self.D <<= 1;
+            }
+
+            return;
+        }
+
+        let operand = if N < 0x8 {
+            bus.read(self.R[self.X as usize])
+        } else {
+            self.fetch(bus)
+        };
+
+        match operation {
+            0x0 => self.D = operand,
+            0x1 => self.D |= operand,
+            0x2 => self.D &= operand,
+            0x3 => self.D ^= operand,
+            0x4 => self.add(operand, self.D, false),
+            0x5 => self.add(operand, !self.D, true),
+            _ => self.add(self.D, !operand, true),
+        }
+    }
+
+    // HELPERS /////////////////////////////////////////////////////////////////////////////////////
+
+    // Reads the byte at R(P), and advances it.
+    //
+    fn fetch<B: Bus + ?Sized>(&mut self, bus: &mut B) -> u8 {
+        let P = self.P as usize;
+        let value = bus.read(self.R[P]);
+        self.R[P] = self.R[P].wrapping_add(1);
+        value
+    }
+
+    // Subtractions are performed as additions of the complement, so that DF is set when there is
+    // no borrow, as on the hardware.
+    //
+    fn add(&mut self, operand1: u8, operand2: u8, carry: bool) {
+        let result = operand1 as u16 + operand2 as u16 + carry as u16;
+
+        self.D = result as u8;
+        self.DF = result > 0xFF;
+    }
+}
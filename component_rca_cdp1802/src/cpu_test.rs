@@ -0,0 +1,168 @@
+use crate::cpu::{Bus, Cpu};
+use demonstrate::demonstrate;
+
+// Loads the program at 0, and executes it, until R0 (the PC at reset) reaches its end.
+//
+fn run_program(cpu: &mut Cpu, memory: &mut [u8], program: &[u8]) -> u32 {
+    memory[..program.len()].copy_from_slice(program);
+
+    let mut cycles = 0;
+
+    while cpu.P != 0 || (cpu.R[0] as usize) < program.len() {
+        cycles += cpu.step(memory) as u32;
+    }
+
+    cycles
+}
+
+struct KeypadBus {
+    memory: Vec<u8>,
+    latched_key: u8,
+    pressed_key: u8,
+}
+
+impl Bus for KeypadBus {
+    fn read(&mut self, address: u16) -> u8 {
+        self.memory[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.memory[address as usize] = value;
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        if port == 2 {
+            self.latched_key = value;
+        }
+    }
+
+    fn flag(&mut self, flag: u8) -> bool {
+        flag == 3 && self.latched_key == self.pressed_key
+    }
+}
+
+demonstrate! {
+    describe "CDP1802" {
+        use super::*;
+
+        before {
+            let mut cpu = Cpu::new();
+            let mut memory = vec![0; 0x100];
+        }
+
+        it "loads immediates and registers halves" {
+            // LDI 12; PHI R5; LDI 34; PLO R5; INC R5; GHI R5
+            //
+            let cycles = run_program(&mut cpu, &mut memory, &[0xF8, 0x12, 0xB5, 0xF8, 0x34, 0xA5, 0x15, 0x95]);
+
+            assert_eq!(cpu.R[5], 0x1235);
+            assert_eq!(cpu.D, 0x12);
+            assert_eq!(cycles, 6 * 2);
+        }
+
+        it "sets DF on carry, and on subtraction without borrow" {
+            // LDI F0; ADI 20
+            //
+            run_program(&mut cpu, &mut memory, &[0xF8, 0xF0, 0xFC, 0x20]);
+
+            assert_eq!((cpu.D, cpu.DF), (0x10, true));
+
+            // LDI 10; SMI 20
+            //
+            let mut cpu = Cpu::new();
+            run_program(&mut cpu, &mut memory, &[0xF8, 0x10, 0xFF, 0x20]);
+
+            assert_eq!((cpu.D, cpu.DF), (0xF0, false));
+
+            // LDI 30; SMI 20
+            //
+            let mut cpu = Cpu::new();
+            run_program(&mut cpu, &mut memory, &[0xF8, 0x30, 0xFF, 0x20]);
+
+            assert_eq!((cpu.D, cpu.DF), (0x10, true));
+        }
+
+        it "rotates through DF" {
+            // LDI 81; SHRC (DF=0); SHRC
+            //
+            run_program(&mut cpu, &mut memory, &[0xF8, 0x81, 0x76, 0x76]);
+
+            assert_eq!((cpu.D, cpu.DF), (0xA0, false));
+        }
+
+        it "branches within the page, and across pages" {
+            // LDI 00; BZ 06; LDI 01; (06) LBR 0009; LDI 02; (09)
+            //
+            let cycles = run_program(&mut cpu, &mut memory, &[0xF8, 0x00, 0x32, 0x06, 0xF8, 0x01, 0xC0, 0x00, 0x09]);
+
+            assert_eq!(cpu.D, 0x00);
+            assert_eq!(cycles, 2 + 2 + 3);
+        }
+
+        it "stores via the data pointer" {
+            // LDI 80; PLO R2; SEX R2; LDI 55; STXD; STXD
+            //
+            run_program(&mut cpu, &mut memory, &[0xF8, 0x80, 0xA2, 0xE2, 0xF8, 0x55, 0x73, 0x73]);
+
+            assert_eq!(&memory[0x7F..=0x80], &[0x55, 0x55]);
+            assert_eq!(cpu.R[2], 0x7E);
+        }
+
+        it "switches the program counter, and returns via SEP" {
+            // LDI 08; PLO R3; SEP R3; (03) end
+            // (08) LDI 42; SEP R0
+            //
+            memory[0x08..0x0B].copy_from_slice(&[0xF8, 0x42, 0xD0]);
+            run_program(&mut cpu, &mut memory, &[0xF8, 0x08, 0xA3, 0xD3]);
+
+            assert_eq!((cpu.P, cpu.D), (0, 0x42));
+        }
+
+        it "saves X and P on interrupt, and restores them on RET" {
+            // (10) RET, with the saved T at M(R2)
+            //
+            cpu.R[1] = 0x10;
+            cpu.R[2] = 0x80;
+            cpu.X = 5;
+            memory[0x10] = 0x70;
+
+            assert!(cpu.interrupt());
+            assert_eq!((cpu.P, cpu.X, cpu.T, cpu.IE), (1, 2, 0x50, false));
+
+            memory[0x80] = cpu.T;
+            cpu.step(&mut memory[..]);
+
+            assert_eq!((cpu.P, cpu.X, cpu.IE), (0, 5, true));
+        }
+
+        it "idles until an interrupt" {
+            cpu.R[1] = 0x10;
+            memory[0] = 0x00;
+
+            cpu.step(&mut memory[..]);
+            cpu.step(&mut memory[..]);
+
+            assert!(cpu.is_idle());
+            assert_eq!(cpu.R[0], 1);
+
+            cpu.interrupt();
+
+            assert!(!cpu.is_idle());
+        }
+
+        it "accesses the devices" {
+            // LDI 80; PLO R2; SEX R2; LDI 07; STR R2; OUT 2; B3 0C; LDI 01; (0C)
+            //
+            let program = [0xF8, 0x80, 0xA2, 0xE2, 0xF8, 0x07, 0x52, 0x62, 0x36, 0x0C, 0xF8, 0x01];
+            memory[..program.len()].copy_from_slice(&program);
+            let mut bus = KeypadBus { memory, latched_key: 0, pressed_key: 7 };
+
+            while (cpu.R[0] as usize) < program.len() {
+                cpu.step(&mut bus);
+            }
+
+            assert_eq!(bus.latched_key, 7);
+            assert_eq!(cpu.D, 0x07);
+        }
+    }
+}
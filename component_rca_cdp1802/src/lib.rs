@@ -0,0 +1,8 @@
+#![allow(non_snake_case)]
+
+pub mod cpu;
+
+pub use crate::cpu::{Bus, Cpu as RcaCdp1802};
+
+#[cfg(test)]
+mod cpu_test;
@@ -0,0 +1,332 @@
+// Line-oriented debugger REPL.
+//
+// The emulation is paused while the prompt is shown; `continue` resumes it (paced at the frame
+// rate) until a stop condition is met.
+
+use interfaces_frontend::IoFrontend;
+use system_chip_8::{
+    Chip8, Chip8Error, Debugger, RegisterCondition, StopReason, WatchpointKind, TIMERS_SPEED,
+};
+
+use std::io::{self, BufRead, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const FRAME_TIME_SLICE: Duration = Duration::from_nanos(1_000_000_000 / TIMERS_SPEED as u64);
+const DEFAULT_DUMP_LENGTH: usize = 64;
+
+const HELP: &str = "\
+Commands:
+  s|step [count]            execute one (or <count>) instructions
+  n|next                    step over subroutine calls
+  o|out                     step out of the current subroutine
+  c|continue [frames]       resume the emulation (optionally, for <frames> frames)
+  b|break <addr>            add a PC breakpoint
+  w|watch <addr> [r|w|rw]   add a memory watchpoint (default: rw)
+  when <reg> <op> <value>   add a register condition (e.g. `when V3 == 5`; ops: == != < <= > >=)
+  d|delete <addr>|#<index>  delete a breakpoint/watchpoint, or a condition by index
+  l|list                    list the breakpoints, watchpoints and conditions
+  r|regs                    show the registers
+  stack                     show the stack
+  x <addr> [length]         dump memory
+  q|quit                    terminate the emulation
+Numeric values are hexadecimal (the `0x` prefix is optional).";
+
+pub fn run<T: IoFrontend>(chip8: &mut Chip8<T>) -> Result<(), Chip8Error> {
+    let mut debugger = Debugger::new();
+    let stdin = io::stdin();
+
+    println!("Type `help` for the list of commands.");
+    print_location(chip8);
+
+    loop {
+        print!("(chip8) ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            return Ok(());
+        }
+
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+
+        let (command, args) = match tokens.split_first() {
+            Some((command, args)) => (*command, args),
+            None => continue,
+        };
+
+        let stop_reason = match (command, args) {
+            ("s", _) | ("step", _) => {
+                let count = match args.first().map(|count| count.parse::<u32>()) {
+                    Some(Ok(count)) => count,
+                    Some(Err(_)) => {
+                        println!("Invalid count");
+                        continue;
+                    }
+                    None => 1,
+                };
+
+                let mut stop_reason = StopReason::Step;
+
+                for _ in 0..count {
+                    stop_reason = debugger.step(chip8)?;
+
+                    if stop_reason != StopReason::Step {
+                        break;
+                    }
+                }
+
+                stop_reason
+            }
+            ("n", []) | ("next", []) => {
+                let stop_reason = debugger.step_over(chip8)?;
+                print_unfinished_step(stop_reason);
+                stop_reason
+            }
+            ("o", []) | ("out", []) => {
+                let stop_reason = debugger.step_out(chip8)?;
+                print_unfinished_step(stop_reason);
+                stop_reason
+            }
+            ("c", _) | ("continue", _) => {
+                let frames = match args.first().map(|frames| frames.parse::<u64>()) {
+                    Some(Ok(frames)) => Some(frames),
+                    Some(Err(_)) => {
+                        println!("Invalid frames count");
+                        continue;
+                    }
+                    None => None,
+                };
+
+                resume(&mut debugger, chip8, frames)?
+            }
+            ("b", [address]) | ("break", [address]) => {
+                if let Some(address) = parse_hex(address) {
+                    debugger.add_breakpoint(address);
+                }
+                continue;
+            }
+            ("w", [address, ..]) | ("watch", [address, ..]) => {
+                let kind = match args.get(1).copied() {
+                    Some("r") => WatchpointKind::Read,
+                    Some("w") => WatchpointKind::Write,
+                    Some("rw") | None => WatchpointKind::ReadWrite,
+                    Some(_) => {
+                        println!("Invalid watchpoint kind");
+                        continue;
+                    }
+                };
+
+                if let Some(address) = parse_hex(address) {
+                    debugger.add_watchpoint(address, kind);
+                }
+                continue;
+            }
+            ("when", [register, comparison, value]) => {
+                match (register.parse(), comparison.parse(), parse_hex(value)) {
+                    (Ok(register), Ok(comparison), Some(value)) => {
+                        debugger.add_condition(RegisterCondition {
+                            register,
+                            comparison,
+                            value,
+                        });
+                    }
+                    (Err(message), _, _) | (_, Err(message), _) => println!("{}", message),
+                    _ => {}
+                }
+                continue;
+            }
+            ("d", [target]) | ("delete", [target]) => {
+                let deleted = if let Some(index) = target.strip_prefix('#') {
+                    index
+                        .parse()
+                        .is_ok_and(|index| debugger.remove_condition(index))
+                } else if let Some(address) = parse_hex(target) {
+                    // Bitwise, so that both are removed.
+                    //
+                    debugger.remove_breakpoint(address) | debugger.remove_watchpoint(address)
+                } else {
+                    continue;
+                };
+
+                if !deleted {
+                    println!("Not found");
+                }
+                continue;
+            }
+            ("l", []) | ("list", []) => {
+                print_stop_conditions(&debugger);
+                continue;
+            }
+            ("r", []) | ("regs", []) => {
+                print_registers(chip8);
+                continue;
+            }
+            ("stack", []) => {
+                for (level, address) in chip8.stack().iter().enumerate().rev() {
+                    println!("#{:<2} {:04X}", level, address);
+                }
+                continue;
+            }
+            ("x", [address, ..]) => {
+                let length = match args.get(1) {
+                    Some(length) => parse_hex(length),
+                    None => Some(DEFAULT_DUMP_LENGTH),
+                };
+
+                if let (Some(address), Some(length)) = (parse_hex(address), length) {
+                    print_memory(chip8.memory(), address, length);
+                }
+                continue;
+            }
+            ("q", []) | ("quit", []) => return Ok(()),
+            ("h", []) | ("help", []) => {
+                println!("{}", HELP);
+                continue;
+            }
+            _ => {
+                println!("Invalid command; type `help` for the list of commands.");
+                continue;
+            }
+        };
+
+        print_stop_reason(stop_reason);
+
+        if stop_reason == StopReason::Halted {
+            return Ok(());
+        }
+
+        print_location(chip8);
+    }
+}
+
+// Runs the emulation in real time, until a stop condition is met, or the given number of frames
+// has been emulated.
+//
+fn resume<T: IoFrontend>(
+    debugger: &mut Debugger,
+    chip8: &mut Chip8<T>,
+    frames: Option<u64>,
+) -> Result<StopReason, Chip8Error> {
+    let mut last_frame_time = Instant::now();
+    let mut frames_emulated = 0;
+
+    loop {
+        let stop_reason = debugger.run_frame(chip8)?;
+
+        if stop_reason != StopReason::FrameCompleted {
+            return Ok(stop_reason);
+        }
+
+        frames_emulated += 1;
+
+        if Some(frames_emulated) == frames {
+            return Ok(stop_reason);
+        }
+
+        let next_frame_time = last_frame_time + FRAME_TIME_SLICE;
+        let current_time = Instant::now();
+
+        if current_time > next_frame_time {
+            last_frame_time = current_time;
+        } else {
+            thread::sleep(next_frame_time - current_time);
+            last_frame_time = next_frame_time;
+        }
+    }
+}
+
+fn parse_hex(value: &str) -> Option<usize> {
+    let digits = value.trim_start_matches("0x");
+
+    let result = usize::from_str_radix(digits, 16).ok();
+
+    if result.is_none() {
+        println!("Invalid value: {}", value);
+    }
+
+    result
+}
+
+fn print_stop_reason(stop_reason: StopReason) {
+    match stop_reason {
+        StopReason::Step | StopReason::FrameCompleted => {}
+        StopReason::Breakpoint { pc } => println!("Breakpoint at {:04X}", pc),
+        StopReason::Watchpoint { pc, access } => println!(
+            "Watchpoint: {:?} access to {:04X} by the instruction at {:04X}",
+            access.kind, access.address, pc
+        ),
+        StopReason::Condition { pc, condition } => println!(
+            "Condition `{}` met after the instruction at {:04X}",
+            condition, pc
+        ),
+        StopReason::Halted => println!("Emulation terminated"),
+    }
+}
+
+// Step over/out stop at the end of the frame, if the subroutine didn't return.
+//
+fn print_unfinished_step(stop_reason: StopReason) {
+    if stop_reason == StopReason::FrameCompleted {
+        println!("Frame completed before the subroutine returned");
+    }
+}
+
+fn print_location<T: IoFrontend>(chip8: &Chip8<T>) {
+    let pc = chip8.registers().PC;
+    let memory = chip8.memory();
+
+    match (memory.get(pc), memory.get(pc + 1)) {
+        (Some(hi_byte), Some(lo_byte)) => println!("{:04X}: {:02X}{:02X}", pc, hi_byte, lo_byte),
+        _ => println!("{:04X}: <out of bounds>", pc),
+    }
+}
+
+fn print_registers<T: IoFrontend>(chip8: &Chip8<T>) {
+    let registers = chip8.registers();
+
+    for (i, value) in registers.V.iter().enumerate() {
+        print!(
+            "V{:X}={:02X}{}",
+            i,
+            value,
+            if i % 8 == 7 { "\n" } else { " " }
+        );
+    }
+
+    println!(
+        "I={:04X} PC={:04X} SP={:X} DT={:02X} ST={:02X}",
+        registers.I, registers.PC, registers.SP, registers.delay_timer, registers.sound_timer
+    );
+}
+
+fn print_memory(memory: &[u8], address: usize, length: usize) {
+    let end_address = (address + length).min(memory.len());
+
+    for line_address in (address..end_address).step_by(16) {
+        let line_end_address = (line_address + 16).min(end_address);
+
+        let formatted_bytes = memory[line_address..line_end_address]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        println!("{:04X}: {}", line_address, formatted_bytes);
+    }
+}
+
+fn print_stop_conditions(debugger: &Debugger) {
+    for address in debugger.breakpoints() {
+        println!("Breakpoint {:04X}", address);
+    }
+
+    for (address, kind) in debugger.watchpoints() {
+        println!("Watchpoint {:04X} ({:?})", address, kind);
+    }
+
+    for (index, condition) in debugger.conditions().iter().enumerate() {
+        println!("Condition #{}: {}", index, condition);
+    }
+}
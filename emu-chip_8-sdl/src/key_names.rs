@@ -0,0 +1,77 @@
+// Host key names, as used by the ROM database key mappings.
+
+use interfaces_frontend::events::EventCode;
+
+pub fn host_key_event(name: &str) -> Option<EventCode> {
+    let event_code = match name {
+        "A" => EventCode::KeyA,
+        "B" => EventCode::KeyB,
+        "C" => EventCode::KeyC,
+        "D" => EventCode::KeyD,
+        "E" => EventCode::KeyE,
+        "F" => EventCode::KeyF,
+        "G" => EventCode::KeyG,
+        "H" => EventCode::KeyH,
+        "I" => EventCode::KeyI,
+        "J" => EventCode::KeyJ,
+        "K" => EventCode::KeyK,
+        "L" => EventCode::KeyL,
+        "M" => EventCode::KeyM,
+        "N" => EventCode::KeyN,
+        "O" => EventCode::KeyO,
+        "P" => EventCode::KeyP,
+        "Q" => EventCode::KeyQ,
+        "R" => EventCode::KeyR,
+        "S" => EventCode::KeyS,
+        "T" => EventCode::KeyT,
+        "U" => EventCode::KeyU,
+        "V" => EventCode::KeyV,
+        "W" => EventCode::KeyW,
+        "X" => EventCode::KeyX,
+        "Y" => EventCode::KeyY,
+        "Z" => EventCode::KeyZ,
+        "0" => EventCode::KeyNum0,
+        "1" => EventCode::KeyNum1,
+        "2" => EventCode::KeyNum2,
+        "3" => EventCode::KeyNum3,
+        "4" => EventCode::KeyNum4,
+        "5" => EventCode::KeyNum5,
+        "6" => EventCode::KeyNum6,
+        "7" => EventCode::KeyNum7,
+        "8" => EventCode::KeyNum8,
+        "9" => EventCode::KeyNum9,
+        "Up" => EventCode::KeyUp,
+        "Down" => EventCode::KeyDown,
+        "Left" => EventCode::KeyLeft,
+        "Right" => EventCode::KeyRight,
+        "Space" => EventCode::KeySpace,
+        "Return" => EventCode::KeyReturn,
+        _ => return None,
+    };
+
+    Some(event_code)
+}
+
+// The inverse of the system keypad mapping (keys 0-9 and A-F).
+//
+pub fn keypad_key_event(key: u8) -> EventCode {
+    match key {
+        0x0 => EventCode::KeyNum0,
+        0x1 => EventCode::KeyNum1,
+        0x2 => EventCode::KeyNum2,
+        0x3 => EventCode::KeyNum3,
+        0x4 => EventCode::KeyNum4,
+        0x5 => EventCode::KeyNum5,
+        0x6 => EventCode::KeyNum6,
+        0x7 => EventCode::KeyNum7,
+        0x8 => EventCode::KeyNum8,
+        0x9 => EventCode::KeyNum9,
+        0xA => EventCode::KeyA,
+        0xB => EventCode::KeyB,
+        0xC => EventCode::KeyC,
+        0xD => EventCode::KeyD,
+        0xE => EventCode::KeyE,
+        0xF => EventCode::KeyF,
+        _ => panic!("Invalid keypad key: {}", key),
+    }
+}
@@ -1,46 +1,303 @@
 #[macro_use]
 extern crate maplit;
 
+mod debugger_repl;
+mod key_names;
+mod rpl_flags_storage;
+
 use clap::{self, App, Arg};
 
-use system_chip_8::Chip8;
-use interfaces_frontend::{events::EventCode, logging::Logger, logging::StdoutLogger};
 use frontend_sdl::FrontendSdl;
+use interfaces_frontend::channel::channel_frontend;
+use interfaces_frontend::{events::EventCode, logging::StdoutLogger};
+use system_chip_8::{
+    BinaryTraceWriter, Chip8, LineTraceWriter, LogTracer, Movie, Quirks, QuirksProfile,
+    RewindConfig, RomDatabase, Speed, Timing, Tracer, DEFAULT_CLOCK_SPEED, DEFAULT_HASH_INTERVAL,
+    TIMERS_SPEED,
+};
+use tools_chip_8::cartridge::load_cartridge;
+
+use key_names::{host_key_event, keypad_key_event};
+use rpl_flags_storage::FileRplFlagsStorage;
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use std::fs;
+// How long the main thread waits for the emulation output, before polling the SDL events.
+//
+const FRONTEND_POLL_TIMEOUT: Duration = Duration::from_millis(5);
+
+struct CommandlineOptions {
+    game_rom_filename: String,
+    debug_mode: bool,
+    speed: Speed,
+    clock_speed: Option<u32>,
+    timing: Timing,
+    hybrid: bool,
+    debugger: bool,
+    quirks_profile: Option<QuirksProfile>,
+    seed: u64,
+    record_movie_filename: Option<String>,
+    play_movie_filename: Option<String>,
+    rpl_flags_directory: Option<PathBuf>,
+    trace_filename: Option<String>,
+    binary_trace_filename: Option<String>,
+    profile_filename: Option<String>,
+    profile_folded_filename: Option<String>,
+}
 
-fn decode_commandline_arguments() -> (String, bool, bool) {
+fn decode_commandline_arguments() -> CommandlineOptions {
     let commandline_args = std::env::args().collect::<Vec<String>>();
 
+    let clock_help = format!(
+        "Clock speed, in instructions per second [default: {}]",
+        DEFAULT_CLOCK_SPEED
+    );
+    let ipf_help = format!(
+        "Clock speed, in instructions per frame ({} frames per second)",
+        TIMERS_SPEED
+    );
+
     let matches = App::new("chip8")
-        .arg(Arg::with_name("GAME_ROM").required(true).index(1))
+        .after_help("Hold Backspace to rewind the gameplay.")
+        .arg(
+            Arg::with_name("GAME_ROM")
+                .required(true)
+                .index(1)
+                .help("ROM file, or Octo cartridge (.gif)"),
+        )
         .arg(
             Arg::with_name("DEBUG")
                 .short("d")
                 .long("debug")
-                .help("Enable debug mode (logs to stdout)"),
+                .conflicts_with_all(&["TRACE", "BINARY_TRACE"])
+                .help("Enable debug mode (logs the executed instructions to stdout)"),
         )
         .arg(
             Arg::with_name("MAX_SPEED")
                 .short("m")
                 .long("max-speed")
-                .help("Set the maximum emulation speed (1000x)"),
+                .help("Run at the maximum emulation speed"),
+        )
+        .arg(
+            Arg::with_name("SPEED")
+                .long("speed")
+                .takes_value(true)
+                .conflicts_with("MAX_SPEED")
+                .validator(|value| match value.parse::<f32>() {
+                    Ok(multiplier) if multiplier > 0.0 => Ok(()),
+                    _ => Err("must be a positive number".to_string()),
+                })
+                .help("Emulation speed multiplier (e.g. 2 for fast forward, 0.5 for slow motion)"),
+        )
+        .arg(
+            Arg::with_name("CLOCK")
+                .short("c")
+                .long("clock")
+                .takes_value(true)
+                .validator(validate_positive_integer)
+                .help(&clock_help),
+        )
+        .arg(
+            Arg::with_name("IPF")
+                .long("ipf")
+                .takes_value(true)
+                .conflicts_with("CLOCK")
+                .validator(validate_positive_integer)
+                .help(&ipf_help),
+        )
+        .arg(
+            Arg::with_name("TIMING")
+                .short("t")
+                .long("timing")
+                .takes_value(true)
+                .possible_values(&Timing::NAMES)
+                .default_value("fixed")
+                .help("Instructions timing; cosmac-vip ignores the clock speed"),
+        )
+        .arg(
+            Arg::with_name("HYBRID")
+                .long("hybrid")
+                .help("Execute the 0NNN machine code routines (COSMAC VIP hybrid programs)"),
+        )
+        .arg(
+            Arg::with_name("DEBUGGER")
+                .long("debugger")
+                .conflicts_with("MAX_SPEED")
+                .help("Start paused, in the interactive debugger"),
+        )
+        .arg(
+            Arg::with_name("QUIRKS")
+                .short("q")
+                .long("quirks")
+                .takes_value(true)
+                .possible_values(&QuirksProfile::NAMES)
+                .help("Interpreter whose behavior is emulated [default: per ROM, or original]")
+                .long_help(concat!(
+                    "Interpreter whose behavior is emulated. By default, the ROM settings (Octo ",
+                    "cartridge options, or ROM database) are used; without them, the behavior of ",
+                    "the emulator before the quirks support (see `Quirks::default()`)."
+                )),
+        )
+        .arg(
+            Arg::with_name("SEED")
+                .short("s")
+                .long("seed")
+                .takes_value(true)
+                .validator(|value| value.parse::<u64>().map(|_| ()).map_err(|e| e.to_string()))
+                .help("Random generator seed (default: based on the current time)"),
+        )
+        .arg(
+            Arg::with_name("RECORD_MOVIE")
+                .long("record-movie")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Record the inputs to a movie file"),
+        )
+        .arg(
+            Arg::with_name("PLAY_MOVIE")
+                .long("play-movie")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with_all(&["RECORD_MOVIE", "QUIRKS", "SEED", "CLOCK", "IPF", "TIMING"])
+                .help("Play back a movie file (the movie settings and seed are used)"),
+        )
+        .arg(
+            Arg::with_name("RPL_FLAGS_DIR")
+                .long("rpl-flags-dir")
+                .takes_value(true)
+                .value_name("DIR")
+                .help("Directory of the persistent RPL flags (default: $HOME/.chip8/rpl_flags)"),
+        )
+        .arg(
+            Arg::with_name("TRACE")
+                .long("trace")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with("BINARY_TRACE")
+                .help("Write the executed instructions to a (diffable) text trace file"),
+        )
+        .arg(
+            Arg::with_name("BINARY_TRACE")
+                .long("binary-trace")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Write the executed instructions to a binary trace file"),
+        )
+        .arg(
+            Arg::with_name("PROFILE")
+                .long("profile")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Profile the ROM execution, and write the report on exit"),
+        )
+        .arg(
+            Arg::with_name("PROFILE_FOLDED")
+                .long("profile-folded")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Profile the ROM execution, and write the flamegraph folded stacks on exit"),
         )
         .get_matches_from(commandline_args);
 
     let game_rom_filename = matches.value_of("GAME_ROM").unwrap().to_string();
     let debug_mode = matches.is_present("DEBUG");
-    let max_speed = matches.is_present("MAX_SPEED");
+    let speed = if matches.is_present("MAX_SPEED") {
+        Speed::Unlimited
+    } else {
+        Speed::Multiplier(
+            matches
+                .value_of("SPEED")
+                .map_or(1.0, |value| value.parse().unwrap()),
+        )
+    };
+    let clock_speed = match (matches.value_of("CLOCK"), matches.value_of("IPF")) {
+        (Some(clock_speed), _) => Some(clock_speed.parse().unwrap()),
+        (_, Some(instructions_per_frame)) => Some(
+            instructions_per_frame
+                .parse::<u32>()
+                .unwrap()
+                .saturating_mul(TIMERS_SPEED),
+        ),
+        _ => None,
+    };
+    let timing = matches.value_of("TIMING").unwrap().parse().unwrap();
+    let hybrid = matches.is_present("HYBRID");
+    let debugger = matches.is_present("DEBUGGER");
+    let quirks_profile = matches
+        .value_of("QUIRKS")
+        .map(|profile| profile.parse().unwrap());
+    let seed = match matches.value_of("SEED") {
+        Some(seed) => seed.parse().unwrap(),
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64,
+    };
+
+    CommandlineOptions {
+        game_rom_filename,
+        debug_mode,
+        speed,
+        clock_speed,
+        timing,
+        hybrid,
+        debugger,
+        quirks_profile,
+        seed,
+        record_movie_filename: matches.value_of("RECORD_MOVIE").map(str::to_string),
+        play_movie_filename: matches.value_of("PLAY_MOVIE").map(str::to_string),
+        rpl_flags_directory: matches
+            .value_of("RPL_FLAGS_DIR")
+            .map(PathBuf::from)
+            .or_else(FileRplFlagsStorage::default_directory),
+        trace_filename: matches.value_of("TRACE").map(str::to_string),
+        binary_trace_filename: matches.value_of("BINARY_TRACE").map(str::to_string),
+        profile_filename: matches.value_of("PROFILE").map(str::to_string),
+        profile_folded_filename: matches.value_of("PROFILE_FOLDED").map(str::to_string),
+    }
+}
 
-    (game_rom_filename, debug_mode, max_speed)
+fn validate_positive_integer(value: String) -> Result<(), String> {
+    match value.parse::<u32>() {
+        Ok(value) if value > 0 => Ok(()),
+        _ => Err("must be a positive integer".to_string()),
+    }
+}
+
+fn create_trace_file(filename: &str) -> BufWriter<File> {
+    let file = File::create(filename).unwrap_or_else(|error| exit_with_error(&error));
+    BufWriter::new(file)
+}
+
+fn exit_with_error(error: &dyn std::error::Error) -> ! {
+    eprintln!("Error: {}", error);
+    std::process::exit(1);
 }
 
 fn main() {
-    let (game_rom_filename, debug_mode, max_speed) = decode_commandline_arguments();
+    let options = decode_commandline_arguments();
+
+    let game_file_data = fs::read(&options.game_rom_filename).unwrap();
 
-    let game_rom_data = fs::read(game_rom_filename).unwrap();
+    // Octo cartridges carry their own settings, which take the place of the ROM database ones.
+    //
+    let (game_rom_data, cartridge_config) =
+        if options.game_rom_filename.to_lowercase().ends_with(".gif") {
+            let cartridge =
+                load_cartridge(&game_file_data).unwrap_or_else(|error| exit_with_error(&error));
+            (cartridge.rom, Some(cartridge.config))
+        } else {
+            (game_file_data, None)
+        };
 
-    let custom_keys_mapping = hashmap! {
+    let rom_database = RomDatabase::bundled();
+    let rom_info = rom_database.lookup(&game_rom_data);
+
+    let mut custom_keys_mapping = hashmap! {
          EventCode::KeyNum4 => EventCode::KeyC,
          EventCode::KeyQ => EventCode::KeyNum4,
          EventCode::KeyW => EventCode::KeyNum5,
@@ -56,15 +313,156 @@ fn main() {
          EventCode::KeyV => EventCode::KeyF,
     };
 
-    let mut sdl_frontend = FrontendSdl::new("CHIP-8!", custom_keys_mapping, Some(60));
+    // The ROM mappings are added to the default ones, taking precedence.
+    //
+    for (host_key, keypad_key) in rom_info.iter().flat_map(|rom_info| &rom_info.keys) {
+        match host_key_event(host_key) {
+            Some(event_code) => {
+                custom_keys_mapping.insert(event_code, keypad_key_event(*keypad_key));
+            }
+            None => eprintln!("Warning: unknown ROM database host key: {}", host_key),
+        }
+    }
+
+    let window_title = match rom_info {
+        Some(rom_info) => format!("CHIP-8! - {}", rom_info.title),
+        None => "CHIP-8!".to_string(),
+    };
+
+    // The framerate is capped by the channel frontend, which sends the frames to the SDL one.
+    //
+    let sdl_frontend = FrontendSdl::new(&window_title, custom_keys_mapping, None);
+    let (frontend, mut frontend_host) = channel_frontend(sdl_frontend, Some(TIMERS_SPEED as u8));
 
-    let mut logger: Option<Box<dyn Logger>> = if debug_mode {
-        Some(Box::new(StdoutLogger::new()))
+    let movie = options.play_movie_filename.map(|movie_filename| {
+        let movie_data = fs::read(movie_filename).unwrap_or_else(|error| exit_with_error(&error));
+        Movie::from_bytes(&movie_data).unwrap_or_else(|error| exit_with_error(&error))
+    });
+
+    let (quirks, clock_speed, timing, hybrid, seed) = match &movie {
+        Some(movie) => (
+            movie.quirks,
+            movie.clock_speed,
+            movie.timing,
+            movie.hybrid,
+            movie.seed,
+        ),
+        None => (
+            // The user options take precedence over the ROM settings.
+            //
+            options
+                .quirks_profile
+                .map(Quirks::profile)
+                .or_else(|| cartridge_config.as_ref().map(|config| config.quirks))
+                .or_else(|| rom_info.and_then(|rom_info| rom_info.quirks))
+                .unwrap_or_default(),
+            options
+                .clock_speed
+                .or_else(|| cartridge_config.as_ref().map(|config| config.clock_speed))
+                .or_else(|| rom_info.and_then(|rom_info| rom_info.clock_speed))
+                .unwrap_or(DEFAULT_CLOCK_SPEED),
+            options.timing,
+            options.hybrid,
+            options.seed,
+        ),
+    };
+
+    let mut chip8 = Chip8::new(frontend, &game_rom_data, quirks, clock_speed, seed)
+        .unwrap_or_else(|error| exit_with_error(&error));
+
+    let tracer: Option<Box<dyn Tracer>> = if options.debug_mode {
+        Some(Box::new(LogTracer::new(StdoutLogger::new())))
+    } else if let Some(filename) = &options.trace_filename {
+        let trace_file = create_trace_file(filename);
+        Some(Box::new(LineTraceWriter::new(trace_file)))
+    } else if let Some(filename) = &options.binary_trace_filename {
+        let trace_file = create_trace_file(filename);
+        Some(Box::new(BinaryTraceWriter::new(trace_file)))
     } else {
         None
     };
 
-    let mut chip8 = Chip8::new(&mut sdl_frontend, &game_rom_data, &mut logger);
+    if let Some(tracer) = tracer {
+        chip8.set_tracer(tracer);
+    }
+
+    chip8.set_timing(timing);
+    chip8.set_hybrid_mode(hybrid);
+    chip8
+        .set_speed(options.speed)
+        .unwrap_or_else(|error| exit_with_error(&error));
+
+    let palette = cartridge_config
+        .as_ref()
+        .map(|config| config.palette)
+        .or_else(|| rom_info.and_then(|rom_info| rom_info.palette));
+
+    if let Some(palette) = palette {
+        chip8.set_palette(palette);
+    }
+
+    chip8.enable_rewind(RewindConfig::new(EventCode::KeyBackspace));
+
+    let debugger = options.debugger;
+    let record_movie_filename = options.record_movie_filename;
+    let profile_filename = options.profile_filename;
+    let profile_folded_filename = options.profile_folded_filename;
+
+    if profile_filename.is_some() || profile_folded_filename.is_some() {
+        chip8.start_profiling();
+    }
+
+    if let Some(movie) = movie {
+        chip8
+            .start_movie_playback(movie)
+            .unwrap_or_else(|error| exit_with_error(&error));
+    } else if record_movie_filename.is_some() {
+        chip8
+            .start_movie_recording(DEFAULT_HASH_INTERVAL)
+            .unwrap_or_else(|error| exit_with_error(&error));
+    } else if let Some(rpl_flags_directory) = options.rpl_flags_directory {
+        // Movies don't store the RPL flags, so they always start with the power-on (zero) ones.
+        //
+        chip8.set_rpl_flags_storage(Box::new(FileRplFlagsStorage::new(rpl_flags_directory)));
+    }
+
+    // SDL must stay in the main thread, so the emulation runs in a dedicated one, exchanging the
+    // frames/events with the main thread via the channel frontend.
+    //
+    let emulation_thread = thread::spawn(move || {
+        let result = if debugger {
+            debugger_repl::run(&mut chip8)
+        } else {
+            chip8.run()
+        };
+
+        // Save the movie and the profile also on error, since they may be useful to investigate it.
+        //
+        if let Some(movie_filename) = record_movie_filename {
+            if let Some(movie) = chip8.stop_movie_recording() {
+                fs::write(movie_filename, movie.to_bytes())
+                    .unwrap_or_else(|error| exit_with_error(&error));
+            }
+        }
+
+        if let Some(profile) = chip8.stop_profiling() {
+            if let Some(profile_filename) = profile_filename {
+                fs::write(profile_filename, profile.text_report())
+                    .unwrap_or_else(|error| exit_with_error(&error));
+            }
+
+            if let Some(profile_folded_filename) = profile_folded_filename {
+                fs::write(profile_folded_filename, profile.folded_stacks())
+                    .unwrap_or_else(|error| exit_with_error(&error));
+            }
+        }
+
+        result
+    });
+
+    while frontend_host.process(FRONTEND_POLL_TIMEOUT) {}
 
-    chip8.run(max_speed);
+    if let Err(error) = emulation_thread.join().unwrap() {
+        exit_with_error(&error);
+    }
 }
@@ -0,0 +1,65 @@
+// File-backed RPL flags storage: one file per ROM, named after the ROM SHA-1 (hex), in a directory.
+//
+// Failures are reported on stderr, without interrupting the emulation; in the worst case, the
+// flags are not persisted.
+
+use system_chip_8::RplFlagsStorage;
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+pub struct FileRplFlagsStorage {
+    directory: PathBuf,
+}
+
+impl FileRplFlagsStorage {
+    pub fn new(directory: PathBuf) -> FileRplFlagsStorage {
+        FileRplFlagsStorage { directory }
+    }
+
+    /// `$HOME/.chip8/rpl_flags`; None if there is no home directory.
+    ///
+    pub fn default_directory() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".chip8").join("rpl_flags"))
+    }
+
+    fn file_path(&self, rom_hash: &[u8; 20]) -> PathBuf {
+        let filename = rom_hash
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+
+        self.directory.join(filename)
+    }
+}
+
+impl RplFlagsStorage for FileRplFlagsStorage {
+    fn load(&mut self, rom_hash: &[u8; 20]) -> Option<Vec<u8>> {
+        let file_path = self.file_path(rom_hash);
+
+        match fs::read(&file_path) {
+            Ok(flags) => Some(flags),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => {
+                eprintln!("Error reading the RPL flags ({:?}): {}", file_path, error);
+                None
+            }
+        }
+    }
+
+    // The file is replaced atomically, so that an interruption doesn't leave corrupted flags.
+    //
+    fn store(&mut self, rom_hash: &[u8; 20], flags: &[u8]) {
+        let file_path = self.file_path(rom_hash);
+        let temp_file_path = file_path.with_extension("tmp");
+
+        let result = fs::create_dir_all(&self.directory)
+            .and_then(|_| fs::write(&temp_file_path, flags))
+            .and_then(|_| fs::rename(&temp_file_path, &file_path));
+
+        if let Err(error) = result {
+            eprintln!("Error writing the RPL flags ({:?}): {}", file_path, error);
+        }
+    }
+}
@@ -5,7 +5,7 @@ use sdl2::{
 };
 
 struct SimpleCallback {
-    generator: fn(sample_i: u32) -> i16,
+    generator: Box<dyn FnMut(u32) -> i16 + Send>,
     sample_i: u32,
 }
 
@@ -27,14 +27,14 @@ impl AudioCallback for SimpleCallback {
     }
 }
 
-pub(crate) struct AudioDeviceSdl {
+pub struct AudioDeviceSdl {
     audio_device: sdl2::audio::AudioDevice<SimpleCallback>,
 }
 
 impl AudioDeviceSdl {
-    pub fn new(
+    pub(crate) fn new(
         audio_subsystem: &AudioSubsystem,
-        generator: fn(sample_i: u32) -> i16,
+        generator: Box<dyn FnMut(u32) -> i16 + Send>,
     ) -> AudioDeviceSdl {
         let audio_spec = AudioSpecDesired {
             freq: Some(AUDIO_DEVICE_FREQUENCY as i32),
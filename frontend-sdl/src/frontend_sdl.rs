@@ -1,4 +1,3 @@
-use interfaces_frontend::audio::AudioDevice as FrontendAudioDevice;
 use interfaces_frontend::{events::EventCode, video::Pixel, IoFrontend};
 
 use crate::audio_device_sdl::AudioDeviceSdl;
@@ -321,6 +320,8 @@ impl FrontendSdl {
 }
 
 impl IoFrontend for FrontendSdl {
+    type Audio = AudioDeviceSdl;
+
     fn init(&mut self, screen_width: u32, screen_height: u32) {
         self.canvas
             .set_logical_size(screen_width, screen_height)
@@ -353,13 +354,8 @@ impl IoFrontend for FrontendSdl {
         }
     }
 
-    fn audio_device(
-        &mut self,
-        generator: fn(sample_i: u32) -> i16,
-    ) -> Box<dyn FrontendAudioDevice> {
-        let audio_device = AudioDeviceSdl::new(&self.audio_subsystem, generator);
-
-        Box::new(audio_device)
+    fn audio_device(&mut self, generator: Box<dyn FnMut(u32) -> i16 + Send>) -> AudioDeviceSdl {
+        AudioDeviceSdl::new(&self.audio_subsystem, generator)
     }
 
     fn read_event(&mut self, blocking: bool) -> Option<(EventCode, bool)> {
@@ -1,4 +1,5 @@
 mod audio_device_sdl;
 mod frontend_sdl;
 
+pub use crate::audio_device_sdl::AudioDeviceSdl;
 pub use crate::frontend_sdl::FrontendSdl;
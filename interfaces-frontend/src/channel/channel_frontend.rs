@@ -0,0 +1,220 @@
+use crate::audio::AudioDevice;
+use crate::events::EventCode;
+use crate::video::Pixel;
+use crate::IoFrontend;
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+
+enum HostMessage {
+    Init(u32, u32),
+    UpdateScreen(Vec<Pixel>),
+    CreateAudioDevice(Box<dyn FnMut(u32) -> i16 + Send>),
+    PlayAudio(usize),
+    PauseAudio(usize),
+}
+
+/// Emulation side of the split frontend; passed to the platform library, in the emulation thread.
+///
+/// The frames are throttled here (rather than by the host frontend), so that the channel is not
+/// flooded when the platform library updates the screen on each instruction.
+///
+pub struct ChannelFrontend {
+    messages: Sender<HostMessage>,
+    events: Receiver<(EventCode, bool)>,
+    host_disconnected: bool,
+
+    audio_devices_count: usize,
+
+    last_frame: Vec<Pixel>,
+    last_screen_update: Instant,
+    min_time_between_screen_updates: Duration,
+}
+
+/// Audio device of the `ChannelFrontend`; relays the play/pause requests to the host.
+///
+pub struct ChannelAudioDevice {
+    messages: Sender<HostMessage>,
+    id: usize,
+}
+
+/// Host side of the split frontend; drives the actual frontend, in the thread owning it (e.g. the
+/// main thread, for SDL).
+///
+pub struct ChannelFrontendHost<F: IoFrontend> {
+    frontend: F,
+    audio_devices: Vec<F::Audio>,
+
+    messages: Receiver<HostMessage>,
+    events: Sender<(EventCode, bool)>,
+}
+
+/// Splits the frontend into the emulation and the host sides.
+///
+/// # Arguments
+///
+/// * `frontend` - actual frontend, owned by the host side.
+/// * `framerate_cap` - maximum frames per second sent to the host; unlimited if None.
+///
+pub fn channel_frontend<F: IoFrontend>(
+    frontend: F,
+    framerate_cap: Option<u8>,
+) -> (ChannelFrontend, ChannelFrontendHost<F>) {
+    let (messages_sender, messages_receiver) = mpsc::channel();
+    let (events_sender, events_receiver) = mpsc::channel();
+
+    let min_time_between_screen_updates = match framerate_cap {
+        None => Duration::from_secs(0),
+        Some(frequency) => Duration::from_nanos(1_000_000_000 / frequency as u64),
+    };
+
+    let channel_frontend = ChannelFrontend {
+        messages: messages_sender,
+        events: events_receiver,
+        host_disconnected: false,
+        audio_devices_count: 0,
+        last_frame: vec![],
+        last_screen_update: Instant::now(),
+        min_time_between_screen_updates,
+    };
+
+    let host = ChannelFrontendHost {
+        frontend,
+        audio_devices: vec![],
+        messages: messages_receiver,
+        events: events_sender,
+    };
+
+    (channel_frontend, host)
+}
+
+impl ChannelFrontend {
+    // If the host is gone, there's no one to display the output, so the send errors are ignored;
+    // the emulation is terminated via a Quit event (see `read_event()`).
+    //
+    fn send(&self, message: HostMessage) {
+        let _ = self.messages.send(message);
+    }
+}
+
+impl IoFrontend for ChannelFrontend {
+    type Audio = ChannelAudioDevice;
+
+    fn init(&mut self, screen_width: u32, screen_height: u32) {
+        self.last_frame.clear();
+        self.send(HostMessage::Init(screen_width, screen_height));
+    }
+
+    // Unchanged frames are not sent; since the platform library keeps updating the screen, a frame
+    // skipped due to the cap is sent on a later update.
+    //
+    fn update_screen(&mut self, pixels: &[Pixel], force_update: bool) {
+        let time_from_last_update = self.last_screen_update.elapsed();
+
+        if time_from_last_update >= self.min_time_between_screen_updates || force_update {
+            if pixels != &self.last_frame[..] {
+                self.last_frame = pixels.to_vec();
+                self.send(HostMessage::UpdateScreen(self.last_frame.clone()));
+            }
+
+            self.last_screen_update = Instant::now();
+        }
+    }
+
+    fn audio_device(&mut self, generator: Box<dyn FnMut(u32) -> i16 + Send>) -> ChannelAudioDevice {
+        let id = self.audio_devices_count;
+
+        self.audio_devices_count += 1;
+        self.send(HostMessage::CreateAudioDevice(generator));
+
+        ChannelAudioDevice {
+            messages: self.messages.clone(),
+            id,
+        }
+    }
+
+    // When the host disconnects, a (single) Quit event is returned.
+    //
+    fn read_event(&mut self, blocking: bool) -> Option<(EventCode, bool)> {
+        if self.host_disconnected {
+            return None;
+        }
+
+        let event = if blocking {
+            self.events.recv().ok()
+        } else {
+            match self.events.try_recv() {
+                Ok(event) => Some(event),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => None,
+            }
+        };
+
+        if event.is_none() {
+            self.host_disconnected = true;
+            return Some((EventCode::Quit, true));
+        }
+
+        event
+    }
+}
+
+impl AudioDevice for ChannelAudioDevice {
+    fn play(&mut self) {
+        let _ = self.messages.send(HostMessage::PlayAudio(self.id));
+    }
+
+    fn pause(&mut self) {
+        let _ = self.messages.send(HostMessage::PauseAudio(self.id));
+    }
+}
+
+impl<F: IoFrontend> ChannelFrontendHost<F> {
+    /// Applies the requests of the emulation side to the frontend, waiting up to `timeout` for the
+    /// first one, then relays the frontend events.
+    ///
+    /// Returns false once the emulation side has been dropped (e.g. the emulation thread ended).
+    ///
+    pub fn process(&mut self, timeout: Duration) -> bool {
+        match self.messages.recv_timeout(timeout) {
+            Ok(message) => self.handle_message(message),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return false,
+        }
+
+        loop {
+            match self.messages.try_recv() {
+                Ok(message) => self.handle_message(message),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+
+        while let Some(event) = self.frontend.read_event(false) {
+            let _ = self.events.send(event);
+        }
+
+        true
+    }
+
+    pub fn frontend(&mut self) -> &mut F {
+        &mut self.frontend
+    }
+
+    // Frames are already throttled on the emulation side, so they're always displayed.
+    //
+    fn handle_message(&mut self, message: HostMessage) {
+        match message {
+            HostMessage::Init(screen_width, screen_height) => {
+                self.frontend.init(screen_width, screen_height)
+            }
+            HostMessage::UpdateScreen(pixels) => self.frontend.update_screen(&pixels, true),
+            HostMessage::CreateAudioDevice(generator) => {
+                let audio_device = self.frontend.audio_device(generator);
+                self.audio_devices.push(audio_device);
+            }
+            HostMessage::PlayAudio(id) => self.audio_devices[id].play(),
+            HostMessage::PauseAudio(id) => self.audio_devices[id].pause(),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use super::channel_frontend;
+use crate::audio::AudioDevice;
+use crate::events::EventCode;
+use crate::video::Pixel;
+use crate::IoFrontend;
+
+use demonstrate::demonstrate;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+#[derive(Default)]
+struct RecordingFrontend {
+    screen_sizes: Vec<(u32, u32)>,
+    frames: Vec<Vec<Pixel>>,
+    audio_log: Rc<RefCell<Vec<&'static str>>>,
+    pending_events: Vec<(EventCode, bool)>,
+}
+
+struct RecordingAudioDevice {
+    audio_log: Rc<RefCell<Vec<&'static str>>>,
+}
+
+impl IoFrontend for RecordingFrontend {
+    type Audio = RecordingAudioDevice;
+
+    fn init(&mut self, screen_width: u32, screen_height: u32) {
+        self.screen_sizes.push((screen_width, screen_height));
+    }
+
+    fn update_screen(&mut self, pixels: &[Pixel], _force_update: bool) {
+        self.frames.push(pixels.to_vec());
+    }
+
+    fn audio_device(&mut self, _generator: Box<dyn FnMut(u32) -> i16 + Send>) -> Self::Audio {
+        RecordingAudioDevice {
+            audio_log: self.audio_log.clone(),
+        }
+    }
+
+    fn read_event(&mut self, _blocking: bool) -> Option<(EventCode, bool)> {
+        self.pending_events.pop()
+    }
+}
+
+impl AudioDevice for RecordingAudioDevice {
+    fn play(&mut self) {
+        self.audio_log.borrow_mut().push("play");
+    }
+
+    fn pause(&mut self) {
+        self.audio_log.borrow_mut().push("pause");
+    }
+}
+
+demonstrate! {
+    describe "split frontend" {
+        use super::*;
+
+        it "relays the output to the host, and the events to the emulation side" {
+            let (mut frontend, mut host) = channel_frontend(RecordingFrontend::default(), None);
+
+            frontend.init(64, 32);
+            frontend.update_screen(&[Pixel(1, 2, 3)], false);
+            frontend.update_screen(&[Pixel(1, 2, 3)], true);
+            frontend.audio_device(Box::new(|_| 0)).play();
+
+            host.frontend().pending_events.push((EventCode::KeyA, true));
+
+            assert!(host.process(Duration::from_secs(0)));
+
+            let host_frontend = host.frontend();
+
+            assert_eq!(host_frontend.screen_sizes, vec![(64, 32)]);
+            // Unchanged frames are not sent.
+            //
+            assert_eq!(host_frontend.frames.len(), 1);
+            assert!(host_frontend.frames[0] == vec![Pixel(1, 2, 3)]);
+            assert_eq!(*host_frontend.audio_log.borrow(), vec!["play"]);
+
+            assert!(frontend.read_event(false) == Some((EventCode::KeyA, true)));
+            assert!(frontend.read_event(false).is_none());
+        }
+
+        it "quits when the host is dropped, and stops when the emulation side is dropped" {
+            let (mut frontend, host) = channel_frontend(RecordingFrontend::default(), None);
+
+            drop(host);
+
+            assert!(frontend.read_event(false) == Some((EventCode::Quit, true)));
+            assert!(frontend.read_event(false).is_none());
+
+            let (frontend, mut host) = channel_frontend(RecordingFrontend::default(), None);
+
+            drop(frontend);
+
+            assert!(!host.process(Duration::from_secs(0)));
+        }
+    }
+}
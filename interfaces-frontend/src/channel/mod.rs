@@ -0,0 +1,12 @@
+// Frontend split across two threads, exchanging frames/audio requests and input events over
+// channels: the platform library runs in a dedicated emulation thread, while the actual frontend
+// stays in the thread owning it (typically the main thread, which SDL requires).
+
+mod channel_frontend;
+
+pub use channel_frontend::{
+    channel_frontend, ChannelAudioDevice, ChannelFrontend, ChannelFrontendHost,
+};
+
+#[cfg(test)]
+mod channel_frontend_test;
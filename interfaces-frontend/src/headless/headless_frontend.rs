@@ -0,0 +1,114 @@
+use crate::audio::AudioDevice;
+use crate::events::EventCode;
+use crate::video::Pixel;
+use crate::IoFrontend;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioTransition {
+    Play,
+    Pause,
+}
+
+/// In-memory implementation of IoFrontend, for tests and tools.
+///
+/// The events are read from a queue, filled by the user; the frames are captured (consecutive
+/// identical ones only once, since the platform libraries may update the screen on each
+/// instruction), as well as the audio transitions.
+///
+#[derive(Default)]
+pub struct HeadlessFrontend {
+    screen_size: Option<(u32, u32)>,
+    frames: Vec<Vec<Pixel>>,
+    events: VecDeque<(EventCode, bool)>,
+    audio_transitions: Arc<Mutex<Vec<AudioTransition>>>,
+}
+
+/// Audio device of the HeadlessFrontend; records the transitions in the frontend.
+///
+pub struct HeadlessAudioDevice {
+    audio_transitions: Arc<Mutex<Vec<AudioTransition>>>,
+}
+
+impl HeadlessFrontend {
+    pub fn new() -> HeadlessFrontend {
+        HeadlessFrontend::default()
+    }
+
+    /// Queues a key event; `pressed` is false for releases.
+    ///
+    pub fn push_event(&mut self, event_code: EventCode, pressed: bool) {
+        self.events.push_back((event_code, pressed));
+    }
+
+    pub fn pending_events(&self) -> usize {
+        self.events.len()
+    }
+
+    /// None until the platform library initializes the screen.
+    ///
+    pub fn screen_size(&self) -> Option<(u32, u32)> {
+        self.screen_size
+    }
+
+    pub fn frames(&self) -> &[Vec<Pixel>] {
+        &self.frames
+    }
+
+    /// Latest frame; empty if none has been received.
+    ///
+    pub fn last_frame(&self) -> &[Pixel] {
+        self.frames.last().map_or(&[], |frame| &frame[..])
+    }
+
+    pub fn audio_transitions(&self) -> Vec<AudioTransition> {
+        self.audio_transitions.lock().unwrap().clone()
+    }
+}
+
+impl IoFrontend for HeadlessFrontend {
+    type Audio = HeadlessAudioDevice;
+
+    fn init(&mut self, screen_width: u32, screen_height: u32) {
+        self.screen_size = Some((screen_width, screen_height));
+    }
+
+    fn update_screen(&mut self, pixels: &[Pixel], _force_update: bool) {
+        if self.last_frame() != pixels {
+            self.frames.push(pixels.to_vec());
+        }
+    }
+
+    fn audio_device(&mut self, _generator: Box<dyn FnMut(u32) -> i16 + Send>) -> Self::Audio {
+        HeadlessAudioDevice {
+            audio_transitions: self.audio_transitions.clone(),
+        }
+    }
+
+    // A blocking read on an empty queue would never complete, so a Quit event is returned instead.
+    //
+    fn read_event(&mut self, blocking: bool) -> Option<(EventCode, bool)> {
+        match self.events.pop_front() {
+            None if blocking => Some((EventCode::Quit, true)),
+            event => event,
+        }
+    }
+}
+
+impl AudioDevice for HeadlessAudioDevice {
+    fn play(&mut self) {
+        self.audio_transitions
+            .lock()
+            .unwrap()
+            .push(AudioTransition::Play);
+    }
+
+    fn pause(&mut self) {
+        self.audio_transitions
+            .lock()
+            .unwrap()
+            .push(AudioTransition::Pause);
+    }
+}
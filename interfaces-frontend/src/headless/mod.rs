@@ -0,0 +1,3 @@
+mod headless_frontend;
+
+pub use headless_frontend::{AudioTransition, HeadlessAudioDevice, HeadlessFrontend};
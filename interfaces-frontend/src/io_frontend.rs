@@ -4,10 +4,16 @@ use crate::video::Pixel;
 
 /// IoFrontend represent the user-facing interface: audio, video and events.
 ///
-/// The desired trait object is instantiated (eg. SDL, testing, ...) and passed to the platform
-/// library, which will use to receive events, and to render audio/video.
+/// The desired implementor is instantiated (eg. SDL, testing, ...) and passed to the platform
+/// library, which will use to receive events, and to render audio/video. The library can either own
+/// it, or borrow it (see the implementation for `&mut F`).
 ///
 pub trait IoFrontend {
+    /// The audio device is a type parameter rather than a trait object, so that the platform
+    /// library is `Send` whenever the frontend and its audio device are.
+    ///
+    type Audio: AudioDevice;
+
     fn init(&mut self, screen_width: u32, screen_height: u32);
 
     /// Requests a screen update to the implementor.
@@ -25,9 +31,11 @@ pub trait IoFrontend {
     /// # Arguments/Return value
     ///
     /// * `generator` - wave generator function; sample_i represent the index of the sample over
-    ///   time; it increments monotonically with a step of 1; returns the amplitude.
+    ///   time; it increments monotonically with a step of 1; returns the amplitude. It can hold
+    ///   state (e.g. a waveform shared with the platform library), and it's invoked from the
+    ///   implementor audio thread, if any.
     ///
-    fn audio_device(&mut self, generator: fn(sample_i: u32) -> i16) -> Box<dyn AudioDevice>;
+    fn audio_device(&mut self, generator: Box<dyn FnMut(u32) -> i16 + Send>) -> Self::Audio;
 
     /// Read an event.
     ///
@@ -43,3 +51,23 @@ pub trait IoFrontend {
     ///
     fn read_event(&mut self, blocking: bool) -> Option<(EventCode, bool)>;
 }
+
+impl<F: IoFrontend + ?Sized> IoFrontend for &mut F {
+    type Audio = F::Audio;
+
+    fn init(&mut self, screen_width: u32, screen_height: u32) {
+        (**self).init(screen_width, screen_height)
+    }
+
+    fn update_screen(&mut self, pixels: &[Pixel], force_update: bool) {
+        (**self).update_screen(pixels, force_update)
+    }
+
+    fn audio_device(&mut self, generator: Box<dyn FnMut(u32) -> i16 + Send>) -> Self::Audio {
+        (**self).audio_device(generator)
+    }
+
+    fn read_event(&mut self, blocking: bool) -> Option<(EventCode, bool)> {
+        (**self).read_event(blocking)
+    }
+}
@@ -6,7 +6,9 @@
 mod io_frontend;
 
 pub mod audio;
+pub mod channel;
 pub mod events;
+pub mod headless;
 pub mod logging;
 pub mod video;
 
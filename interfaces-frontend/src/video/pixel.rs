@@ -6,7 +6,7 @@ use std::ops;
 /// reasons, it's advised for the platform libraries to use this format internally, as converting
 /// every frame is relatively expensive.
 ///
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Copy, PartialEq)]
 pub struct Pixel(pub u8, pub u8, pub u8);
 
 impl ops::BitXorAssign<Pixel> for Pixel {
@@ -0,0 +1,85 @@
+// Shared by the fuzz targets: runs a ROM against the headless frontend, for a bounded number of
+// frames.
+//
+// Errors are the documented way for a machine to reject a program, so they just end the run; any
+// panic is a finding.
+
+use interfaces_frontend::{events::EventCode, headless::HeadlessFrontend};
+use system_chip_8::{Chip8, Chip8Error, Quirks, QuirksProfile, DEFAULT_CLOCK_SPEED};
+
+pub const PROFILES: [QuirksProfile; 4] = [
+    QuirksProfile::CosmacVip,
+    QuirksProfile::Chip48,
+    QuirksProfile::SuperChip,
+    QuirksProfile::XoChip,
+];
+
+// Half a second of emulated time; enough to get through the typical initialization code, while
+// keeping the executions fast.
+//
+pub const FRAMES: u8 = 30;
+
+const SEED: u64 = 0;
+
+/// Frame at which the key event is sent, keypad key (low nibble), and pressed/released.
+///
+pub type KeyEvent = (u8, u8, bool);
+
+pub fn run_rom(rom: &[u8], quirks: Quirks, hybrid_mode: bool, key_events: &[KeyEvent]) {
+    let mut chip8 = match Chip8::new(
+        HeadlessFrontend::new(),
+        rom,
+        quirks,
+        DEFAULT_CLOCK_SPEED,
+        SEED,
+    ) {
+        Ok(chip8) => chip8,
+        Err(Chip8Error::RomTooLarge { .. }) => return,
+        Err(error) => panic!("Unexpected construction error: {}", error),
+    };
+
+    chip8.set_hybrid_mode(hybrid_mode);
+
+    for frame in 0..FRAMES {
+        for (_, key, pressed) in key_events
+            .iter()
+            .filter(|(key_frame, ..)| *key_frame == frame)
+        {
+            chip8
+                .io_frontend_mut()
+                .push_event(keypad_key_event(*key), *pressed);
+        }
+
+        if let Err(error) = chip8.run_frame() {
+            // There is no movie, so it can't desync.
+            //
+            assert!(
+                !matches!(error, Chip8Error::MovieDesync { .. }),
+                "Unexpected error: {}",
+                error
+            );
+            return;
+        }
+    }
+}
+
+fn keypad_key_event(key: u8) -> EventCode {
+    match key & 0x0F {
+        0x0 => EventCode::KeyNum0,
+        0x1 => EventCode::KeyNum1,
+        0x2 => EventCode::KeyNum2,
+        0x3 => EventCode::KeyNum3,
+        0x4 => EventCode::KeyNum4,
+        0x5 => EventCode::KeyNum5,
+        0x6 => EventCode::KeyNum6,
+        0x7 => EventCode::KeyNum7,
+        0x8 => EventCode::KeyNum8,
+        0x9 => EventCode::KeyNum9,
+        0xA => EventCode::KeyA,
+        0xB => EventCode::KeyB,
+        0xC => EventCode::KeyC,
+        0xD => EventCode::KeyD,
+        0xE => EventCode::KeyE,
+        _ => EventCode::KeyF,
+    }
+}
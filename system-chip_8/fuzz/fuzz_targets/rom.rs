@@ -0,0 +1,15 @@
+// Arbitrary ROM bytes, run with each quirks profile, without inputs.
+
+#![no_main]
+
+mod common;
+
+use common::{run_rom, PROFILES};
+use libfuzzer_sys::fuzz_target;
+use system_chip_8::Quirks;
+
+fuzz_target!(|rom: &[u8]| {
+    for profile in PROFILES.iter() {
+        run_rom(rom, Quirks::profile(*profile), false, &[]);
+    }
+});
@@ -0,0 +1,40 @@
+// Arbitrary ROM bytes, along with the machine configuration and a key events sequence.
+//
+// Input layout: quirks profile, hybrid mode, key events count (up to MAX_KEY_EVENTS), key events
+// (frame, key, pressed), then the ROM (all the remaining bytes). This keeps the ROM contiguous,
+// so that ROM files prefixed by a short header are effective seeds.
+
+#![no_main]
+
+mod common;
+
+use common::{run_rom, KeyEvent, PROFILES};
+use libfuzzer_sys::arbitrary::{self, Unstructured};
+use libfuzzer_sys::fuzz_target;
+use system_chip_8::{Quirks, QuirksProfile};
+
+const MAX_KEY_EVENTS: u8 = 32;
+
+fuzz_target!(|data: &[u8]| {
+    let mut input = Unstructured::new(data);
+
+    if let Ok((profile, hybrid_mode, key_events)) = decode_configuration(&mut input) {
+        let rom = input.take_rest();
+
+        run_rom(rom, Quirks::profile(profile), hybrid_mode, &key_events);
+    }
+});
+
+fn decode_configuration(
+    input: &mut Unstructured,
+) -> arbitrary::Result<(QuirksProfile, bool, Vec<KeyEvent>)> {
+    let profile = *input.choose(&PROFILES)?;
+    let hybrid_mode = input.arbitrary::<bool>()?;
+    let key_events_count = input.int_in_range(0..=MAX_KEY_EVENTS)?;
+
+    let key_events = (0..key_events_count)
+        .map(|_| input.arbitrary::<KeyEvent>())
+        .collect::<arbitrary::Result<Vec<_>>>()?;
+
+    Ok((profile, hybrid_mode, key_events))
+}
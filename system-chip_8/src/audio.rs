@@ -0,0 +1,76 @@
+// Sound generation.
+//
+// Until a program loads an XO-CHIP audio pattern (F002), the classic fixed tone is played. Once a
+// pattern is loaded, its 128 bits are played in a loop, as a 1-bit waveform, at the rate defined
+// by the pitch register (FX3A).
+//
+// The state is shared with the wave generator, which is invoked by the frontend audio thread.
+
+use crate::Byte;
+use interfaces_frontend::audio::AUDIO_DEVICE_FREQUENCY;
+
+use std::f64::consts::PI;
+use std::sync::{Arc, Mutex};
+
+const TONE_FREQUENCY: f64 = 750.0; // Herz; typical beep frequency!
+const AMPLITUDE: i16 = i16::MAX / 16; // Volume (i16::MAX = max)
+
+pub(crate) const AUDIO_PATTERN_SIZE: usize = 16;
+pub(crate) const DEFAULT_PITCH: Byte = 64;
+
+const PATTERN_BITS: f64 = (AUDIO_PATTERN_SIZE * 8) as f64;
+
+#[derive(Clone, Copy)]
+pub(crate) struct AudioState {
+    pub(crate) pattern: Option<[Byte; AUDIO_PATTERN_SIZE]>,
+    pub(crate) pitch: Byte,
+}
+
+impl AudioState {
+    pub(crate) fn new() -> AudioState {
+        AudioState {
+            pattern: None,
+            pitch: DEFAULT_PITCH,
+        }
+    }
+}
+
+pub(crate) fn wave_generator(
+    audio_state: Arc<Mutex<AudioState>>,
+) -> Box<dyn FnMut(u32) -> i16 + Send> {
+    // Position in the pattern, in bits; kept across pattern/pitch changes, in order to avoid
+    // clicks.
+    //
+    let mut pattern_position = 0.0;
+
+    Box::new(move |sample_i| {
+        let AudioState { pattern, pitch } = *audio_state.lock().unwrap();
+
+        match pattern {
+            None => {
+                const PERIOD: f64 = AUDIO_DEVICE_FREQUENCY as f64 / TONE_FREQUENCY;
+
+                let period_number = sample_i as f64 / PERIOD;
+                let scale_factor = (period_number * 2.0 * PI).sin();
+                (AMPLITUDE as f64 * scale_factor) as i16
+            }
+            Some(pattern) => {
+                // See http://johnearnest.github.io/Octo/docs/XO-ChipSpecification.html.
+                //
+                let bits_per_second = 4000.0 * 2_f64.powf((pitch as f64 - 64.0) / 48.0);
+
+                let bit_index = pattern_position as usize;
+                let bit_value = (pattern[bit_index / 8] << (bit_index % 8)) & 0b1000_0000;
+
+                pattern_position += bits_per_second / AUDIO_DEVICE_FREQUENCY as f64;
+                pattern_position %= PATTERN_BITS;
+
+                if bit_value != 0 {
+                    AMPLITUDE
+                } else {
+                    -AMPLITUDE
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,622 @@
+use crate::audio::DEFAULT_PITCH;
+use crate::test_harness::{new_program_chip8, run_steps, xo_chip};
+use crate::{
+    Chip8, Chip8Error, Quirks, QuirksProfile, Speed, BIG_FONTSET, BIG_FONTS_LOCATION,
+    FONTS_LOCATION,
+};
+use interfaces_frontend::channel::ChannelFrontend;
+use interfaces_frontend::headless::{AudioTransition, HeadlessFrontend};
+
+use demonstrate::demonstrate;
+
+fn pixel(chip8: &Chip8<HeadlessFrontend>, x: usize, y: usize) -> bool {
+    chip8.screen[y * chip8.screen_width + x] & 1 != 0
+}
+
+fn assert_send<T: Send>() {}
+
+// COSMAC VIP, without the display wait, so that the sprites are drawn immediately.
+//
+fn cosmac_vip() -> Quirks {
+    Quirks {
+        display_wait: false,
+        ..Quirks::profile(QuirksProfile::CosmacVip)
+    }
+}
+
+demonstrate! {
+    describe "Chip8" {
+        use super::*;
+
+        context "arithmetic" {
+            it "adds with 7XNN, wrapping around without affecting VF" {
+                // V0 := 0xFF; V0 += 2
+                //
+                let mut chip8 = new_program_chip8(&[0x60FF, 0x7002], xo_chip());
+                run_steps(&mut chip8, 2);
+
+                assert_eq!(chip8.V[0], 0x01);
+                assert_eq!(chip8.V[15], 0);
+            }
+
+            it "sets VF on carry with 8XY4" {
+                // V0 := 0xF0; V1 := 0x20; V0 += V1; V2 := 1; V2 += V2
+                //
+                let mut chip8 = new_program_chip8(&[0x60F0, 0x6120, 0x8014, 0x6201, 0x8224], xo_chip());
+
+                run_steps(&mut chip8, 3);
+                assert_eq!((chip8.V[0], chip8.V[15]), (0x10, 1));
+
+                run_steps(&mut chip8, 2);
+                assert_eq!((chip8.V[2], chip8.V[15]), (0x02, 0));
+            }
+
+            it "sets VF when there is no borrow with 8XY5" {
+                // V0 := 0x10; V1 := 0x20; V0 -= V1; V2 := 5; V3 := 5; V2 -= V3
+                //
+                let mut chip8 = new_program_chip8(&[0x6010, 0x6120, 0x8015, 0x6205, 0x6305, 0x8235], xo_chip());
+
+                run_steps(&mut chip8, 3);
+                assert_eq!((chip8.V[0], chip8.V[15]), (0xF0, 0));
+
+                // Equal operands don't borrow.
+                //
+                run_steps(&mut chip8, 3);
+                assert_eq!((chip8.V[2], chip8.V[15]), (0x00, 1));
+            }
+
+            it "sets VF when there is no borrow with 8XY7" {
+                // V0 := 0x10; V1 := 0x30; V0 =- V1; V1 =- V0
+                //
+                let mut chip8 = new_program_chip8(&[0x6010, 0x6130, 0x8017, 0x8107], xo_chip());
+
+                run_steps(&mut chip8, 3);
+                assert_eq!((chip8.V[0], chip8.V[15]), (0x20, 1));
+
+                run_steps(&mut chip8, 1);
+                assert_eq!((chip8.V[1], chip8.V[15]), (0xF0, 0));
+            }
+
+            it "gives the flag priority when the destination is VF" {
+                // VF := 0xFF; V0 := 0x01; VF += V0
+                //
+                let mut chip8 = new_program_chip8(&[0x6FFF, 0x6001, 0x8F04], xo_chip());
+                run_steps(&mut chip8, 3);
+
+                assert_eq!(chip8.V[15], 1);
+            }
+
+            it "shifts Vy or Vx, according to the quirk" {
+                // V0 := 0x81; V1 := 0x03; V0 >>= V1; V2 := 0x81; V2 <<= V2
+                //
+                let program = [0x6081, 0x6103, 0x8016, 0x6281, 0x822E];
+
+                let mut chip8 = new_program_chip8(&program, xo_chip());
+                run_steps(&mut chip8, 3);
+                assert_eq!((chip8.V[0], chip8.V[15]), (0x01, 1));
+
+                let mut chip8 = new_program_chip8(&program, Quirks::profile(QuirksProfile::SuperChip));
+                run_steps(&mut chip8, 3);
+                assert_eq!((chip8.V[0], chip8.V[15]), (0x40, 1));
+
+                run_steps(&mut chip8, 2);
+                assert_eq!((chip8.V[2], chip8.V[15]), (0x02, 1));
+            }
+
+            it "resets VF on the logic operations, according to the quirk" {
+                // VF := 5; V0 := 0x0C; V1 := 0x0A; V0 |= V1; V0 &= V1; V0 ^= V1
+                //
+                let program = [0x6F05, 0x600C, 0x610A, 0x8011, 0x8012, 0x8013];
+
+                let mut chip8 = new_program_chip8(&program, cosmac_vip());
+                run_steps(&mut chip8, 4);
+                assert_eq!((chip8.V[0], chip8.V[15]), (0x0E, 0));
+
+                let mut chip8 = new_program_chip8(&program, xo_chip());
+                run_steps(&mut chip8, 4);
+                assert_eq!((chip8.V[0], chip8.V[15]), (0x0E, 5));
+
+                run_steps(&mut chip8, 2);
+                assert_eq!(chip8.V[0], 0x00);
+            }
+
+            it "masks the random values with CXNN" {
+                // V0 := random 0x00; V1 := random 0x0F
+                //
+                let mut chip8 = new_program_chip8(&[0xC000, 0xC10F], xo_chip());
+                run_steps(&mut chip8, 2);
+
+                assert_eq!(chip8.V[0], 0);
+                assert!(chip8.V[1] <= 0x0F);
+            }
+        }
+
+        context "memory" {
+            it "stores the BCD representation with FX33" {
+                // V0 := 254; I := 0x300; bcd V0
+                //
+                let mut chip8 = new_program_chip8(&[0x60FE, 0xA300, 0xF033], xo_chip());
+                run_steps(&mut chip8, 3);
+
+                assert_eq!(&chip8.ram[0x300..0x303], &[2, 5, 4]);
+                assert_eq!(chip8.I, 0x300);
+            }
+
+            it "stores and loads the registers, incrementing I according to the quirk" {
+                // V0 := 1; V1 := 2; I := 0x300; save V1; I := 0x300; load V1 (into V0..V1, after clearing)
+                //
+                let program = [0x6001, 0x6102, 0xA300, 0xF155, 0x6000, 0x6100, 0xA300, 0xF165];
+
+                let mut chip8 = new_program_chip8(&program, xo_chip());
+                run_steps(&mut chip8, 4);
+                assert_eq!(&chip8.ram[0x300..0x302], &[1, 2]);
+                assert_eq!(chip8.I, 0x302);
+
+                run_steps(&mut chip8, 4);
+                assert_eq!(&chip8.V[0..2], &[1, 2]);
+
+                let mut chip8 = new_program_chip8(&program, Quirks::profile(QuirksProfile::Chip48));
+                run_steps(&mut chip8, 4);
+                assert_eq!(chip8.I, 0x301);
+
+                let mut chip8 = new_program_chip8(&program, Quirks::profile(QuirksProfile::SuperChip));
+                run_steps(&mut chip8, 4);
+                assert_eq!(chip8.I, 0x300);
+            }
+
+            it "sets I with ANNN, FX1E and FX29" {
+                // I := 0x300; V0 := 0x10; I += V0; V1 := 0xA; i := hex V1
+                //
+                let mut chip8 = new_program_chip8(&[0xA300, 0x6010, 0xF01E, 0x610A, 0xF129], xo_chip());
+
+                run_steps(&mut chip8, 3);
+                assert_eq!(chip8.I, 0x310);
+
+                run_steps(&mut chip8, 2);
+                assert_eq!(chip8.I, FONTS_LOCATION + 0xA * 5);
+            }
+
+            it "sets and reads the timers" {
+                // V0 := 30; delay := V0; buzzer := V0; V1 := delay
+                //
+                let mut chip8 = new_program_chip8(&[0x601E, 0xF015, 0xF018, 0xF107], xo_chip());
+                run_steps(&mut chip8, 4);
+
+                assert_eq!((chip8.delay_timer, chip8.sound_timer), (30, 30));
+                assert_eq!(chip8.V[1], 30);
+            }
+
+            it "fails on the stores past the end of memory, without modifying it" {
+                // i := long 0xFFFE; V0 := 255; bcd V0
+                //
+                let mut chip8 = new_program_chip8(&[0xF000, 0xFFFE, 0x60FF, 0xF033], xo_chip());
+                run_steps(&mut chip8, 2);
+
+                assert_eq!(chip8.step(), Err(Chip8Error::MemoryOutOfBounds { address: 0x10000 }));
+                assert_eq!(&chip8.ram[0xFFFE..], &[0, 0]);
+                assert_eq!(chip8.PC, 0x206);
+            }
+
+            it "fails on the sprite reads past the end of memory, without drawing" {
+                // i := long 0xFFFF; sprite V0 V0 5
+                //
+                let mut chip8 = new_program_chip8(&[0xF000, 0xFFFF, 0xD005], xo_chip());
+                run_steps(&mut chip8, 1);
+
+                assert_eq!(chip8.step(), Err(Chip8Error::MemoryOutOfBounds { address: 0x10000 }));
+                assert!(chip8.screen.iter().all(|pixel| *pixel == 0));
+                assert_eq!(chip8.PC, 0x204);
+            }
+            it "sets I to the big font digits with FX30" {
+                // V0 := 0x1A; i := bighex V0 (the low nibble is used)
+                //
+                let mut chip8 = new_program_chip8(&[0x601A, 0xF030], xo_chip());
+                run_steps(&mut chip8, 2);
+
+                assert_eq!(chip8.I, BIG_FONTS_LOCATION + 0xA * 10);
+                assert_eq!(&chip8.ram[chip8.I..chip8.I + 10], &BIG_FONTSET[0xA * 10..0xB * 10]);
+            }
+
+            it "stores and loads the RPL flags with FX75 and FX85, up to X" {
+                // V0 := 1; V1 := 2; V2 := 3; saveflags V2; V0 := 0; V1 := 0; V2 := 0; loadflags V1
+                //
+                let program = [0x6001, 0x6102, 0x6203, 0xF275, 0x6000, 0x6100, 0x6200, 0xF185];
+
+                let mut chip8 = new_program_chip8(&program, xo_chip());
+                run_steps(&mut chip8, 8);
+
+                assert_eq!(&chip8.V[0..3], &[1, 2, 0]);
+                assert_eq!(&chip8.rpl_flags[0..4], &[1, 2, 3, 0]);
+                assert_eq!(chip8.I, 0);
+            }
+
+            it "stores and loads the register ranges with 5XY2 and 5XY3, without modifying I" {
+                // I := 0x300; V1 := 1; V2 := 2; V3 := 3; save V1 - V3; load V3 - V1 (reversed)
+                //
+                let program = [0xA300, 0x6101, 0x6202, 0x6303, 0x5132, 0x5313];
+
+                let mut chip8 = new_program_chip8(&program, xo_chip());
+
+                run_steps(&mut chip8, 5);
+                assert_eq!(&chip8.ram[0x300..0x304], &[1, 2, 3, 0]);
+                assert_eq!(chip8.I, 0x300);
+
+                run_steps(&mut chip8, 1);
+                assert_eq!(&chip8.V[1..4], &[3, 2, 1]);
+                assert_eq!(chip8.I, 0x300);
+            }
+        }
+
+        context "flow" {
+            it "skips on the register comparisons" {
+                // V0 := 1; V1 := 1; if V0 != 1 then V2 := 1 (3XNN); if V0 == 1 then V2 := 2 (4XNN);
+                // if V0 != V1 then V3 := 1 (5XY0); if V0 == V1 then V3 := 2 (9XY0)
+                //
+                let program = [0x6001, 0x6101, 0x3001, 0x6201, 0x4001, 0x6202, 0x5010, 0x6301, 0x9010, 0x6302];
+
+                let mut chip8 = new_program_chip8(&program, xo_chip());
+                run_steps(&mut chip8, 8);
+
+                assert_eq!((chip8.V[2], chip8.V[3]), (2, 2));
+                assert_eq!(chip8.PC, 0x200 + 2 * program.len());
+            }
+
+            it "skips the whole XO-CHIP long load" {
+                // V0 := 0; if V0 != 0 then i := long 0x0300; V1 := 1
+                //
+                let mut chip8 = new_program_chip8(&[0x6000, 0x3000, 0xF000, 0x0300, 0x6101], xo_chip());
+                run_steps(&mut chip8, 3);
+
+                assert_eq!(chip8.V[1], 1);
+                assert_eq!(chip8.I, 0);
+            }
+
+            it "jumps with 1NNN, and with BNNN according to the quirk" {
+                // jump 0x206; (0x202) V0 := 1; V1 := 2; (0x206) V0 := 4; jump0 0x200 (+ V0 or V2)
+                //
+                let program = [0x1206, 0x6001, 0x6102, 0x6004, 0xB200];
+
+                let mut chip8 = new_program_chip8(&program, xo_chip());
+                run_steps(&mut chip8, 3);
+                assert_eq!(chip8.PC, 0x204);
+
+                // With the quirk, BXNN uses VX, here V2 = 0.
+                //
+                let mut chip8 = new_program_chip8(&program, Quirks::profile(QuirksProfile::SuperChip));
+                run_steps(&mut chip8, 3);
+                assert_eq!(chip8.PC, 0x200);
+            }
+
+            it "calls and returns, up to the stack depth" {
+                // (0x200) call 0x204; (0x202) jump 0x202; (0x204) return
+                //
+                let mut chip8 = new_program_chip8(&[0x2204, 0x1202, 0x00EE], xo_chip());
+
+                run_steps(&mut chip8, 1);
+                assert_eq!((chip8.PC, chip8.SP, chip8.stack[0]), (0x204, 1, 0x202));
+
+                run_steps(&mut chip8, 1);
+                assert_eq!((chip8.PC, chip8.SP), (0x202, 0));
+
+                // (0x200) call 0x200
+                //
+                let mut chip8 = new_program_chip8(&[0x2200], xo_chip());
+                run_steps(&mut chip8, 16);
+
+                assert_eq!(chip8.step(), Err(Chip8Error::StackOverflow { pc: 0x200 }));
+
+                let mut chip8 = new_program_chip8(&[0x00EE], xo_chip());
+
+                assert_eq!(chip8.step(), Err(Chip8Error::StackUnderflow { pc: 0x200 }));
+            }
+
+            it "stops on 00FD" {
+                let mut chip8 = new_program_chip8(&[0x00FD], xo_chip());
+                run_steps(&mut chip8, 1);
+
+                assert!(!chip8.is_running());
+            }
+        }
+
+        context "keys" {
+            it "skips on the key status with EX9E and EXA1, considering the low nibble" {
+                // V0 := 0x15; if V0 -key then V1 := 1; if V0 key then V2 := 1
+                //
+                let program = [0x6015, 0xE09E, 0x6101, 0xE0A1, 0x6201];
+
+                let mut chip8 = new_program_chip8(&program, xo_chip());
+                chip8.keys_status[5] = true;
+                run_steps(&mut chip8, 4);
+
+                assert_eq!((chip8.V[1], chip8.V[2]), (0, 1));
+
+                let mut chip8 = new_program_chip8(&program, xo_chip());
+                run_steps(&mut chip8, 4);
+
+                assert_eq!((chip8.V[1], chip8.V[2]), (1, 0));
+            }
+
+            it "waits for a key with FX0A" {
+                // V0 := key
+                //
+                let mut chip8 = new_program_chip8(&[0xF00A], Quirks::profile(QuirksProfile::SuperChip));
+                run_steps(&mut chip8, 3);
+
+                assert!(chip8.is_waiting_for_key());
+
+                chip8.keys_status[7] = true;
+                run_steps(&mut chip8, 1);
+
+                assert!(!chip8.is_waiting_for_key());
+                assert_eq!((chip8.V[0], chip8.PC), (7, 0x202));
+            }
+
+            it "completes the FX0A wait on the key release, with the key wait release quirk" {
+                // V0 := key
+                //
+                let mut chip8 = new_program_chip8(&[0xF00A], Quirks::profile(QuirksProfile::CosmacVip));
+
+                // Keys held when the wait starts are ignored.
+                //
+                chip8.keys_status[3] = true;
+                run_steps(&mut chip8, 2);
+
+                chip8.keys_status[3] = false;
+                chip8.keys_status[7] = true;
+                run_steps(&mut chip8, 2);
+
+                assert!(chip8.is_waiting_for_key());
+
+                chip8.keys_status[7] = false;
+                run_steps(&mut chip8, 1);
+
+                assert!(!chip8.is_waiting_for_key());
+                assert_eq!((chip8.V[0], chip8.PC), (7, 0x202));
+            }
+
+            it "keeps the timers running while waiting for a key" {
+                // V0 := 10; delay := V0; V0 := 20; buzzer := V0; V1 := key
+                //
+                let mut chip8 = new_program_chip8(&[0x600A, 0xF015, 0x6014, 0xF018, 0xF10A], xo_chip());
+                run_steps(&mut chip8, 5);
+
+                for _ in 0..12 {
+                    chip8.run_frame().unwrap();
+                }
+
+                assert!(chip8.is_waiting_for_key());
+                assert_eq!((chip8.delay_timer, chip8.sound_timer), (0, 8));
+            }
+
+            it "stops the sound when the sound timer expires while waiting for a key" {
+                // V0 := 5; buzzer := V0; V1 := key
+                //
+                let mut chip8 = new_program_chip8(&[0x6005, 0xF018, 0xF10A], xo_chip());
+                run_steps(&mut chip8, 3);
+
+                assert_eq!(chip8.io_frontend().audio_transitions(), vec![AudioTransition::Play]);
+
+                for _ in 0..6 {
+                    chip8.run_frame().unwrap();
+                }
+
+                assert!(chip8.is_waiting_for_key());
+                assert_eq!(chip8.io_frontend().audio_transitions(), vec![AudioTransition::Play, AudioTransition::Pause]);
+            }
+        }
+
+        context "display" {
+            it "draws XORing, and sets VF on collision" {
+                // I := 0x208; sprite V0 V0 1; sprite V0 V0 1; (0x206) jump 0x206; (0x208) 0xC0
+                //
+                let mut chip8 = new_program_chip8(&[0xA208, 0xD001, 0xD001, 0x1206, 0xC000], xo_chip());
+
+                run_steps(&mut chip8, 2);
+                assert!(pixel(&chip8, 0, 0) && pixel(&chip8, 1, 0) && !pixel(&chip8, 2, 0));
+                assert_eq!(chip8.V[15], 0);
+
+                run_steps(&mut chip8, 1);
+                assert!(!pixel(&chip8, 0, 0) && !pixel(&chip8, 1, 0));
+                assert_eq!(chip8.V[15], 1);
+            }
+
+            it "wraps the starting coordinates" {
+                // V0 := 65; V1 := 33; I := 0x20A; sprite V0 V1 1; (0x208) jump 0x208; (0x20A) 0x80
+                //
+                let mut chip8 = new_program_chip8(&[0x6041, 0x6121, 0xA20A, 0xD011, 0x1208, 0x8000], cosmac_vip());
+                run_steps(&mut chip8, 4);
+
+                assert!(pixel(&chip8, 1, 1));
+            }
+
+            it "wraps or clips the sprites at the edges, according to the quirk" {
+                // V0 := 62; V1 := 31; I := 0x20A; sprite V0 V1 2; (0x208) jump 0x208; (0x20A) 0xF0F0
+                //
+                let program = [0x603E, 0x611F, 0xA20A, 0xD012, 0x1208, 0xF0F0];
+
+                let mut chip8 = new_program_chip8(&program, xo_chip());
+                run_steps(&mut chip8, 4);
+                assert!(pixel(&chip8, 63, 31) && pixel(&chip8, 0, 31) && pixel(&chip8, 1, 0));
+
+                let mut chip8 = new_program_chip8(&program, cosmac_vip());
+                run_steps(&mut chip8, 4);
+                assert!(pixel(&chip8, 63, 31) && !pixel(&chip8, 0, 31) && !pixel(&chip8, 62, 0));
+            }
+
+            it "waits for the vertical blank before drawing, according to the quirk" {
+                // sprite V0 V0 1
+                //
+                let mut chip8 = new_program_chip8(&[0xD001], Quirks::profile(QuirksProfile::CosmacVip));
+                run_steps(&mut chip8, 1);
+
+                assert_eq!(chip8.PC, 0x200);
+
+                // The vblank occurs at the end of the frame, so the sprite is drawn on the next step.
+                //
+                chip8.run_frame().unwrap();
+                run_steps(&mut chip8, 1);
+
+                assert_eq!(chip8.PC, 0x202);
+            }
+
+            it "clears the screen with 00E0" {
+                // I := 0x206; sprite V0 V0 1; clear; (0x206) 0x80
+                //
+                let mut chip8 = new_program_chip8(&[0xA206, 0xD001, 0x00E0, 0x8000], xo_chip());
+
+                run_steps(&mut chip8, 2);
+                assert!(pixel(&chip8, 0, 0));
+
+                run_steps(&mut chip8, 1);
+                assert!(chip8.screen.iter().all(|planes| *planes == 0));
+            }
+
+            it "switches between low and high resolution" {
+                // hires; lores
+                //
+                let mut chip8 = new_program_chip8(&[0x00FF, 0x00FE], xo_chip());
+
+                run_steps(&mut chip8, 1);
+                assert_eq!((chip8.screen_width, chip8.screen_height), (128, 64));
+                assert_eq!(chip8.io_frontend().screen_size(), Some((128, 64)));
+                assert_eq!(chip8.screen.len(), 128 * 64);
+
+                run_steps(&mut chip8, 1);
+                assert_eq!(chip8.io_frontend().screen_size(), Some((64, 32)));
+            }
+
+            it "draws 16x16 sprites in high resolution with DXY0" {
+                // hires; I := 0x208; sprite V0 V0 0; (0x206) jump 0x206; (0x208) 16 lines of 0xFFFF
+                //
+                let mut program = vec![0x00FF, 0xA208, 0xD000, 0x1206];
+                program.extend_from_slice(&[0xFFFF; 16]);
+
+                let mut chip8 = new_program_chip8(&program, xo_chip());
+                run_steps(&mut chip8, 3);
+
+                assert!(pixel(&chip8, 15, 15) && !pixel(&chip8, 16, 0) && !pixel(&chip8, 0, 16));
+            }
+
+            it "scrolls the screen" {
+                // I := 0x20A; sprite V0 V0 1; scroll-down 1; scroll-right; scroll-left; (0x20A) 0x80
+                //
+                let mut chip8 = new_program_chip8(&[0xA20A, 0xD001, 0x00C1, 0x00FB, 0x00FC, 0x8000], xo_chip());
+
+                run_steps(&mut chip8, 3);
+                assert!(!pixel(&chip8, 0, 0) && pixel(&chip8, 0, 1));
+
+                run_steps(&mut chip8, 1);
+                assert!(pixel(&chip8, 4, 1));
+
+                run_steps(&mut chip8, 1);
+                assert!(pixel(&chip8, 0, 1) && !pixel(&chip8, 4, 1));
+            }
+            it "draws on the selected planes only, with FN01" {
+                // plane 2; I := 0x20A; sprite V0 V0 1; plane 3; clear; (0x20A) 0x80
+                //
+                let mut chip8 = new_program_chip8(&[0xF201, 0xA20A, 0xD001, 0xF301, 0x00E0, 0x8000], xo_chip());
+
+                run_steps(&mut chip8, 3);
+                assert_eq!(chip8.screen[0], 0b10);
+                assert_eq!(chip8.V[15], 0);
+
+                // With both planes selected, the clearing affects both.
+                //
+                chip8.screen[1] = 0b01;
+
+                run_steps(&mut chip8, 2);
+                assert!(chip8.screen.iter().all(|planes| *planes == 0));
+            }
+
+            it "selects only the existing planes with FN01" {
+                // plane 0xF; I := 0x208; sprite V0 V0 1; (0x206) jump 0x206; (0x208) 0x80 0x80
+                //
+                let mut chip8 = new_program_chip8(&[0xFF01, 0xA208, 0xD001, 0x1206, 0x8080], xo_chip());
+                run_steps(&mut chip8, 3);
+
+                assert_eq!(chip8.selected_planes, 0b11);
+                assert_eq!(chip8.screen[0], 0b11);
+            }
+        }
+
+        context "audio" {
+            it "loads the audio pattern with F002, and sets the pitch with FX3A" {
+                // I := 0x208; audio; V0 := 100; pitch := V0; (0x208) 0x00FF... (16 bytes)
+                //
+                let mut program = vec![0xA208, 0xF002, 0x6064, 0xF03A];
+                program.extend_from_slice(&[0x00FF; 8]);
+
+                let mut chip8 = new_program_chip8(&program, xo_chip());
+
+                run_steps(&mut chip8, 2);
+
+                let audio_state = *chip8.audio_state.lock().unwrap();
+                assert_eq!(audio_state.pattern.map(|pattern| pattern.to_vec()), Some([0x00, 0xFF].repeat(8)));
+                assert_eq!(audio_state.pitch, DEFAULT_PITCH);
+
+                run_steps(&mut chip8, 2);
+                assert_eq!(chip8.audio_state.lock().unwrap().pitch, 100);
+            }
+        }
+
+        context "machine routines" {
+            it "rejects 0NNN outside hybrid mode" {
+                let mut chip8 = new_program_chip8(&[0x0300], xo_chip());
+
+                assert_eq!(chip8.step(), Err(Chip8Error::InvalidOpcode { pc: 0x200, opcode: 0x0300 }));
+                assert_eq!(chip8.PC, 0x200);
+            }
+
+            it "calls the machine routine with 0NNN in hybrid mode, and continues after it" {
+                // native 0x206; V0 := 1; (0x204) jump 0x204; (0x206) SEP R4
+                //
+                let mut chip8 = new_program_chip8(&[0x0206, 0x6001, 0x1204, 0xD400], xo_chip());
+                chip8.set_hybrid_mode(true);
+
+                run_steps(&mut chip8, 1);
+                assert_eq!(chip8.PC, 0x202);
+
+                run_steps(&mut chip8, 1);
+                assert_eq!(chip8.V[0], 1);
+            }
+        }
+
+        context "configuration" {
+            it "keeps the original behavior with the default quirks" {
+                // V0 := 3; V1 := 0x10; V0 >>= V1; I := 0x300; save V0
+                //
+                let program = [0x6003, 0x6110, 0x8016, 0xA300, 0xF055];
+
+                let mut chip8 = new_program_chip8(&program, Quirks::default());
+                run_steps(&mut chip8, 5);
+
+                assert_eq!((chip8.V[0], chip8.V[15]), (1, 1));
+                assert_eq!(chip8.I, 0x300);
+            }
+
+            it "can be moved to the emulation thread" {
+                assert_send::<Chip8<ChannelFrontend>>();
+                assert_send::<Chip8<HeadlessFrontend>>();
+            }
+
+            it "saturates the clock speed set in instructions per frame" {
+                let mut chip8 = new_program_chip8(&[0x1200], xo_chip());
+                chip8.set_instructions_per_frame(u32::MAX);
+
+                assert_eq!(chip8.clock_speed(), u32::MAX);
+            }
+
+            it "rejects the speed multipliers that are not finite and positive" {
+                let mut chip8 = new_program_chip8(&[0x1200], xo_chip());
+
+                for multiplier in &[0.0, -1.0, f32::NAN, f32::INFINITY] {
+                    assert!(chip8.set_speed(Speed::Multiplier(*multiplier)).is_err());
+                }
+
+                assert_eq!(chip8.speed, Speed::Multiplier(1.0));
+                assert_eq!(chip8.set_speed(Speed::Multiplier(0.5)), Ok(()));
+                assert_eq!(chip8.set_speed(Speed::Unlimited), Ok(()));
+            }
+        }
+    }
+}
@@ -0,0 +1,437 @@
+// Debugger.
+//
+// The debugger drives the machine instruction by instruction, and checks the stop conditions
+// (breakpoints, watchpoints and register conditions) around each one. It's not attached to the
+// machine; it's passed the machine on each operation, so that the machine can be freely driven
+// without it between the debugging sessions.
+//
+// Watchpoints rely on the machine recording the program memory accesses performed by each
+// instruction; the recording is active only while the debugger executes instructions.
+
+use crate::{Chip8, Chip8Error};
+use interfaces_frontend::IoFrontend;
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryAccess {
+    pub address: usize,
+    pub kind: AccessKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchpointKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Register {
+    V(usize),
+    I,
+    PC,
+    SP,
+    DelayTimer,
+    SoundTimer,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// Stops when the comparison `register <comparison> value` becomes true (ie. it's edge-triggered;
+/// it doesn't stop again until it becomes false, and then true again).
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RegisterCondition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+    /// The requested step/step over/step out completed.
+    ///
+    Step,
+    /// The frame completed (see `Debugger::run_frame()`); step over/step out also stop at the end
+    /// of the frame, if the subroutine hasn't returned yet.
+    ///
+    FrameCompleted,
+    Breakpoint {
+        pc: usize,
+    },
+    Watchpoint {
+        pc: usize,
+        access: MemoryAccess,
+    },
+    Condition {
+        pc: usize,
+        condition: RegisterCondition,
+    },
+    /// The emulation has been terminated (e.g. Quit event, or exit instruction).
+    ///
+    Halted,
+}
+
+/// Snapshot of the registers.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Registers {
+    pub V: [u8; 16],
+    pub I: usize,
+    pub PC: usize,
+    pub SP: usize,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl Registers {
+    pub fn get(&self, register: Register) -> usize {
+        match register {
+            Register::V(index) => self.V[index] as usize,
+            Register::I => self.I,
+            Register::PC => self.PC,
+            Register::SP => self.SP,
+            Register::DelayTimer => self.delay_timer as usize,
+            Register::SoundTimer => self.sound_timer as usize,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: Vec<usize>,
+    watchpoints: Vec<(usize, WatchpointKind)>,
+    // The second member is the last evaluation of the condition.
+    //
+    conditions: Vec<(RegisterCondition, bool)>,
+    // When stopping on a breakpoint, it must not trigger again when resuming.
+    //
+    resume_pc: Option<usize>,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger::default()
+    }
+
+    pub fn breakpoints(&self) -> &[usize] {
+        &self.breakpoints
+    }
+
+    pub fn add_breakpoint(&mut self, pc: usize) {
+        if !self.breakpoints.contains(&pc) {
+            self.breakpoints.push(pc);
+        }
+    }
+
+    // Returns false if the breakpoint didn't exist.
+    //
+    pub fn remove_breakpoint(&mut self, pc: usize) -> bool {
+        let previous_count = self.breakpoints.len();
+        self.breakpoints.retain(|breakpoint| *breakpoint != pc);
+        self.breakpoints.len() != previous_count
+    }
+
+    pub fn watchpoints(&self) -> &[(usize, WatchpointKind)] {
+        &self.watchpoints
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize, kind: WatchpointKind) {
+        self.watchpoints
+            .retain(|(watched_address, _)| *watched_address != address);
+        self.watchpoints.push((address, kind));
+    }
+
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        let previous_count = self.watchpoints.len();
+        self.watchpoints
+            .retain(|(watched_address, _)| *watched_address != address);
+        self.watchpoints.len() != previous_count
+    }
+
+    pub fn conditions(&self) -> Vec<RegisterCondition> {
+        self.conditions
+            .iter()
+            .map(|(condition, _)| *condition)
+            .collect()
+    }
+
+    pub fn add_condition(&mut self, condition: RegisterCondition) {
+        self.conditions.push((condition, false));
+    }
+
+    pub fn remove_condition(&mut self, index: usize) -> bool {
+        if index < self.conditions.len() {
+            self.conditions.remove(index);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Executes one instruction.
+    ///
+    pub fn step<T: IoFrontend>(&mut self, chip8: &mut Chip8<T>) -> Result<StopReason, Chip8Error> {
+        Ok(self.execute_instruction(chip8)?.unwrap_or(StopReason::Step))
+    }
+
+    /// Like `step()`, but if the instruction is a subroutine call, the whole subroutine is executed.
+    ///
+    /// A subroutine may never return (e.g. a game main loop), so execution stops anyway at the end
+    /// of the frame (`FrameCompleted`).
+    ///
+    pub fn step_over<T: IoFrontend>(
+        &mut self,
+        chip8: &mut Chip8<T>,
+    ) -> Result<StopReason, Chip8Error> {
+        let Registers { PC, SP, .. } = chip8.registers();
+        let memory = chip8.memory();
+
+        let is_call = memory.get(PC).is_some_and(|opcode_hi| opcode_hi >> 4 == 2);
+
+        if !is_call {
+            return self.step(chip8);
+        }
+
+        let start_frame = chip8.frame_count();
+
+        loop {
+            if let Some(stop_reason) = self.execute_instruction(chip8)? {
+                return Ok(stop_reason);
+            }
+
+            let registers = chip8.registers();
+
+            if registers.SP == SP && registers.PC == PC + 2 {
+                return Ok(StopReason::Step);
+            }
+
+            if chip8.frame_count() != start_frame {
+                return Ok(StopReason::FrameCompleted);
+            }
+        }
+    }
+
+    /// Executes until the current subroutine returns; if not inside a subroutine, it's equivalent
+    /// to `step()`.
+    ///
+    /// Like `step_over()`, execution stops anyway at the end of the frame (`FrameCompleted`).
+    ///
+    pub fn step_out<T: IoFrontend>(
+        &mut self,
+        chip8: &mut Chip8<T>,
+    ) -> Result<StopReason, Chip8Error> {
+        let start_SP = chip8.registers().SP;
+
+        if start_SP == 0 {
+            return self.step(chip8);
+        }
+
+        let start_frame = chip8.frame_count();
+
+        loop {
+            if let Some(stop_reason) = self.execute_instruction(chip8)? {
+                return Ok(stop_reason);
+            }
+
+            if chip8.registers().SP < start_SP {
+                return Ok(StopReason::Step);
+            }
+
+            if chip8.frame_count() != start_frame {
+                return Ok(StopReason::FrameCompleted);
+            }
+        }
+    }
+
+    /// Executes until the end of the frame (timers tick), unless a stop condition is met; this is
+    /// the building block of "continue", where the caller takes care of the pacing.
+    ///
+    pub fn run_frame<T: IoFrontend>(
+        &mut self,
+        chip8: &mut Chip8<T>,
+    ) -> Result<StopReason, Chip8Error> {
+        let start_frame = chip8.frame_count();
+
+        loop {
+            if let Some(stop_reason) = self.execute_instruction(chip8)? {
+                return Ok(stop_reason);
+            }
+
+            if chip8.frame_count() != start_frame {
+                return Ok(StopReason::FrameCompleted);
+            }
+        }
+    }
+
+    // Returns a stop reason only if a stop condition is met.
+    //
+    fn execute_instruction<T: IoFrontend>(
+        &mut self,
+        chip8: &mut Chip8<T>,
+    ) -> Result<Option<StopReason>, Chip8Error> {
+        if !chip8.is_running() {
+            return Ok(Some(StopReason::Halted));
+        }
+
+        let pc = chip8.registers().PC;
+
+        if self.breakpoints.contains(&pc) && self.resume_pc != Some(pc) {
+            self.resume_pc = Some(pc);
+            return Ok(Some(StopReason::Breakpoint { pc }));
+        }
+
+        self.resume_pc = None;
+
+        chip8.memory_accesses = Some(vec![]);
+        let step_result = chip8.step();
+        let memory_accesses = chip8.memory_accesses.take().unwrap();
+
+        step_result?;
+
+        for access in memory_accesses {
+            let watched = self.watchpoints.iter().any(|(address, kind)| {
+                *address == access.address
+                    && match kind {
+                        WatchpointKind::Read => access.kind == AccessKind::Read,
+                        WatchpointKind::Write => access.kind == AccessKind::Write,
+                        WatchpointKind::ReadWrite => true,
+                    }
+            });
+
+            if watched {
+                return Ok(Some(StopReason::Watchpoint { pc, access }));
+            }
+        }
+
+        let registers = chip8.registers();
+        let mut triggered_condition = None;
+
+        for (condition, last_evaluation) in self.conditions.iter_mut() {
+            let evaluation = condition.evaluate(&registers);
+
+            if evaluation && !*last_evaluation && triggered_condition.is_none() {
+                triggered_condition = Some(*condition);
+            }
+
+            *last_evaluation = evaluation;
+        }
+
+        if let Some(condition) = triggered_condition {
+            return Ok(Some(StopReason::Condition { pc, condition }));
+        }
+
+        if !chip8.is_running() {
+            return Ok(Some(StopReason::Halted));
+        }
+
+        Ok(None)
+    }
+}
+
+impl RegisterCondition {
+    pub fn evaluate(&self, registers: &Registers) -> bool {
+        let register_value = registers.get(self.register);
+
+        match self.comparison {
+            Comparison::Equal => register_value == self.value,
+            Comparison::NotEqual => register_value != self.value,
+            Comparison::Less => register_value < self.value,
+            Comparison::LessOrEqual => register_value <= self.value,
+            Comparison::Greater => register_value > self.value,
+            Comparison::GreaterOrEqual => register_value >= self.value,
+        }
+    }
+}
+
+// Parsing/formatting, mostly for the benefit of interactive frontends.
+
+impl FromStr for Register {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.to_ascii_uppercase();
+
+        match name.as_str() {
+            "I" => Ok(Register::I),
+            "PC" => Ok(Register::PC),
+            "SP" => Ok(Register::SP),
+            "DT" => Ok(Register::DelayTimer),
+            "ST" => Ok(Register::SoundTimer),
+            _ if name.len() == 2 && name.starts_with('V') => usize::from_str_radix(&name[1..], 16)
+                .map(Register::V)
+                .map_err(|_| format!("Invalid register: {}", name)),
+            _ => Err(format!("Invalid register: {}", name)),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::V(index) => write!(f, "V{:X}", index),
+            Register::I => write!(f, "I"),
+            Register::PC => write!(f, "PC"),
+            Register::SP => write!(f, "SP"),
+            Register::DelayTimer => write!(f, "DT"),
+            Register::SoundTimer => write!(f, "ST"),
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(symbol: &str) -> Result<Self, Self::Err> {
+        match symbol {
+            "==" => Ok(Comparison::Equal),
+            "!=" => Ok(Comparison::NotEqual),
+            "<" => Ok(Comparison::Less),
+            "<=" => Ok(Comparison::LessOrEqual),
+            ">" => Ok(Comparison::Greater),
+            ">=" => Ok(Comparison::GreaterOrEqual),
+            _ => Err(format!("Invalid comparison: {}", symbol)),
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
+impl fmt::Display for RegisterCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} 0x{:X}",
+            self.register, self.comparison, self.value
+        )
+    }
+}
@@ -0,0 +1,141 @@
+use crate::debugger::{
+    AccessKind, Comparison, Debugger, MemoryAccess, Register, RegisterCondition, Registers,
+    StopReason, WatchpointKind,
+};
+use crate::test_harness::{new_program_chip8, xo_chip};
+use crate::Chip8;
+use interfaces_frontend::headless::HeadlessFrontend;
+
+use demonstrate::demonstrate;
+
+// (0x200) call 0x206; (0x202) jump 0x202; (0x204) padding
+// (0x206) I := 0x300; bcd V0; return
+//
+fn subroutine_chip8() -> Chip8<HeadlessFrontend> {
+    new_program_chip8(&[0x2206, 0x1202, 0x0000, 0xA300, 0xF033, 0x00EE], xo_chip())
+}
+
+// (0x200) call 0x204; (0x202) jump 0x202; (0x204) jump 0x204
+//
+fn endless_subroutine_chip8() -> Chip8<HeadlessFrontend> {
+    new_program_chip8(&[0x2204, 0x1202, 0x1204], xo_chip())
+}
+
+demonstrate! {
+    describe "debugger" {
+        use super::*;
+
+        it "parses registers" {
+            assert_eq!("vA".parse(), Ok(Register::V(10)));
+            assert_eq!("pc".parse(), Ok(Register::PC));
+            assert_eq!("DT".parse(), Ok(Register::DelayTimer));
+            assert!("VG".parse::<Register>().is_err());
+            assert!("V10".parse::<Register>().is_err());
+        }
+
+        it "evaluates register conditions" {
+            let mut registers = Registers { V: [0; 16], I: 0x300, PC: 0x200, SP: 0, delay_timer: 0, sound_timer: 0 };
+            registers.V[3] = 5;
+
+            let condition = RegisterCondition { register: Register::V(3), comparison: Comparison::Equal, value: 5 };
+            assert!(condition.evaluate(&registers));
+
+            let condition = RegisterCondition { register: Register::I, comparison: "<".parse().unwrap(), value: 0x300 };
+            assert!(!condition.evaluate(&registers));
+        }
+
+        context "execution" {
+            it "stops on the breakpoints, and resumes past them" {
+                let mut chip8 = subroutine_chip8();
+                let mut debugger = Debugger::new();
+                debugger.add_breakpoint(0x208);
+
+                assert_eq!(debugger.run_frame(&mut chip8), Ok(StopReason::Breakpoint { pc: 0x208 }));
+                assert_eq!(chip8.registers().PC, 0x208);
+
+                assert_eq!(debugger.step(&mut chip8), Ok(StopReason::Step));
+                assert_eq!(chip8.registers().PC, 0x20A);
+
+                assert!(debugger.remove_breakpoint(0x208));
+                assert!(!debugger.remove_breakpoint(0x208));
+            }
+
+            it "stops on the watched memory accesses" {
+                let mut chip8 = subroutine_chip8();
+                let mut debugger = Debugger::new();
+                debugger.add_watchpoint(0x300, WatchpointKind::Read);
+                debugger.add_watchpoint(0x301, WatchpointKind::Write);
+
+                let expected_access = MemoryAccess { address: 0x301, kind: AccessKind::Write };
+
+                assert_eq!(debugger.run_frame(&mut chip8), Ok(StopReason::Watchpoint { pc: 0x208, access: expected_access }));
+                assert_eq!(chip8.registers().PC, 0x20A);
+            }
+
+            it "stops when a register condition becomes true" {
+                let mut chip8 = subroutine_chip8();
+                let mut debugger = Debugger::new();
+                let condition = RegisterCondition { register: Register::I, comparison: Comparison::Equal, value: 0x300 };
+                debugger.add_condition(condition);
+
+                assert_eq!(debugger.run_frame(&mut chip8), Ok(StopReason::Condition { pc: 0x206, condition }));
+
+                // Edge-triggered: it stays true, so it doesn't stop again.
+                //
+                assert_eq!(debugger.run_frame(&mut chip8), Ok(StopReason::FrameCompleted));
+            }
+
+            it "runs a frame" {
+                let mut chip8 = subroutine_chip8();
+                let mut debugger = Debugger::new();
+
+                assert_eq!(debugger.run_frame(&mut chip8), Ok(StopReason::FrameCompleted));
+                assert_eq!(chip8.frame_count(), 1);
+                assert_eq!(chip8.registers().PC, 0x202);
+            }
+
+            it "steps over a subroutine call" {
+                let mut chip8 = subroutine_chip8();
+                let mut debugger = Debugger::new();
+
+                assert_eq!(debugger.step_over(&mut chip8), Ok(StopReason::Step));
+
+                let registers = chip8.registers();
+                assert_eq!((registers.PC, registers.SP, registers.I), (0x202, 0, 0x300));
+            }
+
+            it "steps out of a subroutine" {
+                let mut chip8 = subroutine_chip8();
+                let mut debugger = Debugger::new();
+
+                debugger.step(&mut chip8).unwrap();
+                assert_eq!(chip8.registers().SP, 1);
+
+                assert_eq!(debugger.step_out(&mut chip8), Ok(StopReason::Step));
+
+                let registers = chip8.registers();
+                assert_eq!((registers.PC, registers.SP, registers.I), (0x202, 0, 0x300));
+            }
+
+            it "stops stepping over and out of a subroutine at the end of the frame" {
+                let mut chip8 = endless_subroutine_chip8();
+                let mut debugger = Debugger::new();
+
+                assert_eq!(debugger.step_over(&mut chip8), Ok(StopReason::FrameCompleted));
+                assert_eq!(chip8.frame_count(), 1);
+
+                assert_eq!(debugger.step_out(&mut chip8), Ok(StopReason::FrameCompleted));
+                assert_eq!(chip8.frame_count(), 2);
+                assert_eq!(chip8.registers().SP, 1);
+            }
+
+            it "reports the termination" {
+                let mut chip8 = new_program_chip8(&[0x00FD], xo_chip());
+                let mut debugger = Debugger::new();
+
+                assert_eq!(debugger.step(&mut chip8), Ok(StopReason::Halted));
+                assert_eq!(debugger.run_frame(&mut chip8), Ok(StopReason::Halted));
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::SaveStateError;
+
+use std::fmt;
+
+/// Errors of the CHIP-8 machine; they're caused by the program (ROM) being executed, with the
+/// exception of the movie desync, the invalid speed and the invalid rewind snapshot (which signals
+/// an emulator bug).
+///
+/// On the execution errors (invalid opcode, stack overflow/underflow, memory out of bounds, machine
+/// routine timeout), the machine state is left as it was before the instruction causing it, so
+/// that it can be inspected. The movie desync is detected after the frame has been executed, so
+/// the state is the one at the end of the desynced frame.
+///
+#[derive(Debug, PartialEq)]
+pub enum Chip8Error {
+    RomTooLarge { size: usize, max_size: usize },
+    InvalidOpcode { pc: usize, opcode: u16 },
+    StackOverflow { pc: usize },
+    StackUnderflow { pc: usize },
+    MemoryOutOfBounds { address: usize },
+    MovieDesync { frame: u64 },
+    MachineRoutineTimeout { pc: usize, address: usize },
+    InvalidSpeed { multiplier: f32 },
+    InvalidRewindSnapshot { error: SaveStateError },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::RomTooLarge { size, max_size } => {
+                write!(f, "Rom too big!: {} bytes ({} allowed)", size, max_size)
+            }
+            Chip8Error::InvalidOpcode { pc, opcode } => {
+                write!(
+                    f,
+                    "Invalid/unsupported instruction at 0x{:X}: {:04X}",
+                    pc, opcode
+                )
+            }
+            Chip8Error::StackOverflow { pc } => write!(f, "Stack overflow at 0x{:X}", pc),
+            Chip8Error::StackUnderflow { pc } => write!(f, "Stack underflow at 0x{:X}", pc),
+            Chip8Error::MemoryOutOfBounds { address } => {
+                write!(f, "Memory access out of bounds: 0x{:X}", address)
+            }
+            Chip8Error::MovieDesync { frame } => write!(f, "Movie desync at frame {}", frame),
+            Chip8Error::MachineRoutineTimeout { pc, address } => write!(
+                f,
+                "Machine code routine 0x{:X} (called at 0x{:X}) didn't return",
+                address, pc
+            ),
+            Chip8Error::InvalidSpeed { multiplier } => {
+                write!(f, "Invalid speed multiplier: {}", multiplier)
+            }
+            Chip8Error::InvalidRewindSnapshot { error } => {
+                write!(f, "Invalid rewind snapshot: {}", error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
@@ -0,0 +1,75 @@
+use crate::test_harness::{assert_golden_frame, frame_hash, run_rom};
+use crate::{Quirks, QuirksProfile};
+use interfaces_frontend::{events::EventCode, headless::AudioTransition};
+
+use demonstrate::demonstrate;
+
+const FLIGHT_RUNNER: &[u8] = include_bytes!("../extra/flightrunner.ch8");
+const TOMBSTON_TIPP: &[u8] = include_bytes!("../extra/tombstontipp.ch8");
+
+demonstrate! {
+    describe "golden frames" {
+        use super::*;
+
+        before {
+            let quirks = Quirks::profile(QuirksProfile::CosmacVip);
+        }
+
+        it "renders Flight Runner" {
+            let chip8 = run_rom(FLIGHT_RUNNER, quirks, 120, &[]);
+
+            assert_golden_frame("flightrunner", chip8.io_frontend().last_frame());
+        }
+
+        it "renders Flight Runner, steered via the keys" {
+            // Flight Runner uses 5/8/7/9 for up/down/left/right.
+            //
+            let key_script = [
+                (30, EventCode::KeyNum7, true),
+                (60, EventCode::KeyNum7, false),
+                (60, EventCode::KeyNum8, true),
+                (90, EventCode::KeyNum8, false),
+            ];
+
+            let chip8 = run_rom(FLIGHT_RUNNER, quirks, 120, &key_script);
+            let steered_frame = chip8.io_frontend().last_frame();
+
+            let unsteered_chip8 = run_rom(FLIGHT_RUNNER, quirks, 120, &[]);
+
+            assert_ne!(frame_hash(steered_frame), frame_hash(unsteered_chip8.io_frontend().last_frame()));
+            assert_golden_frame("flightrunner_keys", steered_frame);
+        }
+
+        it "renders Tombston Tipp" {
+            let chip8 = run_rom(TOMBSTON_TIPP, quirks, 180, &[]);
+
+            assert_golden_frame("tombstontipp", chip8.io_frontend().last_frame());
+        }
+
+        it "is deterministic" {
+            let first_run = run_rom(TOMBSTON_TIPP, quirks, 60, &[]);
+            let second_run = run_rom(TOMBSTON_TIPP, quirks, 60, &[]);
+
+            assert_eq!(
+                frame_hash(first_run.io_frontend().last_frame()),
+                frame_hash(second_run.io_frontend().last_frame())
+            );
+        }
+    }
+    describe "sound" {
+        use super::*;
+
+        it "plays while the sound timer is active" {
+            // V0 := 5; sound := V0; (0x204) jump 0x204
+            //
+            let rom = [0x60, 0x05, 0xF0, 0x18, 0x12, 0x04];
+
+            let chip8 = run_rom(&rom, Quirks::profile(QuirksProfile::CosmacVip), 10, &[]);
+
+            assert_eq!(
+                chip8.io_frontend().audio_transitions(),
+                vec![AudioTransition::Play, AudioTransition::Pause]
+            );
+        }
+    }
+}
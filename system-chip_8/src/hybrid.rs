@@ -0,0 +1,169 @@
+// Hybrid CHIP-8 programs: 0NNN machine code routines, executed by a CDP1802, as on the COSMAC VIP.
+//
+// The routines run against the CHIP-8 RAM, and expect the machine state at the locations of the
+// VIP (4 KiB) interpreter, so the state is copied to memory before the call, and back afterwards:
+//
+//   0EF0-0EFF: V0..VF
+//   0F00-0FFF: display (64x32, 1 bit per pixel, MSB first); only in low resolution
+//
+// and to the CPU registers, according to the interpreter conventions:
+//
+//   R2: stack pointer (X = 2); R3: routine program counter (P = 3); R5: CHIP-8 PC; R6/R7: VX/VY
+//   pointers (X/Y taken from the 0NNN opcode); R8.1/R8.0: delay/sound timers; RA: I; RB.1: display
+//   page
+//
+// The routine returns with SEP R4 (D4). The CPU registers are kept across the calls, since
+// routines may use the temporary registers to keep state; they're not part of the save states.
+//
+// Devices: OUT 2 latches the keypad key (from the low nibble), and EF3 is asserted when the latched
+// key is pressed. The display on/off ports and Q (tone) are ignored; the sound is driven by the
+// CHIP-8 sound timer.
+
+use crate::{Byte, Chip8, Chip8Error, STANDARD_SCREEN_HEIGHT, STANDARD_SCREEN_WIDTH};
+use component_rca_cdp1802::{Bus, RcaCdp1802};
+use interfaces_frontend::IoFrontend;
+
+const REGISTERS_LOCATION: usize = 0xEF0;
+const DISPLAY_LOCATION: usize = 0xF00;
+const DISPLAY_SIZE: usize = STANDARD_SCREEN_WIDTH / 8 * STANDARD_SCREEN_HEIGHT;
+const STACK_LOCATION: u16 = 0xECF;
+
+const RETURN_REGISTER: u8 = 4;
+
+// A routine not returning within this budget (about 1 second on the VIP) is considered hung.
+//
+const MAX_ROUTINE_CYCLES: u32 = 220_000;
+
+struct VipBus<'b> {
+    ram: &'b mut [Byte],
+    keys_status: &'b [bool; 16],
+    latched_key: usize,
+    // Address and previous value of each write, so that the RAM can be restored on timeout.
+    //
+    undo_log: Vec<(u16, Byte)>,
+}
+
+impl<'b> Bus for VipBus<'b> {
+    fn read(&mut self, address: u16) -> u8 {
+        self.ram[address as usize]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.undo_log.push((address, self.ram[address as usize]));
+        self.ram[address as usize] = value;
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        if port == 2 {
+            self.latched_key = (value & 0x0F) as usize;
+        }
+    }
+
+    fn flag(&mut self, flag: u8) -> bool {
+        flag == 3 && self.keys_status[self.latched_key]
+    }
+}
+
+impl<T: IoFrontend> Chip8<T> {
+    /// Enables the execution of the 0NNN machine code routines (disabled by default, in which case
+    /// they're invalid instructions).
+    ///
+    pub fn set_hybrid_mode(&mut self, enabled: bool) {
+        self.hybrid_cpu = if enabled {
+            Some(RcaCdp1802::new())
+        } else {
+            None
+        };
+    }
+
+    // Returns the machine cycles spent by the routine. Must be invoked only in hybrid mode.
+    //
+    // On error, the RAM is restored, so that the machine state is unchanged; since routines are
+    // typically called in loops, only the written bytes are restored, rather than backing up the
+    // whole RAM on each call.
+    //
+    pub(crate) fn execute_machine_routine(&mut self, address: usize) -> Result<u32, Chip8Error> {
+        let mut cpu = self.hybrid_cpu.take().unwrap();
+        let cpu_backup = cpu.clone();
+
+        let vip_state_range = REGISTERS_LOCATION..DISPLAY_LOCATION + DISPLAY_SIZE;
+        let vip_state_backup = self.ram[vip_state_range.clone()].to_vec();
+
+        self.store_vip_state(&mut cpu, address);
+
+        let mut bus = VipBus {
+            ram: &mut self.ram[..],
+            keys_status: &self.keys_status,
+            latched_key: 0,
+            undo_log: vec![],
+        };
+        let mut cycles = 0;
+
+        while cpu.P != RETURN_REGISTER {
+            cycles += cpu.step(&mut bus) as u32;
+
+            if cycles > MAX_ROUTINE_CYCLES {
+                for (address, value) in bus.undo_log.into_iter().rev() {
+                    self.ram[address as usize] = value;
+                }
+
+                self.ram[vip_state_range].copy_from_slice(&vip_state_backup);
+                self.hybrid_cpu = Some(cpu_backup);
+
+                return Err(Chip8Error::MachineRoutineTimeout {
+                    pc: self.PC,
+                    address,
+                });
+            }
+        }
+
+        self.load_vip_state(&cpu);
+        self.hybrid_cpu = Some(cpu);
+
+        Ok(cycles)
+    }
+
+    fn store_vip_state(&mut self, cpu: &mut RcaCdp1802, address: usize) {
+        self.ram[REGISTERS_LOCATION..REGISTERS_LOCATION + 16].copy_from_slice(&self.V);
+
+        if self.screen_width == STANDARD_SCREEN_WIDTH {
+            for (byte_index, pixels) in self.screen.chunks(8).enumerate() {
+                self.ram[DISPLAY_LOCATION + byte_index] = pixels
+                    .iter()
+                    .fold(0, |byte, pixel| (byte << 1) | (*pixel & 1));
+            }
+        }
+
+        cpu.R[2] = STACK_LOCATION;
+        cpu.R[3] = address as u16;
+        cpu.R[5] = (self.PC + 2) as u16;
+        cpu.R[6] = (REGISTERS_LOCATION + ((address >> 8) & 0x0F)) as u16;
+        cpu.R[7] = (REGISTERS_LOCATION + ((address >> 4) & 0x0F)) as u16;
+        cpu.R[8] = ((self.delay_timer as u16) << 8) | self.sound_timer as u16;
+        cpu.R[0xA] = self.I as u16;
+        cpu.R[0xB] = (DISPLAY_LOCATION as u16) | (cpu.R[0xB] & 0x00FF);
+        cpu.P = 3;
+        cpu.X = 2;
+    }
+
+    fn load_vip_state(&mut self, cpu: &RcaCdp1802) {
+        self.V
+            .copy_from_slice(&self.ram[REGISTERS_LOCATION..REGISTERS_LOCATION + 16]);
+
+        if self.screen_width == STANDARD_SCREEN_WIDTH {
+            let display = &self.ram[DISPLAY_LOCATION..DISPLAY_LOCATION + DISPLAY_SIZE];
+
+            for (pixel_index, pixel) in self.screen.iter_mut().enumerate() {
+                let bit = (display[pixel_index / 8] >> (7 - pixel_index % 8)) & 1;
+                *pixel = (*pixel & !1) | bit;
+            }
+
+            self.screen_changed = true;
+        }
+
+        self.PC = cpu.R[5] as usize;
+        self.delay_timer = (cpu.R[8] >> 8) as Byte;
+        self.sound_timer = cpu.R[8] as Byte;
+        self.I = cpu.R[0xA] as usize;
+    }
+}
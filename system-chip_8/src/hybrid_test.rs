@@ -0,0 +1,116 @@
+use crate::test_harness::{new_chip8, run_steps, xo_chip};
+use crate::{Chip8, Chip8Error, MovieError};
+use interfaces_frontend::headless::HeadlessFrontend;
+
+use demonstrate::demonstrate;
+
+// Address of the routines; X = 3, Y = 1 (see the VX/VY pointers).
+//
+const ROUTINE_ADDRESS: usize = 0x310;
+
+// The CHIP-8 program is loaded at the programs location, and the machine code routine at
+// ROUTINE_ADDRESS.
+//
+fn new_hybrid_chip8(program: &[u8], routine: &[u8]) -> Chip8<HeadlessFrontend> {
+    let mut rom = program.to_vec();
+    rom.resize(ROUTINE_ADDRESS - crate::PROGRAMS_LOCATION, 0);
+    rom.extend_from_slice(routine);
+
+    let mut chip8 = new_chip8(&rom, xo_chip());
+    chip8.set_hybrid_mode(true);
+    chip8
+}
+
+demonstrate! {
+    describe "hybrid mode" {
+        use super::*;
+
+        it "passes the registers and the timers to the routine, and back" {
+            // V3 := 9; VA := 0x11; delay := VA; I := 0x123; native 0x310; (0x20A) jump 0x20A
+            //
+            let program = [0x63, 0x09, 0x6A, 0x11, 0xFA, 0x15, 0xA1, 0x23, 0x03, 0x10, 0x12, 0x0A];
+
+            // LDN R6; ADI 1; STR R6 (VX += 1); GHI R8; STR R7 (VY := delay); LDI 0x20; PHI R8
+            // (delay := 0x20); LDI 0x80; PLO RA (I.0 := 0x80); SEP R4
+            //
+            let routine = [0x06, 0xFC, 0x01, 0x56, 0x98, 0x57, 0xF8, 0x20, 0xB8, 0xF8, 0x80, 0xAA, 0xD4];
+
+            let mut chip8 = new_hybrid_chip8(&program, &routine);
+            run_steps(&mut chip8, 5);
+
+            assert_eq!((chip8.V[3], chip8.V[1]), (0x0A, 0x11));
+            assert_eq!((chip8.delay_timer, chip8.I, chip8.PC), (0x20, 0x180, 0x20A));
+        }
+
+        it "passes the display to the routine, and back" {
+            // I := 0x208; sprite V0 V0 1; native 0x310; (0x206) jump 0x206; (0x208) 0x80
+            //
+            let program = [0xA2, 0x08, 0xD0, 0x01, 0x03, 0x10, 0x12, 0x06, 0x80];
+
+            // LDI 0x00; PLO RB (display start); LDN RB; XRI 0xFF; STR RB (invert the first 8 pixels);
+            // SEP R4
+            //
+            let routine = [0xF8, 0x00, 0xAB, 0x0B, 0xFB, 0xFF, 0x5B, 0xD4];
+
+            let mut chip8 = new_hybrid_chip8(&program, &routine);
+            run_steps(&mut chip8, 3);
+
+            let first_pixels = chip8.screen[0..9].iter().map(|pixel| pixel & 1).collect::<Vec<_>>();
+
+            assert_eq!(first_pixels, vec![0, 1, 1, 1, 1, 1, 1, 1, 0]);
+            assert_eq!(chip8.ram[0xF00], 0x7F);
+        }
+
+        it "returns on SEP R4, to the CHIP-8 PC in R5" {
+            // native 0x310; V0 := 1; V1 := 2
+            //
+            let program = [0x03, 0x10, 0x60, 0x01, 0x61, 0x02];
+
+            // INC R5; INC R5 (skip the next CHIP-8 instruction); SEP R4
+            //
+            let routine = [0x15, 0x15, 0xD4];
+
+            let mut chip8 = new_hybrid_chip8(&program, &routine);
+            run_steps(&mut chip8, 2);
+
+            assert_eq!((chip8.V[0], chip8.V[1], chip8.PC), (0, 2, 0x206));
+        }
+
+        it "rolls back the RAM and the CPU, when the routine times out" {
+            // native 0x310
+            //
+            let program = [0x03, 0x10];
+
+            // LDI 0x04; PHI RD; LDI 0x42; STR RD (writes 0x400); (0x316) BR 0x16
+            //
+            let routine = [0xF8, 0x04, 0xBD, 0xF8, 0x42, 0x5D, 0x30, 0x16];
+
+            let mut chip8 = new_hybrid_chip8(&program, &routine);
+
+            // So that the registers and the display copied to the RAM differ from it.
+            //
+            chip8.V[3] = 9;
+            chip8.screen[0] = 1;
+
+            let (ram, cpu) = (chip8.ram.clone(), chip8.hybrid_cpu.clone());
+
+            assert_eq!(chip8.step(), Err(Chip8Error::MachineRoutineTimeout { pc: 0x200, address: ROUTINE_ADDRESS }));
+            assert!(chip8.ram == ram);
+            assert_eq!(chip8.hybrid_cpu, cpu);
+            assert_eq!(chip8.PC, 0x200);
+        }
+
+        it "requires the movies to be played in the recording hybrid mode" {
+            let mut chip8 = new_hybrid_chip8(&[0x12, 0x00], &[]);
+            chip8.start_movie_recording(1).unwrap();
+            let movie = chip8.stop_movie_recording().unwrap();
+
+            assert!(movie.hybrid);
+
+            let mut chip8 = new_hybrid_chip8(&[0x12, 0x00], &[]);
+            chip8.set_hybrid_mode(false);
+
+            assert_eq!(chip8.start_movie_playback(movie), Err(MovieError::HybridModeMismatch));
+        }
+    }
+}
@@ -0,0 +1,275 @@
+// Instructions decoding.
+//
+// Decoding is independent from the machine state (e.g. the quirks), so that it can be shared by the
+// core and the tools (e.g. disassembler).
+//
+// The textual representation is in Cowgod's syntax (see http://devernay.free.fr/hacks/chip8/C8TECH10.HTM),
+// extended with the SCHIP/XO-CHIP mnemonics; values are hexadecimal.
+
+use std::fmt;
+
+type Byte = u8;
+type Word = u16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    // 00CN
+    ScrollDown(usize),
+    // 00DN
+    ScrollUp(usize),
+    // 00E0
+    ClearScreen,
+    // 00EE
+    Return,
+    // 00FB
+    ScrollRight,
+    // 00FC
+    ScrollLeft,
+    // 00FD
+    Exit,
+    // 00FE
+    LoresMode,
+    // 00FF
+    HiresMode,
+    // 0NNN
+    MachineCall(usize),
+    // 1NNN
+    Jump(usize),
+    // 2NNN
+    Call(usize),
+    // 3XNN
+    SkipIfEqualByte(usize, Byte),
+    // 4XNN
+    SkipIfNotEqualByte(usize, Byte),
+    // 5XY0
+    SkipIfEqual(usize, usize),
+    // 5XY2
+    StoreRange(usize, usize),
+    // 5XY3
+    LoadRange(usize, usize),
+    // 6XNN
+    SetByte(usize, Byte),
+    // 7XNN
+    AddByte(usize, Byte),
+    // 8XY0
+    Set(usize, usize),
+    // 8XY1
+    Or(usize, usize),
+    // 8XY2
+    And(usize, usize),
+    // 8XY3
+    Xor(usize, usize),
+    // 8XY4
+    Add(usize, usize),
+    // 8XY5
+    Subtract(usize, usize),
+    // 8XY6
+    ShiftRight(usize, usize),
+    // 8XY7
+    SubtractReverse(usize, usize),
+    // 8XYE
+    ShiftLeft(usize, usize),
+    // 9XY0
+    SkipIfNotEqual(usize, usize),
+    // ANNN
+    SetI(usize),
+    // BNNN (X is used only with the jump quirk)
+    JumpPlusV0(usize, usize),
+    // CXNN
+    Random(usize, Byte),
+    // DXYN
+    Draw(usize, usize, usize),
+    // EX9E
+    SkipIfKeyPressed(usize),
+    // EXA1
+    SkipIfKeyNotPressed(usize),
+    // F000 NNNN; the address is the following word, see `length()`.
+    SetILong,
+    // FN01
+    SelectPlanes(Byte),
+    // F002
+    LoadAudioPattern,
+    // FX07
+    GetDelayTimer(usize),
+    // FX0A
+    WaitKey(usize),
+    // FX15
+    SetDelayTimer(usize),
+    // FX18
+    SetSoundTimer(usize),
+    // FX1E
+    AddToI(usize),
+    // FX29
+    SetIToFont(usize),
+    // FX30
+    SetIToBigFont(usize),
+    // FX33
+    StoreBcd(usize),
+    // FX3A
+    SetPitch(usize),
+    // FX55
+    Store(usize),
+    // FX65
+    Load(usize),
+    // FX75
+    StoreRplFlags(usize),
+    // FX85
+    LoadRplFlags(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecodeError {
+    pub opcode: Word,
+}
+
+impl Instruction {
+    pub fn decode(opcode: Word) -> Result<Instruction, DecodeError> {
+        // When used alone, nibble1 and/or nibble2 are always Vx/Vy; nibble0 and nibble3
+        // are never used alone.
+        //
+        let nibble0 = (opcode >> 12) as usize;
+        let Vx = ((opcode & 0x0F00) >> 8) as usize;
+        let Vy = ((opcode & 0x00F0) >> 4) as usize;
+        let nibble3 = (opcode & 0x000F) as usize;
+
+        let address = (opcode & 0x0FFF) as usize;
+        let n = (opcode & 0x00FF) as Byte;
+
+        let instruction = match (nibble0, Vx, Vy, nibble3) {
+            // Some instructions are in the 0x0NNN range (machine code routine call), and need to be
+            // placed before it, therefore, out of order.
+            //
+            (0, 0, 0xC, _) => Instruction::ScrollDown(nibble3),
+            (0, 0, 0xD, _) => Instruction::ScrollUp(nibble3),
+            (0, 0, 0xE, 0) => Instruction::ClearScreen,
+            (0, 0, 0xE, 0xE) => Instruction::Return,
+            (0, 0, 0xF, 0xB) => Instruction::ScrollRight,
+            (0, 0, 0xF, 0xC) => Instruction::ScrollLeft,
+            (0, 0, 0xF, 0xD) => Instruction::Exit,
+            (0, 0, 0xF, 0xE) => Instruction::LoresMode,
+            (0, 0, 0xF, 0xF) => Instruction::HiresMode,
+            (0, _, _, _) => Instruction::MachineCall(address),
+            (1, _, _, _) => Instruction::Jump(address),
+            (2, _, _, _) => Instruction::Call(address),
+            (3, _, _, _) => Instruction::SkipIfEqualByte(Vx, n),
+            (4, _, _, _) => Instruction::SkipIfNotEqualByte(Vx, n),
+            (5, _, _, 0) => Instruction::SkipIfEqual(Vx, Vy),
+            (5, _, _, 2) => Instruction::StoreRange(Vx, Vy),
+            (5, _, _, 3) => Instruction::LoadRange(Vx, Vy),
+            (6, _, _, _) => Instruction::SetByte(Vx, n),
+            (7, _, _, _) => Instruction::AddByte(Vx, n),
+            (8, _, _, 0) => Instruction::Set(Vx, Vy),
+            (8, _, _, 1) => Instruction::Or(Vx, Vy),
+            (8, _, _, 2) => Instruction::And(Vx, Vy),
+            (8, _, _, 3) => Instruction::Xor(Vx, Vy),
+            (8, _, _, 4) => Instruction::Add(Vx, Vy),
+            (8, _, _, 5) => Instruction::Subtract(Vx, Vy),
+            (8, _, _, 6) => Instruction::ShiftRight(Vx, Vy),
+            (8, _, _, 7) => Instruction::SubtractReverse(Vx, Vy),
+            (8, _, _, 0xE) => Instruction::ShiftLeft(Vx, Vy),
+            (9, _, _, 0) => Instruction::SkipIfNotEqual(Vx, Vy),
+            (0xA, _, _, _) => Instruction::SetI(address),
+            (0xB, _, _, _) => Instruction::JumpPlusV0(address, Vx),
+            (0xC, _, _, _) => Instruction::Random(Vx, n),
+            (0xD, _, _, _) => Instruction::Draw(Vx, Vy, nibble3),
+            (0xE, _, 9, 0xE) => Instruction::SkipIfKeyPressed(Vx),
+            (0xE, _, 0xA, 1) => Instruction::SkipIfKeyNotPressed(Vx),
+            (0xF, 0, 0, 0) => Instruction::SetILong,
+            // The nibble is not a register, but using the same variable is simpler.
+            //
+            (0xF, _, 0, 1) => Instruction::SelectPlanes(Vx as Byte),
+            (0xF, 0, 0, 2) => Instruction::LoadAudioPattern,
+            (0xF, _, 0, 7) => Instruction::GetDelayTimer(Vx),
+            (0xF, _, 0, 0xA) => Instruction::WaitKey(Vx),
+            (0xF, _, 1, 5) => Instruction::SetDelayTimer(Vx),
+            (0xF, _, 1, 8) => Instruction::SetSoundTimer(Vx),
+            (0xF, _, 1, 0xE) => Instruction::AddToI(Vx),
+            (0xF, _, 2, 9) => Instruction::SetIToFont(Vx),
+            (0xF, _, 3, 0) => Instruction::SetIToBigFont(Vx),
+            (0xF, _, 3, 3) => Instruction::StoreBcd(Vx),
+            (0xF, _, 3, 0xA) => Instruction::SetPitch(Vx),
+            (0xF, _, 5, 5) => Instruction::Store(Vx),
+            (0xF, _, 6, 5) => Instruction::Load(Vx),
+            (0xF, _, 7, 5) => Instruction::StoreRplFlags(Vx),
+            (0xF, _, 8, 5) => Instruction::LoadRplFlags(Vx),
+            _ => return Err(DecodeError { opcode }),
+        };
+
+        Ok(instruction)
+    }
+
+    /// Length in bytes, including the operands.
+    ///
+    pub fn length(&self) -> usize {
+        match self {
+            Instruction::SetILong => 4,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::ScrollDown(n) => write!(f, "SCD #{:X}", n),
+            Instruction::ScrollUp(n) => write!(f, "SCU #{:X}", n),
+            Instruction::ClearScreen => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LoresMode => write!(f, "LOW"),
+            Instruction::HiresMode => write!(f, "HIGH"),
+            Instruction::MachineCall(address) => write!(f, "SYS #{:03X}", address),
+            Instruction::Jump(address) => write!(f, "JP #{:03X}", address),
+            Instruction::Call(address) => write!(f, "CALL #{:03X}", address),
+            Instruction::SkipIfEqualByte(Vx, n) => write!(f, "SE V{:X}, #{:02X}", Vx, n),
+            Instruction::SkipIfNotEqualByte(Vx, n) => write!(f, "SNE V{:X}, #{:02X}", Vx, n),
+            Instruction::SkipIfEqual(Vx, Vy) => write!(f, "SE V{:X}, V{:X}", Vx, Vy),
+            Instruction::StoreRange(Vx, Vy) => write!(f, "LD [I], V{:X}-V{:X}", Vx, Vy),
+            Instruction::LoadRange(Vx, Vy) => write!(f, "LD V{:X}-V{:X}, [I]", Vx, Vy),
+            Instruction::SetByte(Vx, n) => write!(f, "LD V{:X}, #{:02X}", Vx, n),
+            Instruction::AddByte(Vx, n) => write!(f, "ADD V{:X}, #{:02X}", Vx, n),
+            Instruction::Set(Vx, Vy) => write!(f, "LD V{:X}, V{:X}", Vx, Vy),
+            Instruction::Or(Vx, Vy) => write!(f, "OR V{:X}, V{:X}", Vx, Vy),
+            Instruction::And(Vx, Vy) => write!(f, "AND V{:X}, V{:X}", Vx, Vy),
+            Instruction::Xor(Vx, Vy) => write!(f, "XOR V{:X}, V{:X}", Vx, Vy),
+            Instruction::Add(Vx, Vy) => write!(f, "ADD V{:X}, V{:X}", Vx, Vy),
+            Instruction::Subtract(Vx, Vy) => write!(f, "SUB V{:X}, V{:X}", Vx, Vy),
+            Instruction::ShiftRight(Vx, Vy) => write!(f, "SHR V{:X}, V{:X}", Vx, Vy),
+            Instruction::SubtractReverse(Vx, Vy) => write!(f, "SUBN V{:X}, V{:X}", Vx, Vy),
+            Instruction::ShiftLeft(Vx, Vy) => write!(f, "SHL V{:X}, V{:X}", Vx, Vy),
+            Instruction::SkipIfNotEqual(Vx, Vy) => write!(f, "SNE V{:X}, V{:X}", Vx, Vy),
+            Instruction::SetI(address) => write!(f, "LD I, #{:03X}", address),
+            Instruction::JumpPlusV0(address, _) => write!(f, "JP V0, #{:03X}", address),
+            Instruction::Random(Vx, n) => write!(f, "RND V{:X}, #{:02X}", Vx, n),
+            Instruction::Draw(Vx, Vy, n) => write!(f, "DRW V{:X}, V{:X}, #{:X}", Vx, Vy, n),
+            Instruction::SkipIfKeyPressed(Vx) => write!(f, "SKP V{:X}", Vx),
+            Instruction::SkipIfKeyNotPressed(Vx) => write!(f, "SKNP V{:X}", Vx),
+            Instruction::SetILong => write!(f, "LD I, LONG"),
+            Instruction::SelectPlanes(n) => write!(f, "PLANE #{:X}", n),
+            Instruction::LoadAudioPattern => write!(f, "LD AUDIO, [I]"),
+            Instruction::GetDelayTimer(Vx) => write!(f, "LD V{:X}, DT", Vx),
+            Instruction::WaitKey(Vx) => write!(f, "LD V{:X}, K", Vx),
+            Instruction::SetDelayTimer(Vx) => write!(f, "LD DT, V{:X}", Vx),
+            Instruction::SetSoundTimer(Vx) => write!(f, "LD ST, V{:X}", Vx),
+            Instruction::AddToI(Vx) => write!(f, "ADD I, V{:X}", Vx),
+            Instruction::SetIToFont(Vx) => write!(f, "LD F, V{:X}", Vx),
+            Instruction::SetIToBigFont(Vx) => write!(f, "LD HF, V{:X}", Vx),
+            Instruction::StoreBcd(Vx) => write!(f, "LD B, V{:X}", Vx),
+            Instruction::SetPitch(Vx) => write!(f, "LD PITCH, V{:X}", Vx),
+            Instruction::Store(Vx) => write!(f, "LD [I], V{:X}", Vx),
+            Instruction::Load(Vx) => write!(f, "LD V{:X}, [I]", Vx),
+            Instruction::StoreRplFlags(Vx) => write!(f, "LD R, V{:X}", Vx),
+            Instruction::LoadRplFlags(Vx) => write!(f, "LD V{:X}, R", Vx),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid/unsupported instruction: {:04X}", self.opcode)
+    }
+}
+
+impl std::error::Error for DecodeError {}
@@ -0,0 +1,35 @@
+use crate::instruction::{DecodeError, Instruction};
+use demonstrate::demonstrate;
+
+demonstrate! {
+    describe "instruction" {
+        use super::*;
+
+        it "decodes the instructions" {
+            assert_eq!(Instruction::decode(0x00E0), Ok(Instruction::ClearScreen));
+            assert_eq!(Instruction::decode(0x00C4), Ok(Instruction::ScrollDown(4)));
+            assert_eq!(Instruction::decode(0x0123), Ok(Instruction::MachineCall(0x123)));
+            assert_eq!(Instruction::decode(0x5AB2), Ok(Instruction::StoreRange(0xA, 0xB)));
+            assert_eq!(Instruction::decode(0xD125), Ok(Instruction::Draw(1, 2, 5)));
+            assert_eq!(Instruction::decode(0xF201), Ok(Instruction::SelectPlanes(2)));
+            assert_eq!(Instruction::decode(0xF000), Ok(Instruction::SetILong));
+        }
+
+        it "rejects invalid opcodes" {
+            assert_eq!(Instruction::decode(0x5AB1), Err(DecodeError { opcode: 0x5AB1 }));
+            assert_eq!(Instruction::decode(0xE19F), Err(DecodeError { opcode: 0xE19F }));
+        }
+
+        it "formats the instructions in the Cowgod syntax" {
+            assert_eq!(Instruction::Jump(0x2A0).to_string(), "JP #2A0");
+            assert_eq!(Instruction::SetByte(0xA, 0x0F).to_string(), "LD VA, #0F");
+            assert_eq!(Instruction::Draw(1, 2, 5).to_string(), "DRW V1, V2, #5");
+            assert_eq!(Instruction::Load(3).to_string(), "LD V3, [I]");
+        }
+
+        it "reports the length" {
+            assert_eq!(Instruction::SetILong.length(), 4);
+            assert_eq!(Instruction::ClearScreen.length(), 2);
+        }
+    }
+}
@@ -1,12 +1,82 @@
 // For clarity, any register reference is upper case.
 #![allow(non_snake_case)]
 
-use interfaces_frontend::{
-    audio::AudioDevice, audio::AUDIO_DEVICE_FREQUENCY, events::EventCode, logging::Logger,
-    video::Pixel, IoFrontend,
+mod audio;
+mod debugger;
+mod error;
+mod hybrid;
+mod instruction;
+mod movie;
+mod profiler;
+mod quirks;
+mod rewind;
+mod rng;
+mod rom_database;
+mod rpl_storage;
+mod save_state;
+mod timing;
+mod trace;
+
+pub use crate::debugger::{
+    AccessKind, Comparison, Debugger, MemoryAccess, Register, RegisterCondition, Registers,
+    StopReason, WatchpointKind,
+};
+pub use crate::error::Chip8Error;
+pub use crate::instruction::{DecodeError, Instruction};
+pub use crate::movie::{Movie, MovieError, DEFAULT_HASH_INTERVAL};
+pub use crate::profiler::{Profile, SubroutineProfile};
+pub use crate::quirks::{IndexIncrement, Quirks, QuirksProfile};
+pub use crate::rewind::RewindConfig;
+pub use crate::rng::{RandomGenerator, XorShiftGenerator};
+pub use crate::rom_database::{RomDatabase, RomDatabaseError, RomInfo};
+pub use crate::rpl_storage::{MemoryRplFlagsStorage, RplFlagsStorage};
+pub use crate::save_state::SaveStateError;
+pub use crate::timing::Timing;
+pub use crate::trace::{
+    read_binary_trace, BinaryTraceWriter, LineTraceWriter, LogTracer, MemoryWrite, RegisterChange,
+    TraceError, TraceEvent, Tracer,
 };
 
-use std::f64::consts::PI;
+#[cfg(test)]
+mod chip8_test;
+#[cfg(test)]
+mod debugger_test;
+#[cfg(test)]
+mod golden_test;
+#[cfg(test)]
+mod hybrid_test;
+#[cfg(test)]
+mod instruction_test;
+#[cfg(test)]
+mod movie_test;
+#[cfg(test)]
+mod profiler_test;
+#[cfg(test)]
+mod rewind_test;
+#[cfg(test)]
+mod rng_test;
+#[cfg(test)]
+mod rom_database_test;
+#[cfg(test)]
+mod rpl_storage_test;
+#[cfg(test)]
+mod save_state_test;
+#[cfg(test)]
+mod test_harness;
+#[cfg(test)]
+mod timing_test;
+#[cfg(test)]
+mod trace_test;
+
+use interfaces_frontend::{audio::AudioDevice, events::EventCode, video::Pixel, IoFrontend};
+
+use audio::{AudioState, AUDIO_PATTERN_SIZE};
+use component_rca_cdp1802::RcaCdp1802;
+use movie::MovieMode;
+use rewind::Rewind;
+use sha1_smol::Sha1;
+
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -16,15 +86,16 @@ type Word = u16;
 // Simplification: the below are words, however, since they're used in indexing, the required
 // casting makes usage very ugly, therefore, they're defined as usize.
 //
-const RAM_SIZE: usize = 4096;
+// XO-CHIP extends the RAM to 64 KiB; since programs for the other platforms don't access the extra
+// space, it's always available.
+//
+const RAM_SIZE: usize = 0x10000;
 const FONTS_LOCATION: usize = 0; // There's no reference location, but this is common practice
-const PROGRAMS_LOCATION: usize = 0x200;
+const BIG_FONTS_LOCATION: usize = FONTS_LOCATION + FONTSET.len();
+pub const PROGRAMS_LOCATION: usize = 0x200;
 
-const CLOCK_SPEED: u32 = 500; // Herz
-const TIMERS_SPEED: u32 = 60; // Herz
-
-const TONE_FREQUENCY: f64 = 750.0; // Herz; typical beep frequency!
-const AMPLITUDE: i16 = i16::MAX / 16; // Volume (i16::MAX = max)
+pub const DEFAULT_CLOCK_SPEED: u32 = 500; // Herz
+pub const TIMERS_SPEED: u32 = 60; // Herz; also the frame rate
 
 const STANDARD_SCREEN_WIDTH: usize = 64;
 const STANDARD_SCREEN_HEIGHT: usize = 32;
@@ -50,9 +121,58 @@ const FONTSET: [Byte; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
-pub struct Chip8<'a, T: IoFrontend> {
-    ram: [Byte; RAM_SIZE],
-    screen: Vec<Pixel>,
+// Super-CHIP 1.1 fonts (8x10). The original set includes only the digits; the letters are an Octo
+// addition, commonly supported.
+//
+const BIG_FONTSET: [Byte; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+// Number of HP-48 RPL user flags (FX75/FX85). Super-CHIP 1.1 supports 8; XO-CHIP extends them to 16.
+//
+const RPL_FLAGS_COUNT: usize = 16;
+
+// Horizontal scroll amount, in pixels of the current resolution.
+//
+const HORIZONTAL_SCROLL_PIXELS: usize = 4;
+
+// XO-CHIP bitplanes. Each screen entry is a bitmask of the planes set; the palette maps each
+// combination to a color. Programs not using XO-CHIP instructions only draw on the first plane.
+//
+const PLANES_COUNT: usize = 2;
+
+/// Palette used when none is specified; index 0 is the background, and 1 is the standard CHIP-8
+/// foreground.
+///
+pub const DEFAULT_PALETTE: [Pixel; 4] = [
+    Pixel::OFF,
+    Pixel::ON,
+    Pixel(0xAA, 0xAA, 0xAA),
+    Pixel(0x55, 0x55, 0x55),
+];
+
+// Owns all its state, including the frontend and the pluggable components (tracer, random
+// generator, RPL flags storage), so that it can be moved to (and run in) another thread; see
+// `interfaces_frontend::channel`. This is why the components traits require `Send`.
+//
+pub struct Chip8<T: IoFrontend> {
+    ram: Box<[Byte; RAM_SIZE]>,
+    screen: Vec<Byte>,
     stack: [usize; 16], // Simplification (exactly: word); see location constants comment.
 
     V: [Byte; 16],
@@ -66,47 +186,141 @@ pub struct Chip8<'a, T: IoFrontend> {
     delay_timer: Byte,
     sound_timer: Byte,
 
+    rpl_flags: [Byte; RPL_FLAGS_COUNT],
+    rpl_flags_storage: Option<Box<dyn RplFlagsStorage>>,
+
     // True/false for key pressed/released.
     //
     keys_status: [bool; 16],
 
-    io_frontend: &'a mut T,
-    audio_device: Box<dyn AudioDevice>,
-    logger: &'a mut Option<Box<dyn Logger>>,
+    quirks: Quirks,
+
+    random_generator: Box<dyn RandomGenerator>,
+    seed: u64,
+
+    // SHA-1 of the ROM; identifies the program (e.g. for the movies).
+    //
+    rom_hash: [Byte; 20],
+
+    io_frontend: T,
+    audio_device: T::Audio,
+    audio_state: Arc<Mutex<AudioState>>,
+    tracer: Option<Box<dyn Tracer>>,
+
+    // Memory writes of the current instruction; collected only while tracing.
+    //
+    traced_memory_writes: Vec<MemoryWrite>,
 
     screen_width: usize,
     screen_height: usize,
+
+    // Bitmask of the planes affected by the drawing/scrolling/clearing instructions.
+    //
+    selected_planes: Byte,
+    palette: [Pixel; 4],
+
+    // Screen converted to the output format; regenerated only when the screen changes, since the
+    // frontend is updated on each cycle.
+    //
+    frame: Vec<Pixel>,
+    screen_changed: bool,
+
+    // Instructions per second; not used by the COSMAC VIP timing.
+    //
+    clock_speed: u32,
+    speed: Speed,
+    timing: Timing,
+
+    // Incremented on each cycle by the cycle cost; when it reaches the frame cost, the timers tick.
+    // With the fixed timing, the cost is TIMERS_SPEED, and the frame cost is the clock speed;
+    // with the COSMAC VIP timing, they're machine cycles.
+    //
+    timers_accumulator: u32,
+
+    // Set on timers tick; used by the display wait quirk.
+    //
+    vblank_occurred: bool,
+
+    emulation_running: bool,
+
+    // Number of frames (timers ticks) emulated.
+    //
+    frame_count: u64,
+
+    // FX0A in progress; while set, no instructions are executed, but the timers keep running.
+    //
+    key_wait: Option<KeyWait>,
+
+    rewind: Option<Rewind>,
+
+    movie: Option<MovieMode>,
+
+    // CPU executing the 0NNN machine code routines; present only in hybrid mode.
+    //
+    hybrid_cpu: Option<RcaCdp1802>,
+
+    // Present only while profiling; see `start_profiling()`.
+    //
+    profile: Option<Profile>,
+
+    // Program memory accesses of the current instruction; recorded only while the debugger
+    // executes instructions (`Some`).
+    //
+    memory_accesses: Option<Vec<MemoryAccess>>,
+}
+
+/// Emulation speed, relative to real time.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Speed {
+    /// Must be finite and positive; e.g. 2.0 for fast forward, 0.5 for slow motion.
+    ///
+    Multiplier(f32),
+    Unlimited,
 }
 
-impl<'a, T: IoFrontend> Chip8<'a, T> {
+// State of an FX0A (wait for key) instruction.
+//
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct KeyWait {
+    Vx: usize,
+    // Keys status at the previous cycle; the wait is completed by the transitions, so keys held
+    // when the instruction is executed don't count.
+    //
+    previous_keys: [bool; 16],
+    // With the release quirk, the key pressed during the wait; its release completes the wait.
+    //
+    pressed_key: Option<usize>,
+}
+
+impl<T: IoFrontend> Chip8<T> {
     // Simplification: load the data on instantiation, as there is practically no initialization
     // stage (BIOS/firmware).
     //
+    // The seed is used by the default random generator; the same seed, with the same inputs,
+    // produces the same emulation.
+    //
+    // The clock speed is in instructions per second (see `DEFAULT_CLOCK_SPEED`).
+    //
     pub fn new(
-        io_frontend: &'a mut T,
+        mut io_frontend: T,
         game_rom: &[Byte],
-        logger: &'a mut Option<Box<dyn Logger>>,
-    ) -> Chip8<'a, T> {
+        quirks: Quirks,
+        clock_speed: u32,
+        seed: u64,
+    ) -> Result<Chip8<T>, Chip8Error> {
         if game_rom.len() > RAM_SIZE - PROGRAMS_LOCATION {
-            panic!(
-                "Rom too big!: {} bytes ({} allowed)",
-                game_rom.len(),
-                RAM_SIZE - PROGRAMS_LOCATION
-            );
+            return Err(Chip8Error::RomTooLarge {
+                size: game_rom.len(),
+                max_size: RAM_SIZE - PROGRAMS_LOCATION,
+            });
         }
 
-        fn wave_generator(sample_i: u32) -> i16 {
-            const PERIOD: f64 = AUDIO_DEVICE_FREQUENCY as f64 / TONE_FREQUENCY;
-
-            let period_number = sample_i as f64 / PERIOD;
-            let scale_factor = (period_number * 2.0 * PI).sin();
-            (AMPLITUDE as f64 * scale_factor) as i16
-        };
-
-        let audio_device = io_frontend.audio_device(wave_generator);
+        let audio_state = Arc::new(Mutex::new(AudioState::new()));
+        let audio_device = io_frontend.audio_device(audio::wave_generator(audio_state.clone()));
 
         let mut chip8 = Chip8 {
-            ram: [0; RAM_SIZE],
+            ram: Box::new([0; RAM_SIZE]),
             screen: vec![],
             stack: [0; 16],
 
@@ -118,102 +332,373 @@ impl<'a, T: IoFrontend> Chip8<'a, T> {
             delay_timer: 0,
             sound_timer: 0,
 
+            rpl_flags: [0; RPL_FLAGS_COUNT],
+            rpl_flags_storage: None,
+
             keys_status: [false; 16],
 
+            quirks,
+
+            random_generator: Box::new(XorShiftGenerator::new(seed)),
+            seed,
+
+            rom_hash: Sha1::from(game_rom).digest().bytes(),
+
             io_frontend,
             audio_device,
-            logger,
+            audio_state,
+            tracer: None,
+            traced_memory_writes: vec![],
 
             screen_width: STANDARD_SCREEN_WIDTH,
             screen_height: STANDARD_SCREEN_HEIGHT,
+
+            selected_planes: 1,
+            palette: DEFAULT_PALETTE,
+
+            frame: vec![],
+            screen_changed: true,
+
+            clock_speed: clock_speed.max(1),
+            speed: Speed::Multiplier(1.0),
+            timing: Timing::Fixed,
+
+            timers_accumulator: 0,
+
+            vblank_occurred: false,
+
+            emulation_running: true,
+
+            frame_count: 0,
+
+            key_wait: None,
+
+            rewind: None,
+
+            movie: None,
+
+            hybrid_cpu: None,
+            profile: None,
+
+            memory_accesses: None,
         };
 
         chip8.ram[FONTS_LOCATION..FONTS_LOCATION + FONTSET.len()].copy_from_slice(&FONTSET);
+        chip8.ram[BIG_FONTS_LOCATION..BIG_FONTS_LOCATION + BIG_FONTSET.len()]
+            .copy_from_slice(&BIG_FONTSET);
 
         chip8.ram[PROGRAMS_LOCATION..PROGRAMS_LOCATION + game_rom.len()].copy_from_slice(game_rom);
 
         chip8.setup_graphics();
 
-        chip8
+        Ok(chip8)
     }
 
-    pub fn run(&mut self, max_speed: bool) {
-        let cycle_time_slice = Duration::new(0, 1_000_000_000 / CLOCK_SPEED);
-        let timers_time_slice = Duration::new(0, 1_000_000_000 / TIMERS_SPEED);
-
-        let mut last_cycle_time = Instant::now();
-        let mut next_timers_time = last_cycle_time;
+    /// Runs until the emulation is terminated, pacing the frames according to the speed (see
+    /// `set_speed()`).
+    ///
+    pub fn run(&mut self) -> Result<(), Chip8Error> {
+        let mut last_frame_time = Instant::now();
 
-        let mut emulation_running = true;
+        while self.emulation_running {
+            self.run_frame()?;
 
-        while emulation_running {
-            let mut screen_drawn = false;
-            let previous_sound_timer = self.sound_timer;
+            // Computed on each frame, since the speed can be changed at any time.
+            //
+            let frame_time_slice = match self.speed {
+                Speed::Multiplier(multiplier) => {
+                    Duration::from_secs_f32(1.0 / (TIMERS_SPEED as f32 * multiplier))
+                }
+                Speed::Unlimited => Duration::from_secs(0),
+            };
 
-            self.emulate_cycle(&mut emulation_running, &mut screen_drawn);
+            // Use a fixed loop time (start time + N * frame_time_slice), unless we're running late,
+            // in which case, the current frame is expanded.
+            //
+            // The code would be more expressive if it was possible to set `delay = current_time - next_frame_time`,
+            // but it panics when the result is negative!
+            //
+            let next_frame_time = last_frame_time + frame_time_slice;
+            let current_time = Instant::now();
 
-            if !screen_drawn {
-                self.io_frontend.update_screen(&self.screen, false);
+            if current_time > next_frame_time {
+                last_frame_time = current_time;
+            } else {
+                thread::sleep(next_frame_time - current_time);
+                last_frame_time = next_frame_time;
             }
+        }
 
-            self.set_keys(&mut emulation_running);
+        Ok(())
+    }
 
-            // If there are no delays, use a fixed loop time (start time + N * cycle_time_slice).
-            // If there is a delay, expand the current loop (time), and delay the timers' next tick.
-            //
-            // The code would be more expressive if it was possible to set `delay = current_time - next_cycle_time`,
-            // but it panics when the result is negative!
-            //
-            let next_cycle_time = last_cycle_time + cycle_time_slice;
+    /// Executes a single instruction, along with the related housekeeping (screen update, events
+    /// polling, timers and sound).
+    ///
+    /// While an FX0A instruction waits for a key, a cycle elapses without executing instructions.
+    ///
+    /// There is no sleeping; pacing is entirely up to the caller.
+    ///
+    /// The rewind hotkey is consumed, but doesn't stop the execution; rewinding works only
+    /// through `run_frame()`, since it steps back one frame at a time.
+    ///
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        self.emulate_step()?;
+
+        Ok(())
+    }
 
-            // This check doesn't need to account delays, because it uses the next cycle time, which,
-            // at this step, is not recalculated.
-            //
-            if last_cycle_time <= next_timers_time && next_timers_time < next_cycle_time {
-                self.update_timers();
-                next_timers_time += timers_time_slice;
+    /// Executes instructions until the timers tick (60 Hz), which is conventionally a frame.
+    ///
+    /// The number of instructions executed is variable, since the clock speed is not a multiple of
+    /// the timers speed.
+    ///
+    /// While rewinding (see `enable_rewind()`), instead of executing instructions, the previous
+    /// snapshot is restored.
+    ///
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        if self.is_rewinding() {
+            self.rewind_frame()?;
+            self.set_keys();
+            return Ok(());
+        }
+
+        while self.emulation_running {
+            let timers_ticked = self.emulate_step()?;
+
+            if timers_ticked {
+                break;
             }
+        }
 
-            self.handle_sound_playback(previous_sound_timer);
+        Ok(())
+    }
 
-            let current_time = Instant::now();
+    /// Executes (up to) the given number of instructions; execution stops earlier if emulation
+    /// is terminated (e.g. Quit event).
+    ///
+    /// Like `step()`, it doesn't rewind.
+    ///
+    pub fn run_for(&mut self, cycles: u32) -> Result<(), Chip8Error> {
+        for _ in 0..cycles {
+            if !self.emulation_running {
+                break;
+            }
 
-            // WATCH OUT! Before checking if we're running at max speed, we need to check if we're
-            // running late, which takes priority!
-            //
-            if current_time > next_cycle_time {
-                last_cycle_time = current_time;
-                next_timers_time += current_time - next_cycle_time;
-            } else if max_speed {
-                last_cycle_time = current_time;
-                next_timers_time -= next_cycle_time - current_time;
-            } else {
-                thread::sleep(next_cycle_time - current_time);
-                last_cycle_time = next_cycle_time;
+            self.emulate_step()?;
+        }
+
+        Ok(())
+    }
+
+    /// False if emulation has been terminated (e.g. Quit event); further steps have no effect.
+    ///
+    pub fn is_running(&self) -> bool {
+        self.emulation_running
+    }
+
+    /// Instructions per second; can be changed at any time.
+    ///
+    pub fn set_clock_speed(&mut self, clock_speed: u32) {
+        self.clock_speed = clock_speed.max(1);
+    }
+
+    pub fn clock_speed(&self) -> u32 {
+        self.clock_speed
+    }
+
+    /// Convenience for setting the clock speed as instructions per frame (timers tick), which is
+    /// the unit commonly used by the CHIP-8 community (e.g. Octo).
+    ///
+    pub fn set_instructions_per_frame(&mut self, instructions: u32) {
+        self.set_clock_speed(instructions.saturating_mul(TIMERS_SPEED));
+    }
+
+    /// Can be changed at any time; see `Timing`.
+    ///
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        self.timers_accumulator = 0;
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// Speed relative to real time (e.g. fast forward/slow motion); affects only `run()`.
+    ///
+    /// Multipliers that are not finite and positive are rejected, leaving the speed unchanged.
+    ///
+    pub fn set_speed(&mut self, speed: Speed) -> Result<(), Chip8Error> {
+        if let Speed::Multiplier(multiplier) = speed {
+            if !multiplier.is_finite() || multiplier <= 0.0 {
+                return Err(Chip8Error::InvalidSpeed { multiplier });
             }
         }
+
+        self.speed = speed;
+
+        Ok(())
+    }
+
+    /// True while an FX0A instruction waits for a key.
+    ///
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.key_wait.is_some()
+    }
+
+    /// Number of frames (timers ticks) emulated so far.
+    ///
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            V: self.V,
+            I: self.I,
+            PC: self.PC,
+            SP: self.SP,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    /// The active stack entries (return addresses), from the bottom.
+    ///
+    pub fn stack(&self) -> &[usize] {
+        &self.stack[..self.SP]
+    }
+
+    pub fn memory(&self) -> &[Byte] {
+        &self.ram[..]
+    }
+
+    pub fn io_frontend(&self) -> &T {
+        &self.io_frontend
+    }
+
+    pub fn io_frontend_mut(&mut self) -> &mut T {
+        &mut self.io_frontend
+    }
+
+    /// Replaces the (default) random generator.
+    ///
+    pub fn set_random_generator(&mut self, random_generator: Box<dyn RandomGenerator>) {
+        self.random_generator = random_generator;
+    }
+
+    /// Sets the colors of the planes combinations; see `DEFAULT_PALETTE`.
+    ///
+    pub fn set_palette(&mut self, palette: [Pixel; 4]) {
+        self.palette = palette;
+        self.screen_changed = true;
     }
 
     fn setup_graphics(&mut self) {
-        self.screen = vec![Pixel::OFF; self.screen_width * self.screen_height];
+        self.screen = vec![0; self.screen_width * self.screen_height];
+        self.screen_changed = true;
         self.io_frontend
             .init(self.screen_width as u32, self.screen_height as u32);
     }
 
-    fn emulate_cycle(&mut self, emulation_running: &mut bool, screen_drawn: &mut bool) {
+    fn update_frontend_screen(&mut self, force_update: bool) {
+        if self.screen_changed {
+            let palette = self.palette;
+
+            self.frame.clear();
+            self.frame
+                .extend(self.screen.iter().map(|planes| palette[*planes as usize]));
+
+            self.screen_changed = false;
+        }
+
+        self.io_frontend.update_screen(&self.frame, force_update);
+    }
+
+    // Returns true if the timers ticked.
+    //
+    fn emulate_step(&mut self) -> Result<bool, Chip8Error> {
+        if !self.emulation_running {
+            return Ok(false);
+        }
+
+        let previous_sound_timer = self.sound_timer;
+
+        let cycles = self.emulate_cycle()?;
+
+        self.update_frontend_screen(false);
+
+        // With movies, the keys are handled only at the frame boundaries.
+        //
+        if self.movie.is_none() {
+            self.set_keys();
+        }
+
+        // The timers run at a rate that is not a divisor of the clock speed, so an accumulator is
+        // used, which keeps the ratio exact over time.
+        //
+        let frame_cycles = match self.timing {
+            Timing::Fixed => self.clock_speed,
+            Timing::CosmacVip => timing::VIP_FRAME_CYCLES,
+        };
+
+        self.timers_accumulator += cycles;
+
+        let timers_ticked = self.timers_accumulator >= frame_cycles;
+
+        if timers_ticked {
+            // A cycle can't span more than a frame; the excess (e.g. a long DXYN) is dropped.
+            //
+            self.timers_accumulator =
+                (self.timers_accumulator - frame_cycles).min(frame_cycles - 1);
+            self.update_timers();
+            self.vblank_occurred = true;
+            self.frame_count += 1;
+            self.profile_frame();
+        }
+
+        self.handle_sound_playback(previous_sound_timer);
+
+        if timers_ticked && self.movie.is_some() {
+            self.handle_movie_frame()?;
+        }
+
+        // Here rather than in `run_frame()`, so that the snapshots are taken regardless of how the
+        // machine is driven (e.g. `step()`/`run_for()`).
+        //
+        if timers_ticked {
+            self.record_rewind_snapshot();
+        }
+
+        Ok(timers_ticked)
+    }
+
+    // Returns the cost of the cycle; see `timers_accumulator`.
+    //
+    fn emulate_cycle(&mut self) -> Result<u32, Chip8Error> {
+        if self.key_wait.is_some() {
+            self.handle_key_wait();
+            return Ok(self.idle_cycle_cost());
+        }
+
         // The decode/execute stages are conventionally split. In this system there is not real need
         // for this, so, for simplicity, they're merged. A separate-stages design would likely have
         // a function pointer and the operands as intermediate values.
         //
-        let instruction = self.cycle_fetch();
+        let instruction = self.cycle_fetch()?;
 
-        self.cycle_decode_execute(instruction, emulation_running, screen_drawn);
+        self.cycle_decode_execute(instruction)
     }
 
-    // Return true if a quit event has been received.
+    // Stops the emulation if a quit event has been received.
     //
-    fn set_keys(&mut self, emulation_running: &mut bool) {
+    fn set_keys(&mut self) {
         while let Some((keycode, key_pressed)) = self.io_frontend.read_event(false) {
+            if self.movie.is_none() && self.handle_rewind_event(&keycode, key_pressed) {
+                continue;
+            }
+
             let key_index = match keycode {
                 EventCode::KeyNum0 => 0,
                 EventCode::KeyNum1 => 1,
@@ -232,7 +717,7 @@ impl<'a, T: IoFrontend> Chip8<'a, T> {
                 EventCode::KeyE => 14,
                 EventCode::KeyF => 15,
                 EventCode::Quit => {
-                    *emulation_running = false;
+                    self.emulation_running = false;
                     return;
                 }
                 _ => continue,
@@ -242,6 +727,57 @@ impl<'a, T: IoFrontend> Chip8<'a, T> {
         }
     }
 
+    // Used when the keys are not taken from the frontend (movie playback).
+    //
+    fn handle_quit_events(&mut self) {
+        while let Some((keycode, _)) = self.io_frontend.read_event(false) {
+            if keycode == EventCode::Quit {
+                self.emulation_running = false;
+            }
+        }
+    }
+
+    // Invoked on each cycle while waiting for a key; the keys are sampled by the step (or by the
+    // movie), so the transitions are detected here.
+    //
+    fn handle_key_wait(&mut self) {
+        let mut key_wait = self.key_wait.unwrap();
+        let mut completing_key = None;
+
+        for (key_index, (pressed, previously_pressed)) in self
+            .keys_status
+            .iter()
+            .zip(key_wait.previous_keys.iter())
+            .enumerate()
+        {
+            let just_pressed = *pressed && !*previously_pressed;
+
+            if self.quirks.key_wait_release {
+                if just_pressed && key_wait.pressed_key.is_none() {
+                    key_wait.pressed_key = Some(key_index);
+                } else if !*pressed && key_wait.pressed_key == Some(key_index) {
+                    completing_key = Some(key_index);
+                    break;
+                }
+            } else if just_pressed {
+                completing_key = Some(key_index);
+                break;
+            }
+        }
+
+        match completing_key {
+            Some(key_index) => {
+                self.V[key_wait.Vx] = key_index as Byte;
+                self.PC += 2;
+                self.key_wait = None;
+            }
+            None => {
+                key_wait.previous_keys = self.keys_status;
+                self.key_wait = Some(key_wait);
+            }
+        }
+    }
+
     fn update_timers(&mut self) {
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
@@ -267,194 +803,233 @@ impl<'a, T: IoFrontend> Chip8<'a, T> {
 
     // CYCLE MAIN STAGES ///////////////////////////////////////////////////////////////////////////
 
-    fn cycle_fetch(&self) -> Word {
-        let instruction_hi_byte = self.ram[self.PC] as Word;
-        let instruction_lo_byte = self.ram[self.PC + 1] as Word;
-        (instruction_hi_byte << 8) + instruction_lo_byte
+    fn cycle_fetch(&self) -> Result<Word, Chip8Error> {
+        let instruction_hi_byte = self.fetch_memory(self.PC)? as Word;
+        let instruction_lo_byte = self.fetch_memory(self.PC + 1)? as Word;
+        Ok((instruction_hi_byte << 8) + instruction_lo_byte)
     }
 
-    fn cycle_decode_execute(
-        &mut self,
-        instruction: Word,
-        emulation_running: &mut bool,
-        screen_drawn: &mut bool,
-    ) {
-        // When used alone, nibble1 and/or nibble2 are always Vx/Vy; nibble0 and nibble3
-        // are never used alone.
+    fn cycle_decode_execute(&mut self, opcode: Word) -> Result<u32, Chip8Error> {
+        let instruction = Instruction::decode(opcode).map_err(|_| Chip8Error::InvalidOpcode {
+            pc: self.PC,
+            opcode,
+        })?;
+
+        // Computed before the execution, since some costs depend on the registers.
         //
-        let nibble0 = (instruction >> 12) as usize;
-        let Vx = ((instruction & 0x0F00) >> 8) as usize;
-        let Vy = ((instruction & 0x00F0) >> 4) as usize;
-        let nibble3 = (instruction & 0x000F) as usize;
-
-        match (nibble0, Vx, Vy, nibble3) {
-            // Some instructions are in the 0x0NNN range (machine code routine call), and need to be
-            // placed before it, therefore, out of order.
+        let mut cycles = match self.timing {
+            Timing::Fixed => TIMERS_SPEED,
+            Timing::CosmacVip => timing::vip_cycles(&instruction, &self.V),
+        };
+        let PC = self.PC;
+        let previous_registers = if self.is_tracing() {
+            self.traced_memory_writes.clear();
+            Some(self.registers())
+        } else {
+            None
+        };
+
+        match instruction {
+            Instruction::ScrollDown(lines) => self.execute_scroll_down(lines),
+            Instruction::ScrollUp(lines) => self.execute_scroll_up(lines),
+            Instruction::ClearScreen => self.execute_clear_screen(),
+            Instruction::Return => self.execute_return_from_subroutine()?,
+            Instruction::ScrollRight => self.execute_scroll_right(),
+            Instruction::ScrollLeft => self.execute_scroll_left(),
+            Instruction::Exit => self.execute_exit(),
+            Instruction::LoresMode => self.execute_set_lores_mode(),
+            Instruction::HiresMode => self.execute_set_hires_mode(),
+            // Call machine code routine; supported only in hybrid mode.
             //
-            (0, 0, 0xD, _) => panic!("Unsupported instruction: 00DN (XO-CHIP)"),
-            (0, 0, 0xE, 0) => {
-                self.execute_clear_screen();
-            }
-            (0, 0, 0xE, 0xE) => {
-                self.execute_return_from_subroutine();
-            }
-            (0, 0, 0xF, 0xC) => {
-                panic!("Unsupported instruction: 00FC (Super-CHIP 1.1)");
-            }
-            (0, 0, 0xF, 0xF) => {
-                self.execute_set_hires_mode();
-            }
-            (0, _, _, _) => panic!(
-                "Call machine code routine instruction or extension not implemented: {:04X}",
-                instruction
-            ),
-            (1, _, _, _) => {
-                let address = (instruction & 0x0FFF) as usize;
-                self.execute_goto(address);
-            }
-            (2, _, _, _) => {
-                let address = (instruction & 0x0FFF) as usize;
-                self.execute_call_subroutine(address);
-            }
-            (3, _, _, _) => {
-                let n = (instruction & 0x00FF) as Byte;
-                self.execute_skip_next_instruction_if_Vx_equals_n(Vx, n);
-            }
-            (4, _, _, _) => {
-                let n = (instruction & 0x00FF) as Byte;
-                self.execute_skip_next_instruction_if_Vx_not_equals_n(Vx, n);
-            }
-            (5, _, _, 0) => {
-                self.execute_skip_next_instruction_if_Vx_equals_Vy(Vx, Vy);
-            }
-            (5, _, _, 2) => {
-                panic!("Unsupported instruction: 5XY2 (XO-CHIP)");
-            }
-            (5, _, _, 3) => {
-                panic!("Unsupported instruction: 5XY3 (XO-CHIP)");
-            }
-            (6, _, _, _) => {
-                let n = (instruction & 0x00FF) as Byte;
-                self.execute_set_Vx_to_n(Vx, n);
-            }
-            (7, _, _, _) => {
-                let n = (instruction & 0x00FF) as Byte;
-                self.execute_add_n_to_Vx(Vx, n);
-            }
-            (8, _, _, 0) => {
-                self.execute_set_Vx_to_Vy(Vx, Vy);
-            }
-            (8, _, _, 1) => {
-                self.execute_set_Vx_to_Vx_or_Vy(Vx, Vy);
-            }
-            (8, _, _, 2) => {
-                self.execute_set_Vx_to_Vx_and_Vy(Vx, Vy);
-            }
-            (8, _, _, 3) => {
-                self.execute_set_Vx_to_Vx_xor_Vy(Vx, Vy);
-            }
-            (8, _, _, 4) => {
-                self.execute_add_Vy_to_Vx(Vx, Vy);
-            }
-            (8, _, _, 5) => {
-                self.execute_subtract_Vy_from_Vx(Vx, Vy);
-            }
-            (8, _, _, 6) => {
-                // Vy is ignored
-                self.execute_shift_right_Vx(Vx);
-            }
-            (8, _, _, 7) => {
-                self.execute_set_Vx_to_Vy_minus_Vx(Vx, Vy);
-            }
-            (8, _, _, 0xE) => {
-                let Vx = ((instruction & 0x0F00) >> 8) as usize;
-                // Vy is ignored
-                self.execute_shift_left_Vx(Vx);
-            }
-            (9, _, _, 0) => {
-                self.execute_skip_next_instruction_if_Vx_not_equals_Vy(Vx, Vy);
-            }
-            (0xA, _, _, _) => {
-                let value = (instruction & 0x0FFF) as usize;
-                self.execute_set_I(value);
-            }
-            (0xB, _, _, _) => {
-                let address = (instruction & 0x0FFF) as usize;
-                self.execute_goto_plus_V0(address);
-            }
-            (0xC, _, _, _) => {
-                let n = (instruction & 0x00FF) as Byte;
-                self.execute_set_Vx_to_masked_random(Vx, n);
-            }
-            (0xD, _, _, _) => {
-                let lines = (instruction & 0x00F) as usize;
-                self.execute_draw_sprite(Vx, Vy, lines);
-            }
-            (0xE, _, 9, 0xE) => {
-                self.execute_skip_next_instruction_if_Vx_key_pressed(Vx);
-            }
-            (0xE, _, 0xA, 1) => {
-                self.execute_skip_next_instruction_if_Vx_key_not_pressed(Vx);
-            }
-            (0xF, 0, 0, 0) => {
-                panic!("Unsupported instruction: F000 (XO-CHIP)");
-            }
-            (0xF, _, 0, 1) => {
-                panic!("Unsupported instruction: FN01 (XO-CHIP)");
-            }
-            (0xF, _, 0, 2) => {
-                panic!("Unsupported instruction: F002 (XO-CHIP)");
+            Instruction::MachineCall(address) if self.hybrid_cpu.is_some() => {
+                let routine_cycles = self.execute_machine_routine(address)?;
+
+                if self.timing == Timing::CosmacVip {
+                    cycles += routine_cycles;
+                }
             }
-            (0xF, _, 0, 7) => {
-                self.execute_set_Vx_to_delay_timer(Vx);
+            Instruction::MachineCall(_) => {
+                return Err(Chip8Error::InvalidOpcode {
+                    pc: self.PC,
+                    opcode,
+                });
             }
-            (0xF, _, 0, 0xA) => {
-                self.execute_wait_keypress(Vx, emulation_running, screen_drawn);
+            Instruction::Jump(address) => self.execute_goto(address),
+            Instruction::Call(address) => self.execute_call_subroutine(address)?,
+            Instruction::SkipIfEqualByte(Vx, n) => {
+                self.execute_skip_next_instruction_if_Vx_equals_n(Vx, n)
             }
-            (0xF, _, 1, 5) => {
-                self.execute_set_delay_timer_to_Vx(Vx);
+            Instruction::SkipIfNotEqualByte(Vx, n) => {
+                self.execute_skip_next_instruction_if_Vx_not_equals_n(Vx, n)
             }
-            (0xF, _, 1, 8) => {
-                self.execute_set_sound_timer_to_Vx(Vx);
+            Instruction::SkipIfEqual(Vx, Vy) => {
+                self.execute_skip_next_instruction_if_Vx_equals_Vy(Vx, Vy)
             }
-            (0xF, _, 1, 0xE) => {
-                self.execute_add_Vx_to_I(Vx);
+            Instruction::StoreRange(Vx, Vy) => {
+                self.execute_dump_registers_range_to_memory(Vx, Vy)?
             }
-            (0xF, _, 2, 0x9) => {
-                self.execute_set_I_to_Vx_sprite_address(Vx);
+            Instruction::LoadRange(Vx, Vy) => {
+                self.execute_load_registers_range_from_memory(Vx, Vy)?
             }
-            (0xF, _, 3, 3) => {
-                self.execute_store_Vx_bcd_representation(Vx);
+            Instruction::SetByte(Vx, n) => self.execute_set_Vx_to_n(Vx, n),
+            Instruction::AddByte(Vx, n) => self.execute_add_n_to_Vx(Vx, n),
+            Instruction::Set(Vx, Vy) => self.execute_set_Vx_to_Vy(Vx, Vy),
+            Instruction::Or(Vx, Vy) => self.execute_set_Vx_to_Vx_or_Vy(Vx, Vy),
+            Instruction::And(Vx, Vy) => self.execute_set_Vx_to_Vx_and_Vy(Vx, Vy),
+            Instruction::Xor(Vx, Vy) => self.execute_set_Vx_to_Vx_xor_Vy(Vx, Vy),
+            Instruction::Add(Vx, Vy) => self.execute_add_Vy_to_Vx(Vx, Vy),
+            Instruction::Subtract(Vx, Vy) => self.execute_subtract_Vy_from_Vx(Vx, Vy),
+            Instruction::ShiftRight(Vx, Vy) => self.execute_shift_right_Vx(Vx, Vy),
+            Instruction::SubtractReverse(Vx, Vy) => self.execute_set_Vx_to_Vy_minus_Vx(Vx, Vy),
+            Instruction::ShiftLeft(Vx, Vy) => self.execute_shift_left_Vx(Vx, Vy),
+            Instruction::SkipIfNotEqual(Vx, Vy) => {
+                self.execute_skip_next_instruction_if_Vx_not_equals_Vy(Vx, Vy)
             }
-            (0xF, _, 5, 5) => {
-                self.execute_dump_registers_to_memory(Vx);
+            Instruction::SetI(value) => self.execute_set_I(value),
+            Instruction::JumpPlusV0(address, Vx) => self.execute_goto_plus_V0(address, Vx),
+            Instruction::Random(Vx, n) => self.execute_set_Vx_to_masked_random(Vx, n),
+            Instruction::Draw(Vx, Vy, lines) => self.execute_draw_sprite(Vx, Vy, lines)?,
+            Instruction::SkipIfKeyPressed(Vx) => {
+                self.execute_skip_next_instruction_if_Vx_key_pressed(Vx)
             }
-            (0xF, _, 6, 5) => {
-                self.execute_load_registers_from_memory(Vx);
+            Instruction::SkipIfKeyNotPressed(Vx) => {
+                self.execute_skip_next_instruction_if_Vx_key_not_pressed(Vx)
             }
-            _ => panic!("Invalid/unsupported instruction: {:04X}", instruction),
+            Instruction::SetILong => self.execute_set_I_to_long_address()?,
+            Instruction::SelectPlanes(planes) => self.execute_select_planes(planes),
+            Instruction::LoadAudioPattern => self.execute_load_audio_pattern()?,
+            Instruction::GetDelayTimer(Vx) => self.execute_set_Vx_to_delay_timer(Vx),
+            Instruction::WaitKey(Vx) => self.execute_wait_keypress(Vx),
+            Instruction::SetDelayTimer(Vx) => self.execute_set_delay_timer_to_Vx(Vx),
+            Instruction::SetSoundTimer(Vx) => self.execute_set_sound_timer_to_Vx(Vx),
+            Instruction::AddToI(Vx) => self.execute_add_Vx_to_I(Vx),
+            Instruction::SetIToFont(Vx) => self.execute_set_I_to_Vx_sprite_address(Vx),
+            Instruction::SetIToBigFont(Vx) => self.execute_set_I_to_Vx_big_sprite_address(Vx),
+            Instruction::StoreBcd(Vx) => self.execute_store_Vx_bcd_representation(Vx)?,
+            Instruction::SetPitch(Vx) => self.execute_set_pitch_to_Vx(Vx),
+            Instruction::Store(Vx) => self.execute_dump_registers_to_memory(Vx)?,
+            Instruction::Load(Vx) => self.execute_load_registers_from_memory(Vx)?,
+            Instruction::StoreRplFlags(Vx) => self.execute_store_registers_to_rpl_flags(Vx),
+            Instruction::LoadRplFlags(Vx) => self.execute_load_registers_from_rpl_flags(Vx),
+        }
+
+        // A DXYN waiting for the vblank (display wait quirk) is repeated, so it's charged as idle.
+        //
+        if matches!(instruction, Instruction::Draw(..)) && self.PC == PC {
+            return Ok(self.idle_cycle_cost());
+        }
+
+        if let Some(previous_registers) = previous_registers {
+            self.trace_instruction(PC, opcode, instruction, &previous_registers);
+        }
+
+        self.profile_instruction(PC, instruction);
+
+        Ok(cycles)
+    }
+
+    fn idle_cycle_cost(&self) -> u32 {
+        match self.timing {
+            Timing::Fixed => TIMERS_SPEED,
+            Timing::CosmacVip => timing::VIP_IDLE_CYCLES,
         }
     }
 
     // OPCODE EXECUTION ////////////////////////////////////////////////////////////////////////////
 
+    fn execute_scroll_down(&mut self, lines: usize) {
+        let shift = (lines * self.screen_width).min(self.screen.len());
+
+        for i in (0..self.screen.len()).rev() {
+            let source = if i >= shift {
+                self.screen[i - shift]
+            } else {
+                0
+            };
+            self.move_selected_planes(i, source);
+        }
+
+        self.PC += 2;
+    }
+
+    fn execute_scroll_up(&mut self, lines: usize) {
+        let shift = (lines * self.screen_width).min(self.screen.len());
+
+        for i in 0..self.screen.len() {
+            let source = *self.screen.get(i + shift).unwrap_or(&0);
+            self.move_selected_planes(i, source);
+        }
+
+        self.PC += 2;
+    }
+
     fn execute_clear_screen(&mut self) {
-        self.log(format!("[{:X}] CLS", self.PC));
+        for i in 0..self.screen.len() {
+            self.move_selected_planes(i, 0);
+        }
 
-        self.screen = vec![Pixel::OFF; self.screen_width * self.screen_height];
         self.PC += 2;
     }
 
-    fn execute_return_from_subroutine(&mut self) {
-        self.log(format!("[{:X}] RET", self.PC));
+    fn execute_return_from_subroutine(&mut self) -> Result<(), Chip8Error> {
+        if self.SP == 0 {
+            return Err(Chip8Error::StackUnderflow { pc: self.PC });
+        }
 
         self.SP -= 1;
         self.PC = self.stack[self.SP];
+
+        Ok(())
     }
 
-    fn execute_set_hires_mode(&mut self) {
-        self.log(format!("[{:X}] HIRES", self.PC));
+    fn execute_scroll_right(&mut self) {
+        for y in 0..self.screen_height {
+            let line_start = y * self.screen_width;
+
+            for x in (0..self.screen_width).rev() {
+                let source = if x >= HORIZONTAL_SCROLL_PIXELS {
+                    self.screen[line_start + x - HORIZONTAL_SCROLL_PIXELS]
+                } else {
+                    0
+                };
+                self.move_selected_planes(line_start + x, source);
+            }
+        }
+
+        self.PC += 2;
+    }
 
+    fn execute_scroll_left(&mut self) {
+        for y in 0..self.screen_height {
+            let line_start = y * self.screen_width;
+
+            for x in 0..self.screen_width {
+                let source = if x + HORIZONTAL_SCROLL_PIXELS < self.screen_width {
+                    self.screen[line_start + x + HORIZONTAL_SCROLL_PIXELS]
+                } else {
+                    0
+                };
+                self.move_selected_planes(line_start + x, source);
+            }
+        }
+
+        self.PC += 2;
+    }
+
+    // The PC is not advanced, for consistency with the machine being halted.
+    //
+    fn execute_exit(&mut self) {
+        self.emulation_running = false;
+    }
+
+    fn execute_set_lores_mode(&mut self) {
+        self.screen_width = STANDARD_SCREEN_WIDTH;
+        self.screen_height = STANDARD_SCREEN_HEIGHT;
+        self.setup_graphics();
+        self.PC += 2;
+    }
+
+    fn execute_set_hires_mode(&mut self) {
         self.screen_width = HIRES_SCREEN_WIDTH;
         self.screen_height = HIRES_SCREEN_HEIGHT;
         self.setup_graphics();
@@ -462,95 +1037,92 @@ impl<'a, T: IoFrontend> Chip8<'a, T> {
     }
 
     fn execute_goto(&mut self, address: usize) {
-        self.log(format!("[{:X}] JP 0x{:X}", self.PC, address));
-
         self.PC = address;
     }
 
-    fn execute_call_subroutine(&mut self, address: usize) {
-        self.log(format!("[{:X}] CALL 0x{:X}", self.PC, address));
+    fn execute_call_subroutine(&mut self, address: usize) -> Result<(), Chip8Error> {
+        if self.SP == self.stack.len() {
+            return Err(Chip8Error::StackOverflow { pc: self.PC });
+        }
 
         self.stack[self.SP] = self.PC + 2;
         self.SP += 1;
         self.PC = address;
+
+        Ok(())
     }
 
     fn execute_skip_next_instruction_if_Vx_equals_n(&mut self, Vx: usize, n: Byte) {
-        self.log(format!("[{:X}] SE V{}, 0x{:X}", self.PC, Vx, n));
-
         if self.V[Vx] == n {
-            self.PC += 4;
+            self.skip_next_instruction();
         } else {
             self.PC += 2;
         }
     }
 
     fn execute_skip_next_instruction_if_Vx_not_equals_n(&mut self, Vx: usize, n: Byte) {
-        self.log(format!("[{:X}] SNE V{}, 0x{:X}", self.PC, Vx, n));
-
         if self.V[Vx] != n {
-            self.PC += 4;
+            self.skip_next_instruction();
         } else {
             self.PC += 2;
         }
     }
 
     fn execute_skip_next_instruction_if_Vx_equals_Vy(&mut self, Vx: usize, Vy: usize) {
-        self.log(format!("[{:X}] SE V{}, V{}", self.PC, Vx, Vy));
-
         if self.V[Vx] == self.V[Vy] {
-            self.PC += 4;
+            self.skip_next_instruction();
         } else {
             self.PC += 2;
         }
     }
 
     fn execute_set_Vx_to_n(&mut self, Vx: usize, n: Byte) {
-        self.log(format!("[{:X}] LD V{}, 0x{:X}", self.PC, Vx, n));
-
         self.V[Vx] = n;
         self.PC += 2;
     }
 
     fn execute_add_n_to_Vx(&mut self, Vx: usize, n: Byte) {
-        self.log(format!("[{:X}] ADD V{}, 0x{:X}", self.PC, Vx, n));
-
         let (addition_result, _) = self.V[Vx].overflowing_add(n);
         self.V[Vx] = addition_result;
         self.PC += 2;
     }
 
     fn execute_set_Vx_to_Vy(&mut self, Vx: usize, Vy: usize) {
-        self.log(format!("[{:X}] LD V{}, V{}", self.PC, Vx, Vy));
-
         self.V[Vx] = self.V[Vy];
         self.PC += 2;
     }
 
     fn execute_set_Vx_to_Vx_or_Vy(&mut self, Vx: usize, Vy: usize) {
-        self.log(format!("[{:X}] OR V{}, V{}", self.PC, Vx, Vy));
-
         self.V[Vx] |= self.V[Vy];
+
+        if self.quirks.logic_resets_VF {
+            self.V[15] = 0;
+        }
+
         self.PC += 2;
     }
 
     fn execute_set_Vx_to_Vx_and_Vy(&mut self, Vx: usize, Vy: usize) {
-        self.log(format!("[{:X}] AND V{}, V{}", self.PC, Vx, Vy));
-
         self.V[Vx] &= self.V[Vy];
+
+        if self.quirks.logic_resets_VF {
+            self.V[15] = 0;
+        }
+
         self.PC += 2;
     }
 
     fn execute_set_Vx_to_Vx_xor_Vy(&mut self, Vx: usize, Vy: usize) {
-        self.log(format!("[{:X}] XOR V{}, V{}", self.PC, Vx, Vy));
-
         self.V[Vx] ^= self.V[Vy];
+
+        if self.quirks.logic_resets_VF {
+            self.V[15] = 0;
+        }
+
         self.PC += 2;
     }
 
     fn execute_add_Vy_to_Vx(&mut self, Vx: usize, Vy: usize) {
-        self.log(format!("[{:X}] ADD V{}, V{}", self.PC, Vx, Vy));
-
         let (addition_result, carry) = self.V[Vx].overflowing_add(self.V[Vy]);
         self.V[Vx] = addition_result;
         self.V[15] = carry as Byte;
@@ -558,243 +1130,235 @@ impl<'a, T: IoFrontend> Chip8<'a, T> {
     }
 
     fn execute_subtract_Vy_from_Vx(&mut self, Vx: usize, Vy: usize) {
-        self.log(format!("[{:X}] SUB V{}, V{}", self.PC, Vx, Vy));
-
         let (subtraction_result, carry) = self.V[Vx].overflowing_sub(self.V[Vy]);
         self.V[Vx] = subtraction_result;
         self.V[15] = (!carry) as Byte;
         self.PC += 2;
     }
 
-    fn execute_shift_right_Vx(&mut self, Vx: usize) {
-        self.log(format!("[{:X}] SHR V{} {{, Vy}}", self.PC, Vx));
+    fn execute_shift_right_Vx(&mut self, Vx: usize, Vy: usize) {
+        let source = if self.quirks.shift_uses_Vy { Vy } else { Vx };
+        let shifted_out = self.V[source] & 1;
 
-        self.V[15] = self.V[Vx] & 1;
-        self.V[Vx] >>= 1;
+        // The flag is set last, so that it takes priority when Vx is VF.
+        //
+        self.V[Vx] = self.V[source] >> 1;
+        self.V[15] = shifted_out;
         self.PC += 2;
     }
 
     fn execute_set_Vx_to_Vy_minus_Vx(&mut self, Vx: usize, Vy: usize) {
-        self.log(format!("[{:X}] SUBN V{}, V{}", self.PC, Vx, Vy));
-
         let (subtraction_result, carry) = self.V[Vy].overflowing_sub(self.V[Vx]);
         self.V[Vx] = subtraction_result;
         self.V[15] = (!carry) as Byte;
         self.PC += 2;
     }
 
-    fn execute_shift_left_Vx(&mut self, Vx: usize) {
-        self.log(format!("[{:X}] SHL V{} {{, Vy}}", self.PC, Vx));
+    fn execute_shift_left_Vx(&mut self, Vx: usize, Vy: usize) {
+        let source = if self.quirks.shift_uses_Vy { Vy } else { Vx };
+        let shifted_out = self.V[source] >> 7;
 
-        self.V[15] = self.V[Vx] >> 7;
-        self.V[Vx] <<= 1;
+        self.V[Vx] = self.V[source] << 1;
+        self.V[15] = shifted_out;
         self.PC += 2;
     }
 
     fn execute_skip_next_instruction_if_Vx_not_equals_Vy(&mut self, Vx: usize, Vy: usize) {
-        self.log(format!("[{:X}] SNE V{}, V{}", self.PC, Vx, Vy));
-
         if self.V[Vx] != self.V[Vy] {
-            self.PC += 4;
+            self.skip_next_instruction();
         } else {
             self.PC += 2;
         }
     }
 
     fn execute_set_I(&mut self, value: usize) {
-        self.log(format!("[{:X}] LD I, 0x{:X}", self.PC, value));
         self.I = value;
         self.PC += 2;
     }
 
-    fn execute_goto_plus_V0(&mut self, address: usize) {
-        self.log(format!("[{:X}] JP V0, 0x{:X}", self.PC, address));
+    // With the jump quirk, the register is the address high nibble (BXNN).
+    //
+    fn execute_goto_plus_V0(&mut self, address: usize, Vx: usize) {
+        let register = if self.quirks.jump_uses_Vx { Vx } else { 0 };
 
-        self.PC = address + self.V[0] as usize;
+        self.PC = address + self.V[register] as usize;
     }
 
     fn execute_set_Vx_to_masked_random(&mut self, Vx: usize, n: Byte) {
-        self.log(format!("[{:X}] RND V{}, 0x{:X}", self.PC, Vx, n));
-
-        self.V[Vx] = rand::random::<Byte>() & n;
+        self.V[Vx] = self.random_generator.next_byte() & n;
         self.PC += 2;
     }
 
-    fn execute_draw_sprite(&mut self, Vx: usize, Vy: usize, lines: usize) {
-        self.log(format!(
-            "[{:X}] DRW V{}, V{}, {}; I={:X}, x={}, y={}",
-            self.PC, Vx, Vy, lines, self.I, self.V[Vx], self.V[Vy],
-        ));
+    fn execute_draw_sprite(
+        &mut self,
+        Vx: usize,
+        Vy: usize,
+        lines: usize,
+    ) -> Result<(), Chip8Error> {
+        if self.quirks.display_wait && !self.vblank_occurred {
+            // Don't advance the PC, so that the instruction is repeated until the vblank.
+            //
+            return Ok(());
+        }
 
-        let top_x = self.V[Vx] as usize;
-        let top_y = self.V[Vy] as usize;
+        // The starting coordinates always wrap.
+        //
+        let top_x = self.V[Vx] as usize % self.screen_width;
+        let top_y = self.V[Vy] as usize % self.screen_height;
 
         // lines = 0 is a Superchip feature; see https://chip8.fandom.com/wiki/Instruction_Draw.
         //
         let (bytes_per_line, lines) = if lines == 0 { (2, 16) } else { (1, lines) };
 
+        // With multiple planes selected (XO-CHIP), the sprite data of each plane follows the
+        // previous one.
+        //
+        // The data is read upfront, so that the screen is not modified in case of error.
+        //
+        let planes_count = self.selected_planes.count_ones() as usize;
+        let sprite_data = self.read_memory_slice(self.I, bytes_per_line * lines * planes_count)?;
+
+        self.vblank_occurred = false;
+
         let mut sprite_collided: Byte = 0;
+        let mut plane_data_start = 0;
 
-        for y_shift in 0..lines {
-            let pixel_y = (top_y + y_shift) % self.screen_height;
+        for plane in 0..PLANES_COUNT {
+            let plane_mask = 1 << plane;
 
-            for sprite_line_index in 0..bytes_per_line {
-                for x_shift in 0..8 {
-                    // Sprites wrap around; see http://devernay.free.fr/hacks/chip8/C8TECH10.HTM#Dxyn.
-                    //
-                    let pixel_x = (top_x + x_shift + 8 * sprite_line_index) % self.screen_width;
+            if self.selected_planes & plane_mask == 0 {
+                continue;
+            }
 
-                    let pixel_source_byte =
-                        self.ram[self.I + bytes_per_line * y_shift + sprite_line_index];
+            for y_shift in 0..lines {
+                if self.quirks.clip_sprites && top_y + y_shift >= self.screen_height {
+                    break;
+                }
 
-                    let pixel_value = (pixel_source_byte << x_shift) & 0b1000_0000;
+                let pixel_y = (top_y + y_shift) % self.screen_height;
 
-                    if pixel_value != 0 {
-                        let pixel_screen_index = self.screen_width * pixel_y + pixel_x;
+                for sprite_line_index in 0..bytes_per_line {
+                    for x_shift in 0..8 {
+                        if self.quirks.clip_sprites
+                            && top_x + x_shift + 8 * sprite_line_index >= self.screen_width
+                        {
+                            break;
+                        }
 
-                        // The two `screen` assignments constitute one XOR operation.
+                        // Without clipping, sprites wrap around; see http://devernay.free.fr/hacks/chip8/C8TECH10.HTM#Dxyn.
                         //
-                        if self.screen[pixel_screen_index] == Pixel::ON {
-                            sprite_collided = 1;
-                        }
+                        let pixel_x = (top_x + x_shift + 8 * sprite_line_index) % self.screen_width;
+
+                        let pixel_source_byte = sprite_data
+                            [plane_data_start + bytes_per_line * y_shift + sprite_line_index];
+
+                        let pixel_value = (pixel_source_byte << x_shift) & 0b1000_0000;
+
+                        if pixel_value != 0 {
+                            let pixel_screen_index = self.screen_width * pixel_y + pixel_x;
+
+                            if self.screen[pixel_screen_index] & plane_mask != 0 {
+                                sprite_collided = 1;
+                            }
 
-                        self.screen[pixel_screen_index] ^= Pixel::ON;
+                            self.screen[pixel_screen_index] ^= plane_mask;
+                        }
                     }
                 }
             }
+
+            plane_data_start += bytes_per_line * lines;
         }
 
+        self.screen_changed = true;
+
         self.V[15] = sprite_collided;
         self.PC += 2;
+
+        Ok(())
     }
 
     fn execute_skip_next_instruction_if_Vx_key_pressed(&mut self, Vx: usize) {
-        self.log(format!("[{:X}] SKP V{}", self.PC, Vx));
-
-        let keyIndex = self.V[Vx] as usize;
+        // Only the low nibble is considered, as the original interpreter did.
+        //
+        let keyIndex = (self.V[Vx] & 0x0F) as usize;
 
         if self.keys_status[keyIndex] {
-            self.PC += 4;
+            self.skip_next_instruction();
         } else {
             self.PC += 2;
         }
     }
 
     fn execute_skip_next_instruction_if_Vx_key_not_pressed(&mut self, Vx: usize) {
-        self.log(format!("[{:X}] SKNP V{}", self.PC, Vx));
-
-        let keyIndex = self.V[Vx] as usize;
+        let keyIndex = (self.V[Vx] & 0x0F) as usize;
 
         if !self.keys_status[keyIndex] {
-            self.PC += 4;
+            self.skip_next_instruction();
         } else {
             self.PC += 2;
         }
     }
 
     fn execute_set_Vx_to_delay_timer(&mut self, Vx: usize) {
-        self.log(format!(
-            "[{:X}] LD V{}, DT; DT = {}",
-            self.PC, Vx, self.delay_timer
-        ));
-
         self.V[Vx] = self.delay_timer;
         self.PC += 2;
     }
 
-    fn execute_wait_keypress(
-        &mut self,
-        Vx: usize,
-        emulation_running: &mut bool,
-        screen_drawn: &mut bool,
-    ) {
-        self.log(format!("[{:X}] LD V{}, K", self.PC, Vx));
-
-        self.io_frontend.update_screen(&self.screen, true);
-        *screen_drawn = true;
-
-        loop {
-            if let Some((key_code, key_pressed)) = self.io_frontend.read_event(true) {
-                let key_index = match key_code {
-                    EventCode::KeyNum0 => 0,
-                    EventCode::KeyNum1 => 1,
-                    EventCode::KeyNum2 => 2,
-                    EventCode::KeyNum3 => 3,
-                    EventCode::KeyNum4 => 4,
-                    EventCode::KeyNum5 => 5,
-                    EventCode::KeyNum6 => 6,
-                    EventCode::KeyNum7 => 7,
-                    EventCode::KeyNum8 => 8,
-                    EventCode::KeyNum9 => 9,
-                    EventCode::KeyA => 10,
-                    EventCode::KeyB => 11,
-                    EventCode::KeyC => 12,
-                    EventCode::KeyD => 13,
-                    EventCode::KeyE => 14,
-                    EventCode::KeyF => 15,
-                    EventCode::Quit => {
-                        *emulation_running = false;
-                        return;
-                    }
-                    _ => continue,
-                };
-
-                // Don't forget to register key released events!
-                //
-                self.keys_status[key_index] = key_pressed;
-
-                if key_pressed {
-                    self.V[Vx] = key_index as Byte;
-                    self.PC += 2;
-                    return;
-                }
-            };
-        }
+    // The wait is a machine state, handled by the main loop (see `handle_key_wait()`), so that the
+    // timers, the sound and the screen keep being updated; the PC is advanced when it completes.
+    //
+    fn execute_wait_keypress(&mut self, Vx: usize) {
+        self.key_wait = Some(KeyWait {
+            Vx,
+            previous_keys: self.keys_status,
+            pressed_key: None,
+        });
     }
 
     fn execute_set_delay_timer_to_Vx(&mut self, Vx: usize) {
-        self.log(format!("[{:X}] LD DT, V{}", self.PC, Vx));
-
         self.delay_timer = self.V[Vx];
         self.PC += 2;
     }
 
     fn execute_set_sound_timer_to_Vx(&mut self, Vx: usize) {
-        self.log(format!("[{:X}] LD ST, V{}", self.PC, Vx));
-
         self.sound_timer = self.V[Vx];
         self.PC += 2;
     }
 
     fn execute_add_Vx_to_I(&mut self, Vx: usize) {
-        self.log(format!("[{:X}] ADD I, V{}", self.PC, Vx));
-
         self.I += self.V[Vx] as usize;
         self.PC += 2;
     }
 
     fn execute_set_I_to_Vx_sprite_address(&mut self, Vx: usize) {
-        self.log(format!("[{:X}] LD F, V{}", self.PC, Vx));
-
-        self.I = (self.V[Vx] * 5) as usize;
+        self.I = FONTS_LOCATION + (self.V[Vx] & 0x0F) as usize * 5;
         self.PC += 2;
     }
 
-    fn execute_store_Vx_bcd_representation(&mut self, Vx: usize) {
-        self.log(format!("[{:X}] LD B, V{}", self.PC, Vx));
+    fn execute_set_I_to_Vx_big_sprite_address(&mut self, Vx: usize) {
+        self.I = BIG_FONTS_LOCATION + (self.V[Vx] & 0x0F) as usize * 10;
+        self.PC += 2;
+    }
 
+    fn execute_store_Vx_bcd_representation(&mut self, Vx: usize) -> Result<(), Chip8Error> {
         let most_significant_digit = self.V[Vx] / 100;
         let middle_digit = (self.V[Vx] % 100) / 10;
         let least_significant_digit = self.V[Vx] % 10;
-        self.ram[self.I] = most_significant_digit;
-        self.ram[self.I + 1] = middle_digit;
-        self.ram[self.I + 2] = least_significant_digit;
+        self.write_memory_slice(
+            self.I,
+            &[
+                most_significant_digit,
+                middle_digit,
+                least_significant_digit,
+            ],
+        )?;
         self.PC += 2;
-    }
 
-    fn execute_dump_registers_to_memory(&mut self, Vx: usize) {
-        self.log(format!("[{:X}] LD I, V{}", self.PC, Vx));
+        Ok(())
+    }
 
+    fn execute_dump_registers_to_memory(&mut self, Vx: usize) -> Result<(), Chip8Error> {
         // An amusing, but too verbose, Rust-y approach is
         //
         //   for (address, v) in self.ram.iter_mut().skip(self.I).take(16).zip(self.V.iter()) { /* ... */ }
@@ -803,26 +1367,213 @@ impl<'a, T: IoFrontend> Chip8<'a, T> {
         //
         //   for (i, value) in self.V.iter().enumerate() { /* ... */ }
         //
-        for i in 0..=Vx {
-            self.ram[self.I + i] = self.V[i];
-        }
+        let registers = self.V;
+        self.write_memory_slice(self.I, &registers[0..=Vx])?;
+
+        self.increment_I_after_load_store(Vx);
         self.PC += 2;
+
+        Ok(())
     }
 
-    fn execute_load_registers_from_memory(&mut self, Vx: usize) {
-        self.log(format!("[{:X}] LD V{}, I", self.PC, Vx));
+    fn execute_load_registers_from_memory(&mut self, Vx: usize) -> Result<(), Chip8Error> {
+        let values = self.read_memory_slice(self.I, Vx + 1)?;
+        self.V[0..=Vx].copy_from_slice(&values);
+
+        self.increment_I_after_load_store(Vx);
+        self.PC += 2;
 
-        for i in 0..=Vx {
-            self.V[i] = self.ram[self.I + i];
+        Ok(())
+    }
+
+    fn execute_store_registers_to_rpl_flags(&mut self, Vx: usize) {
+        self.rpl_flags[0..=Vx].copy_from_slice(&self.V[0..=Vx]);
+        self.persist_rpl_flags();
+        self.PC += 2;
+    }
+
+    fn execute_load_registers_from_rpl_flags(&mut self, Vx: usize) {
+        self.V[0..=Vx].copy_from_slice(&self.rpl_flags[0..=Vx]);
+        self.PC += 2;
+    }
+
+    fn execute_dump_registers_range_to_memory(
+        &mut self,
+        Vx: usize,
+        Vy: usize,
+    ) -> Result<(), Chip8Error> {
+        // The range can be reversed; I is not modified.
+        //
+        let values = Self::registers_range(Vx, Vy)
+            .map(|register| self.V[register])
+            .collect::<Vec<_>>();
+        self.write_memory_slice(self.I, &values)?;
+
+        self.PC += 2;
+
+        Ok(())
+    }
+
+    fn execute_load_registers_range_from_memory(
+        &mut self,
+        Vx: usize,
+        Vy: usize,
+    ) -> Result<(), Chip8Error> {
+        let values = self.read_memory_slice(self.I, Vx.max(Vy) - Vx.min(Vy) + 1)?;
+
+        for (register, value) in Self::registers_range(Vx, Vy).zip(values) {
+            self.V[register] = value;
         }
+
+        self.PC += 2;
+
+        Ok(())
+    }
+
+    // F000 NNNN; the only four bytes instruction.
+    //
+    fn execute_set_I_to_long_address(&mut self) -> Result<(), Chip8Error> {
+        let address_hi_byte = self.fetch_memory(self.PC + 2)? as usize;
+        let address_lo_byte = self.fetch_memory(self.PC + 3)? as usize;
+        let address = (address_hi_byte << 8) + address_lo_byte;
+
+        self.I = address;
+        self.PC += 4;
+
+        Ok(())
+    }
+
+    fn execute_select_planes(&mut self, planes: Byte) {
+        // Only the existing planes can be selected; otherwise, DXYN would read sprite data for
+        // planes that are not drawn.
+        //
+        self.selected_planes = planes & ((1 << PLANES_COUNT) - 1);
+        self.PC += 2;
+    }
+
+    fn execute_load_audio_pattern(&mut self) -> Result<(), Chip8Error> {
+        let mut pattern = [0; AUDIO_PATTERN_SIZE];
+        pattern.copy_from_slice(&self.read_memory_slice(self.I, AUDIO_PATTERN_SIZE)?);
+
+        self.audio_state.lock().unwrap().pattern = Some(pattern);
+        self.PC += 2;
+
+        Ok(())
+    }
+
+    fn execute_set_pitch_to_Vx(&mut self, Vx: usize) {
+        self.audio_state.lock().unwrap().pitch = self.V[Vx];
         self.PC += 2;
     }
 
     // HELPERS /////////////////////////////////////////////////////////////////////////////////////
 
-    fn log(&mut self, message: String) {
-        if let Some(logger) = self.logger {
-            logger.log(message);
+    // All the program memory accesses go through these functions.
+    //
+    // Instruction fetches are not data accesses, so they're not recorded.
+    //
+    fn fetch_memory(&self, address: usize) -> Result<Byte, Chip8Error> {
+        self.ram
+            .get(address)
+            .copied()
+            .ok_or(Chip8Error::MemoryOutOfBounds { address })
+    }
+
+    fn read_memory(&mut self, address: usize) -> Result<Byte, Chip8Error> {
+        let value = self.fetch_memory(address)?;
+
+        self.record_memory_access(address, AccessKind::Read);
+
+        Ok(value)
+    }
+
+    fn read_memory_slice(
+        &mut self,
+        address: usize,
+        length: usize,
+    ) -> Result<Vec<Byte>, Chip8Error> {
+        (address..address + length)
+            .map(|address| self.read_memory(address))
+            .collect()
+    }
+
+    // The whole range is validated before writing, so that memory is not partially modified in
+    // case of error.
+    //
+    fn write_memory_slice(&mut self, address: usize, values: &[Byte]) -> Result<(), Chip8Error> {
+        let end_address = address + values.len();
+
+        if end_address > RAM_SIZE {
+            return Err(Chip8Error::MemoryOutOfBounds {
+                address: address.max(RAM_SIZE),
+            });
+        }
+
+        self.ram[address..end_address].copy_from_slice(values);
+
+        for address in address..end_address {
+            self.record_memory_access(address, AccessKind::Write);
+        }
+
+        if self.is_tracing() {
+            for (offset, value) in values.iter().enumerate() {
+                self.traced_memory_writes.push(MemoryWrite {
+                    address: address + offset,
+                    value: *value,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn record_memory_access(&mut self, address: usize, kind: AccessKind) {
+        if let Some(memory_accesses) = &mut self.memory_accesses {
+            memory_accesses.push(MemoryAccess { address, kind });
+        }
+
+        self.profile_memory_access(address, kind);
+    }
+
+    // XO-CHIP: F000 NNNN is a double-length instruction, which must be entirely skipped.
+    //
+    // This is not a program memory access, so the RAM is accessed directly.
+    //
+    fn skip_next_instruction(&mut self) {
+        let next_instruction_hi_byte = self.ram.get(self.PC + 2);
+        let next_instruction_lo_byte = self.ram.get(self.PC + 3);
+
+        if (next_instruction_hi_byte, next_instruction_lo_byte) == (Some(&0xF0), Some(&0x00)) {
+            self.PC += 6;
+        } else {
+            self.PC += 4;
+        }
+    }
+
+    // Replaces the selected planes of the given screen entry with the corresponding ones of
+    // `source`; used by the scrolling/clearing instructions.
+    //
+    fn move_selected_planes(&mut self, pixel_screen_index: usize, source: Byte) {
+        let pixel = &mut self.screen[pixel_screen_index];
+
+        *pixel = (*pixel & !self.selected_planes) | (source & self.selected_planes);
+
+        self.screen_changed = true;
+    }
+
+    fn registers_range(Vx: usize, Vy: usize) -> Box<dyn Iterator<Item = usize>> {
+        if Vx <= Vy {
+            Box::new(Vx..=Vy)
+        } else {
+            Box::new((Vy..=Vx).rev())
+        }
+    }
+
+    fn increment_I_after_load_store(&mut self, Vx: usize) {
+        match self.quirks.load_store {
+            IndexIncrement::None => {}
+            IndexIncrement::X => self.I += Vx,
+            IndexIncrement::XPlusOne => self.I += Vx + 1,
         }
     }
 }
@@ -0,0 +1,415 @@
+// Movies: input recording and playback.
+//
+// A movie is the keypad state of each frame, plus what's needed to reproduce the emulation from
+// power-on (random generator seed, ROM, quirks, clock speed, timing, hybrid mode). While a movie
+// is recorded or played back, the keys are sampled only at the frame boundaries (timers tick), so
+// that the recorded inputs exactly reproduce the session. During playback, the frontend is polled
+// only for the quit event, and the rewind is disabled.
+//
+// Desyncs (e.g. due to emulator changes, or the clock speed/timing changed during the session) are
+// detected via state hashes, taken every `hash_interval` frames.
+//
+// File format; all the integers are little endian:
+//
+//   magic:            4 bytes; "CH8M"
+//   version:          u16
+//   seed:             u64
+//   ROM hash:         20 bytes; SHA-1 of the ROM
+//   quirks:           u8 flags (bit 0: shift uses Vy, 1: jump uses Vx, 2: clip sprites, 3: logic
+//                     resets VF, 4: display wait, 5: key wait release), u8 load/store I
+//                     increment (0: none, 1: X, 2: X + 1)
+//   clock speed:      u32; instructions per second
+//   timing:           u8; 0: fixed, 1: COSMAC VIP
+//   hybrid mode:      u8; 0: disabled, 1: enabled (see `Chip8::set_hybrid_mode()`)
+//   hash interval:    u32; frames
+//   frames count:     u32
+//   frames:           u16 each; bitmask of the pressed keys (bit N = key N)
+//   hashes count:     u32
+//   hashes:           u32 each; checksum of the save state (see `save_state`) at the end of frame
+//                     (N + 1) * hash interval
+//
+// The frame N keys are sampled at the end of the frame N, and are in effect during the frame N + 1;
+// at power-on, all the keys are released.
+
+use crate::save_state::state_checksum;
+use crate::{Byte, Chip8, Chip8Error, IndexIncrement, Quirks, Timing};
+use interfaces_frontend::IoFrontend;
+
+use std::convert::TryInto;
+use std::fmt;
+
+const MAGIC: &[u8; 4] = b"CH8M";
+const VERSION: u16 = 1;
+
+pub const DEFAULT_HASH_INTERVAL: u32 = 60;
+
+#[derive(Debug, PartialEq)]
+pub enum MovieError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    InvalidData,
+    NotAtPowerOn,
+    RomMismatch,
+    SeedMismatch,
+    QuirksMismatch,
+    ClockSpeedMismatch,
+    TimingMismatch,
+    HybridModeMismatch,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Movie {
+    pub seed: u64,
+    pub rom_hash: [Byte; 20],
+    pub quirks: Quirks,
+    pub clock_speed: u32,
+    pub timing: Timing,
+    pub hybrid: bool,
+    pub hash_interval: u32,
+    pub frames: Vec<u16>,
+    pub hashes: Vec<u32>,
+}
+
+pub(crate) enum MovieMode {
+    Recording(Movie),
+    Playback(Movie),
+}
+
+impl Movie {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+
+        buffer.extend_from_slice(MAGIC);
+        buffer.extend_from_slice(&VERSION.to_le_bytes());
+        buffer.extend_from_slice(&self.seed.to_le_bytes());
+        buffer.extend_from_slice(&self.rom_hash);
+        buffer.extend_from_slice(&encode_quirks(&self.quirks));
+        buffer.extend_from_slice(&self.clock_speed.to_le_bytes());
+        buffer.push(match self.timing {
+            Timing::Fixed => 0,
+            Timing::CosmacVip => 1,
+        });
+        buffer.push(self.hybrid as u8);
+        buffer.extend_from_slice(&self.hash_interval.to_le_bytes());
+
+        buffer.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+
+        for keys in &self.frames {
+            buffer.extend_from_slice(&keys.to_le_bytes());
+        }
+
+        buffer.extend_from_slice(&(self.hashes.len() as u32).to_le_bytes());
+
+        for hash in &self.hashes {
+            buffer.extend_from_slice(&hash.to_le_bytes());
+        }
+
+        buffer
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, MovieError> {
+        let mut reader = Reader { data, position: 0 };
+
+        if reader.take(4)? != MAGIC {
+            return Err(MovieError::InvalidMagic);
+        }
+
+        let version = u16::from_le_bytes(reader.take(2)?.try_into().unwrap());
+
+        if version != VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        let seed = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+        let rom_hash = reader.take(20)?.try_into().unwrap();
+        let quirks = decode_quirks(reader.take(2)?)?;
+        let clock_speed = reader.u32()?;
+        let timing = match reader.take(1)?[0] {
+            0 => Timing::Fixed,
+            1 => Timing::CosmacVip,
+            _ => return Err(MovieError::InvalidData),
+        };
+        let hybrid = match reader.take(1)?[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(MovieError::InvalidData),
+        };
+        let hash_interval = reader.u32()?;
+
+        if clock_speed == 0 || hash_interval == 0 {
+            return Err(MovieError::InvalidData);
+        }
+
+        let frames_count = reader.u32()? as usize;
+        let frames = reader
+            .take(2 * frames_count)?
+            .chunks(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect();
+
+        let hashes_count = reader.u32()? as usize;
+        let hashes = reader
+            .take(4 * hashes_count)?
+            .chunks(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+
+        if reader.position != data.len() {
+            return Err(MovieError::InvalidData);
+        }
+
+        Ok(Movie {
+            seed,
+            rom_hash,
+            quirks,
+            clock_speed,
+            timing,
+            hybrid,
+            hash_interval,
+            frames,
+            hashes,
+        })
+    }
+}
+
+impl<T: IoFrontend> Chip8<T> {
+    /// Starts recording a movie; must be invoked at power-on (before any instruction is executed).
+    ///
+    pub fn start_movie_recording(&mut self, hash_interval: u32) -> Result<(), MovieError> {
+        if self.frame_count != 0 || self.PC != crate::PROGRAMS_LOCATION {
+            return Err(MovieError::NotAtPowerOn);
+        }
+
+        self.movie = Some(MovieMode::Recording(Movie {
+            seed: self.seed,
+            rom_hash: self.rom_hash,
+            quirks: self.quirks,
+            clock_speed: self.clock_speed,
+            timing: self.timing,
+            hybrid: self.hybrid_cpu.is_some(),
+            hash_interval: hash_interval.max(1),
+            frames: vec![],
+            hashes: vec![],
+        }));
+
+        Ok(())
+    }
+
+    /// Returns None if a movie was not being recorded.
+    ///
+    pub fn stop_movie_recording(&mut self) -> Option<Movie> {
+        match self.movie.take() {
+            Some(MovieMode::Recording(movie)) => Some(movie),
+            other => {
+                self.movie = other;
+                None
+            }
+        }
+    }
+
+    /// Starts the playback of a movie; must be invoked at power-on, and the machine must have been
+    /// created with the movie ROM, seed, quirks, clock speed, timing and hybrid mode.
+    ///
+    /// When the movie ends, the input is taken again from the frontend.
+    ///
+    pub fn start_movie_playback(&mut self, movie: Movie) -> Result<(), MovieError> {
+        if self.frame_count != 0 || self.PC != crate::PROGRAMS_LOCATION {
+            return Err(MovieError::NotAtPowerOn);
+        }
+
+        if movie.rom_hash != self.rom_hash {
+            return Err(MovieError::RomMismatch);
+        }
+
+        if movie.seed != self.seed {
+            return Err(MovieError::SeedMismatch);
+        }
+
+        if movie.quirks != self.quirks {
+            return Err(MovieError::QuirksMismatch);
+        }
+
+        if movie.clock_speed != self.clock_speed {
+            return Err(MovieError::ClockSpeedMismatch);
+        }
+
+        if movie.timing != self.timing {
+            return Err(MovieError::TimingMismatch);
+        }
+
+        if movie.hybrid != self.hybrid_cpu.is_some() {
+            return Err(MovieError::HybridModeMismatch);
+        }
+
+        self.movie = Some(MovieMode::Playback(movie));
+
+        Ok(())
+    }
+
+    pub fn is_movie_playing(&self) -> bool {
+        matches!(self.movie, Some(MovieMode::Playback(_)))
+    }
+
+    // Invoked at the end of each frame, when a movie is active.
+    //
+    pub(crate) fn handle_movie_frame(&mut self) -> Result<(), Chip8Error> {
+        let frame = self.frame_count;
+
+        let hash_interval = match &self.movie {
+            Some(MovieMode::Recording(movie)) | Some(MovieMode::Playback(movie)) => {
+                movie.hash_interval as u64
+            }
+            None => return Ok(()),
+        };
+
+        let state_hash = if frame.is_multiple_of(hash_interval) {
+            Some(state_checksum(&self.save_state()))
+        } else {
+            None
+        };
+
+        let frame_index = (frame - 1) as usize;
+
+        match self.movie.as_mut().unwrap() {
+            MovieMode::Recording(movie) => {
+                movie.hashes.extend(state_hash);
+
+                self.set_keys();
+
+                let keys = self
+                    .keys_status
+                    .iter()
+                    .enumerate()
+                    .fold(0, |keys, (i, pressed)| keys | ((*pressed as u16) << i));
+
+                // The borrow checker doesn't allow reusing `movie`, due to `set_keys()`.
+                //
+                if let Some(MovieMode::Recording(movie)) = &mut self.movie {
+                    movie.frames.push(keys);
+                }
+            }
+            MovieMode::Playback(movie) => {
+                if let Some(state_hash) = state_hash {
+                    let hash_index = (frame / hash_interval - 1) as usize;
+
+                    if movie
+                        .hashes
+                        .get(hash_index)
+                        .is_some_and(|hash| *hash != state_hash)
+                    {
+                        return Err(Chip8Error::MovieDesync { frame });
+                    }
+                }
+
+                match movie.frames.get(frame_index).copied() {
+                    Some(keys) => {
+                        for (i, pressed) in self.keys_status.iter_mut().enumerate() {
+                            *pressed = keys & (1 << i) != 0;
+                        }
+
+                        self.handle_quit_events();
+                    }
+                    None => {
+                        self.movie = None;
+                        self.set_keys();
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], MovieError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .ok_or(MovieError::Truncated)?;
+        let bytes = self
+            .data
+            .get(self.position..end)
+            .ok_or(MovieError::Truncated)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, MovieError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+fn encode_quirks(quirks: &Quirks) -> [u8; 2] {
+    let flags = quirks.shift_uses_Vy as u8
+        | (quirks.jump_uses_Vx as u8) << 1
+        | (quirks.clip_sprites as u8) << 2
+        | (quirks.logic_resets_VF as u8) << 3
+        | (quirks.display_wait as u8) << 4
+        | (quirks.key_wait_release as u8) << 5;
+
+    let load_store = match quirks.load_store {
+        IndexIncrement::None => 0,
+        IndexIncrement::X => 1,
+        IndexIncrement::XPlusOne => 2,
+    };
+
+    [flags, load_store]
+}
+
+fn decode_quirks(bytes: &[u8]) -> Result<Quirks, MovieError> {
+    let flags = bytes[0];
+
+    let load_store = match bytes[1] {
+        0 => IndexIncrement::None,
+        1 => IndexIncrement::X,
+        2 => IndexIncrement::XPlusOne,
+        _ => return Err(MovieError::InvalidData),
+    };
+
+    Ok(Quirks {
+        shift_uses_Vy: flags & 0b00_0001 != 0,
+        load_store,
+        jump_uses_Vx: flags & 0b00_0010 != 0,
+        clip_sprites: flags & 0b00_0100 != 0,
+        logic_resets_VF: flags & 0b00_1000 != 0,
+        display_wait: flags & 0b01_0000 != 0,
+        key_wait_release: flags & 0b10_0000 != 0,
+    })
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::InvalidMagic => write!(f, "Not a movie (invalid magic)"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported movie version: {}", version)
+            }
+            MovieError::Truncated => write!(f, "Truncated movie"),
+            MovieError::InvalidData => write!(f, "Invalid movie data"),
+            MovieError::NotAtPowerOn => write!(f, "Movies can be started only at power-on"),
+            MovieError::RomMismatch => write!(f, "The movie has been recorded with another ROM"),
+            MovieError::SeedMismatch => write!(f, "The movie has been recorded with another seed"),
+            MovieError::QuirksMismatch => {
+                write!(f, "The movie has been recorded with other quirks")
+            }
+            MovieError::ClockSpeedMismatch => {
+                write!(f, "The movie has been recorded with another clock speed")
+            }
+            MovieError::TimingMismatch => {
+                write!(f, "The movie has been recorded with another timing")
+            }
+            MovieError::HybridModeMismatch => {
+                write!(f, "The movie has been recorded with another hybrid mode")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
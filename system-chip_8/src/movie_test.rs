@@ -0,0 +1,108 @@
+use crate::movie::{Movie, MovieError};
+use crate::test_harness::{frame_hash, new_chip8, run_frames};
+use crate::{Chip8Error, Quirks, QuirksProfile, Timing};
+use interfaces_frontend::events::EventCode;
+
+use demonstrate::demonstrate;
+
+const FLIGHT_RUNNER: &[u8] = include_bytes!("../extra/flightrunner.ch8");
+const FRAMES: u64 = 120;
+
+// Flight Runner uses 5/8/7/9 for up/down/left/right.
+//
+fn record_flight_runner(hash_interval: u32) -> (Movie, String) {
+    let key_script = [
+        (30, EventCode::KeyNum7, true),
+        (60, EventCode::KeyNum7, false),
+        (60, EventCode::KeyNum8, true),
+        (90, EventCode::KeyNum8, false),
+    ];
+
+    let mut chip8 = new_chip8(FLIGHT_RUNNER, Quirks::profile(QuirksProfile::CosmacVip));
+    chip8.start_movie_recording(hash_interval).unwrap();
+
+    run_frames(&mut chip8, FRAMES, &key_script);
+
+    let last_frame_hash = frame_hash(chip8.io_frontend().last_frame());
+
+    (chip8.stop_movie_recording().unwrap(), last_frame_hash)
+}
+
+demonstrate! {
+    describe "movie" {
+        use super::*;
+
+        context "file format" {
+            before {
+                let movie = Movie {
+                    seed: 0x0123_4567_89AB_CDEF,
+                    rom_hash: [7; 20],
+                    quirks: Quirks::profile(QuirksProfile::Chip48),
+                    clock_speed: 1000,
+                    timing: Timing::CosmacVip,
+                    hybrid: true,
+                    hash_interval: 60,
+                    frames: vec![0, 0b1000_0000_0000_0001, 0xFFFF],
+                    hashes: vec![0xCAFE_BABE],
+                };
+            }
+
+            it "reads back the written movie" {
+                assert_eq!(Movie::from_bytes(&movie.to_bytes()), Ok(movie));
+            }
+
+            it "detects truncation" {
+                let mut data = movie.to_bytes();
+                data.pop();
+
+                assert_eq!(Movie::from_bytes(&data), Err(MovieError::Truncated));
+            }
+
+            it "rejects other formats" {
+                let mut data = movie.to_bytes();
+                data[0] = b'X';
+
+                assert_eq!(Movie::from_bytes(&data), Err(MovieError::InvalidMagic));
+            }
+        }
+
+        context "playback" {
+            it "reproduces the recorded session" {
+                let (movie, last_frame_hash) = record_flight_runner(10);
+
+                assert_eq!(movie.frames.len() as u64, FRAMES);
+                assert!(movie.frames.iter().any(|keys| *keys != 0));
+
+                let mut chip8 = new_chip8(FLIGHT_RUNNER, Quirks::profile(QuirksProfile::CosmacVip));
+                chip8.start_movie_playback(movie).unwrap();
+
+                run_frames(&mut chip8, FRAMES, &[]);
+
+                assert_eq!(frame_hash(chip8.io_frontend().last_frame()), last_frame_hash);
+            }
+
+            it "detects a desync" {
+                let (movie, _) = record_flight_runner(1);
+
+                let mut chip8 = new_chip8(FLIGHT_RUNNER, Quirks::profile(QuirksProfile::CosmacVip));
+                chip8.start_movie_playback(movie).unwrap();
+
+                run_frames(&mut chip8, 5, &[]);
+
+                // Diverge from the recorded session.
+                //
+                chip8.ram[0xFFF] ^= 0xFF;
+
+                assert_eq!(chip8.run_frame(), Err(Chip8Error::MovieDesync { frame: 6 }));
+            }
+
+            it "rejects the movies recorded with other settings" {
+                let (movie, _) = record_flight_runner(10);
+
+                let mut chip8 = new_chip8(FLIGHT_RUNNER, Quirks::profile(QuirksProfile::SuperChip));
+
+                assert_eq!(chip8.start_movie_playback(movie), Err(MovieError::QuirksMismatch));
+            }
+        }
+    }
+}
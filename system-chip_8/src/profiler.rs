@@ -0,0 +1,334 @@
+// ROM execution profiler.
+//
+// While profiling (see `Chip8::start_profiling()`), the machine counts:
+//
+// - the executions of each instruction address (which also gives the code coverage);
+// - the instructions executed in each subroutine, by pairing the 2NNN/00EE instructions, both
+//   inclusive (including the nested calls) and exclusive; the code outside any subroutine is
+//   attributed to the root ("main");
+// - the program memory reads/writes of each address (instruction fetches excluded).
+//
+// The time is measured in executed instructions, since the budget of a CHIP-8 program is the
+// number of instructions per frame; idle cycles (FX0A, and DXYN waiting for the vblank) are not
+// counted.
+//
+// Subroutines entered before the profiling started are attributed to the root. If the stack is
+// unwound by other means (e.g. state loading/rewinding), the profiler stack follows it.
+//
+// The profile can be exported as a text report, and as folded stacks (one `frame;frame;... count`
+// line per stack), the input format of the flamegraph tools (e.g. `flamegraph.pl`, `inferno`).
+
+use crate::{AccessKind, Chip8, Instruction};
+use interfaces_frontend::IoFrontend;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+const ROOT_NAME: &str = "main";
+
+// Entries in the hot instructions report section.
+//
+const REPORT_TOP_ENTRIES: usize = 20;
+
+// Size of the memory heatmap regions, in the report.
+//
+const HEATMAP_REGION_SIZE: usize = 0x100;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    frames: u64,
+    instructions: u64,
+    // Address -> (executions, last instruction executed at the address).
+    //
+    executions: BTreeMap<usize, (u64, Instruction)>,
+    subroutines: BTreeMap<usize, SubroutineProfile>,
+    memory_reads: BTreeMap<usize, u64>,
+    memory_writes: BTreeMap<usize, u64>,
+    // Subroutines stack (entry addresses) -> instructions executed with exactly that stack.
+    //
+    folded_stacks: HashMap<Vec<usize>, u64>,
+    call_stack: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SubroutineProfile {
+    pub calls: u64,
+    pub inclusive_instructions: u64,
+    pub exclusive_instructions: u64,
+}
+
+impl Profile {
+    /// Frames (timers ticks) elapsed while profiling.
+    ///
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn executions(&self, address: usize) -> u64 {
+        self.executions
+            .get(&address)
+            .map_or(0, |(executions, _)| *executions)
+    }
+
+    /// The addresses of the executed instructions, sorted.
+    ///
+    pub fn executed_addresses(&self) -> Vec<usize> {
+        self.executions.keys().copied().collect()
+    }
+
+    pub fn subroutine(&self, address: usize) -> Option<&SubroutineProfile> {
+        self.subroutines.get(&address)
+    }
+
+    pub fn memory_reads(&self, address: usize) -> u64 {
+        self.memory_reads.get(&address).copied().unwrap_or(0)
+    }
+
+    pub fn memory_writes(&self, address: usize) -> u64 {
+        self.memory_writes.get(&address).copied().unwrap_or(0)
+    }
+
+    /// Folded stacks, sorted, one per line; the subroutines are named after their address.
+    ///
+    pub fn folded_stacks(&self) -> String {
+        let mut lines = self
+            .folded_stacks
+            .iter()
+            .map(|(stack, instructions)| {
+                let frames = std::iter::once(ROOT_NAME.to_string())
+                    .chain(stack.iter().map(|address| subroutine_name(*address)))
+                    .collect::<Vec<_>>();
+
+                format!("{} {}\n", frames.join(";"), instructions)
+            })
+            .collect::<Vec<_>>();
+
+        lines.sort();
+        lines.concat()
+    }
+
+    pub fn text_report(&self) -> String {
+        let mut report = String::new();
+
+        writeln!(
+            report,
+            "Frames: {}; instructions: {} ({:.1} per frame); addresses executed: {}",
+            self.frames,
+            self.instructions,
+            self.per_frame(self.instructions),
+            self.executions.len()
+        )
+        .unwrap();
+
+        writeln!(report, "\nHot instructions:\n").unwrap();
+        writeln!(report, "  Address    Executions   Share  Instruction").unwrap();
+
+        let mut executions = self.executions.iter().collect::<Vec<_>>();
+        executions.sort_by(|(_, (count_1, _)), (_, (count_2, _))| count_2.cmp(count_1));
+
+        for (address, (count, instruction)) in executions.iter().take(REPORT_TOP_ENTRIES) {
+            writeln!(
+                report,
+                "  {:<9}  {:>10}  {:>5.1}%  {}",
+                format!("{:04X}", address),
+                count,
+                self.share(*count),
+                instruction
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nSubroutines (instructions):\n").unwrap();
+        writeln!(
+            report,
+            "  Name        Calls   Inclusive   Share   Exclusive  Per frame"
+        )
+        .unwrap();
+
+        let root_exclusive = self.folded_stacks.get(&vec![]).copied().unwrap_or_default();
+
+        let mut subroutines = self
+            .subroutines
+            .iter()
+            .map(|(address, profile)| (subroutine_name(*address), *profile))
+            .collect::<Vec<_>>();
+
+        subroutines.sort_by(|(_, profile_1), (_, profile_2)| {
+            profile_2
+                .inclusive_instructions
+                .cmp(&profile_1.inclusive_instructions)
+        });
+
+        let root = SubroutineProfile {
+            calls: 0,
+            inclusive_instructions: self.instructions,
+            exclusive_instructions: root_exclusive,
+        };
+
+        for (name, profile) in std::iter::once((ROOT_NAME.to_string(), root)).chain(subroutines) {
+            writeln!(
+                report,
+                "  {:<9}  {:>6}  {:>10}  {:>5.1}%  {:>10}  {:>9.1}",
+                name,
+                profile.calls,
+                profile.inclusive_instructions,
+                self.share(profile.inclusive_instructions),
+                profile.exclusive_instructions,
+                self.per_frame(profile.inclusive_instructions)
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            report,
+            "\nMemory heatmap (per 0x{:X} bytes):\n",
+            HEATMAP_REGION_SIZE
+        )
+        .unwrap();
+        writeln!(report, "  Region          Reads      Writes").unwrap();
+
+        let mut regions = BTreeMap::<usize, (u64, u64)>::new();
+
+        for (address, reads) in &self.memory_reads {
+            regions.entry(address / HEATMAP_REGION_SIZE).or_default().0 += reads;
+        }
+
+        for (address, writes) in &self.memory_writes {
+            regions.entry(address / HEATMAP_REGION_SIZE).or_default().1 += writes;
+        }
+
+        for (region, (reads, writes)) in regions {
+            let start = region * HEATMAP_REGION_SIZE;
+
+            writeln!(
+                report,
+                "  {:<9}  {:>10}  {:>10}",
+                format!("{:04X}-{:04X}", start, start + HEATMAP_REGION_SIZE - 1),
+                reads,
+                writes
+            )
+            .unwrap();
+        }
+
+        report
+    }
+
+    fn share(&self, instructions: u64) -> f64 {
+        if self.instructions == 0 {
+            0.0
+        } else {
+            100.0 * instructions as f64 / self.instructions as f64
+        }
+    }
+
+    fn per_frame(&self, instructions: u64) -> f64 {
+        instructions as f64 / self.frames.max(1) as f64
+    }
+
+    fn record_instruction(&mut self, address: usize, instruction: Instruction, SP: usize) {
+        self.instructions += 1;
+
+        let (executions, last_instruction) =
+            self.executions.entry(address).or_insert((0, instruction));
+        *executions += 1;
+        *last_instruction = instruction;
+
+        *self
+            .folded_stacks
+            .entry(self.call_stack.clone())
+            .or_default() += 1;
+
+        // With recursion, a subroutine must be counted once per instruction.
+        //
+        let mut counted_subroutines = self.call_stack.clone();
+        counted_subroutines.sort_unstable();
+        counted_subroutines.dedup();
+
+        for subroutine_address in counted_subroutines {
+            self.subroutines
+                .entry(subroutine_address)
+                .or_default()
+                .inclusive_instructions += 1;
+        }
+
+        if let Some(current_subroutine) = self.call_stack.last() {
+            self.subroutines
+                .entry(*current_subroutine)
+                .or_default()
+                .exclusive_instructions += 1;
+        }
+
+        match instruction {
+            Instruction::Call(subroutine_address) => {
+                self.subroutines
+                    .entry(subroutine_address)
+                    .or_default()
+                    .calls += 1;
+                self.call_stack.push(subroutine_address);
+            }
+            Instruction::Return => {
+                self.call_stack.pop();
+            }
+            _ => {}
+        }
+
+        self.call_stack.truncate(SP);
+    }
+
+    fn record_memory_access(&mut self, address: usize, kind: AccessKind) {
+        let accesses = match kind {
+            AccessKind::Read => &mut self.memory_reads,
+            AccessKind::Write => &mut self.memory_writes,
+        };
+
+        *accesses.entry(address).or_default() += 1;
+    }
+}
+
+fn subroutine_name(address: usize) -> String {
+    format!("sub_{:04X}", address)
+}
+
+impl<T: IoFrontend> Chip8<T> {
+    /// Starts profiling; if already profiling, the profile is reset.
+    ///
+    pub fn start_profiling(&mut self) {
+        self.profile = Some(Profile::default());
+    }
+
+    /// Returns None if not profiling.
+    ///
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    /// The profile collected so far, if profiling.
+    ///
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    pub(crate) fn profile_instruction(&mut self, address: usize, instruction: Instruction) {
+        let SP = self.SP;
+
+        if let Some(profile) = &mut self.profile {
+            profile.record_instruction(address, instruction, SP);
+        }
+    }
+
+    pub(crate) fn profile_memory_access(&mut self, address: usize, kind: AccessKind) {
+        if let Some(profile) = &mut self.profile {
+            profile.record_memory_access(address, kind);
+        }
+    }
+
+    pub(crate) fn profile_frame(&mut self) {
+        if let Some(profile) = &mut self.profile {
+            profile.frames += 1;
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use crate::test_harness::{new_chip8, run_steps, xo_chip};
+use crate::SubroutineProfile;
+
+use demonstrate::demonstrate;
+
+demonstrate! {
+    describe "profiler" {
+        use super::*;
+
+        it "counts the executions and the memory accesses" {
+            // (0x200) call 0x206; call 0x206; (0x204) jump 0x204
+            // (0x206) V0 := 1; I := 0x300; bcd V0; load V0; return
+            //
+            let rom = [
+                0x22, 0x06, 0x22, 0x06, 0x12, 0x04,
+                0x60, 0x01, 0xA3, 0x00, 0xF0, 0x33, 0xF0, 0x65, 0x00, 0xEE,
+            ];
+
+            let mut chip8 = new_chip8(&rom, xo_chip());
+            chip8.start_profiling();
+            run_steps(&mut chip8, 14);
+
+            let profile = chip8.stop_profiling().unwrap();
+
+            assert_eq!(profile.instructions(), 14);
+            assert_eq!((profile.executions(0x200), profile.executions(0x204), profile.executions(0x206)), (1, 2, 2));
+            assert_eq!(profile.executed_addresses(), vec![0x200, 0x202, 0x204, 0x206, 0x208, 0x20A, 0x20C, 0x20E]);
+
+            assert_eq!((profile.memory_writes(0x300), profile.memory_writes(0x302)), (2, 2));
+            assert_eq!((profile.memory_reads(0x300), profile.memory_reads(0x301)), (2, 0));
+
+            assert_eq!(
+                profile.subroutine(0x206),
+                Some(&SubroutineProfile { calls: 2, inclusive_instructions: 10, exclusive_instructions: 10 })
+            );
+            assert_eq!(profile.folded_stacks(), "main 4\nmain;sub_0206 10\n");
+
+            assert!(chip8.stop_profiling().is_none());
+        }
+
+        it "attributes the nested calls" {
+            // (0x200) call 0x204; (0x202) jump 0x202; (0x204) call 0x208; return; (0x208) return
+            //
+            let rom = [0x22, 0x04, 0x12, 0x02, 0x22, 0x08, 0x00, 0xEE, 0x00, 0xEE];
+
+            let mut chip8 = new_chip8(&rom, xo_chip());
+            chip8.start_profiling();
+            run_steps(&mut chip8, 5);
+
+            let profile = chip8.profile().unwrap();
+
+            assert_eq!(
+                profile.subroutine(0x204),
+                Some(&SubroutineProfile { calls: 1, inclusive_instructions: 3, exclusive_instructions: 2 })
+            );
+            assert_eq!(
+                profile.folded_stacks(),
+                "main 2\nmain;sub_0204 2\nmain;sub_0204;sub_0208 1\n"
+            );
+        }
+
+        it "reports the instructions per frame" {
+            // (0x200) call 0x204; (0x202) jump 0x200; (0x204) return
+            //
+            let rom = [0x22, 0x04, 0x12, 0x00, 0x00, 0xEE];
+
+            let mut chip8 = new_chip8(&rom, xo_chip());
+            chip8.start_profiling();
+
+            for _ in 0..3 {
+                chip8.run_frame().unwrap();
+            }
+
+            let profile = chip8.stop_profiling().unwrap();
+            let report = profile.text_report();
+
+            assert_eq!(profile.frames(), 3);
+            assert!(report.starts_with(&format!("Frames: 3; instructions: {} (", profile.instructions())));
+            assert!(report.contains(&format!("  0204       {:>10}  ", profile.executions(0x204))));
+            assert!(report.contains(&format!("  sub_0204   {:>6}  ", profile.subroutine(0x204).unwrap().calls)));
+        }
+    }
+}
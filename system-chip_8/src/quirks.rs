@@ -0,0 +1,152 @@
+// Behaviors that differ between the CHIP-8 interpreters. Each ROM is written against a specific
+// interpreter, so in order to run correctly, the matching set of quirks needs to be selected.
+//
+// References:
+//
+// - https://github.com/Timendus/chip8-test-suite#quirks-test
+// - https://chip8.gulrak.net
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Behavior of I on FX55/FX65 (register dump/load).
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IndexIncrement {
+    /// I is not modified (CHIP-48 1.1/SUPER-CHIP).
+    ///
+    None,
+    /// I is incremented by X (CHIP-48 1.0).
+    ///
+    X,
+    /// I is incremented by X + 1 (COSMAC VIP, XO-CHIP).
+    ///
+    XPlusOne,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    /// 8XY6/8XYE: shift Vy, and store the result in Vx; otherwise, Vx is shifted in place.
+    ///
+    pub shift_uses_Vy: bool,
+    /// FX55/FX65: I increment.
+    ///
+    pub load_store: IndexIncrement,
+    /// BNNN: jump to NNN + VX (X being the address high nibble), rather than NNN + V0.
+    ///
+    pub jump_uses_Vx: bool,
+    /// DXYN: clip the sprites at the screen edges; otherwise, they wrap around. The starting
+    /// coordinates always wrap.
+    ///
+    pub clip_sprites: bool,
+    /// 8XY1/8XY2/8XY3: reset VF.
+    ///
+    pub logic_resets_VF: bool,
+    /// DXYN: wait for the vertical blank (ie. the timers tick) before drawing, so that at most
+    /// one sprite per frame is drawn.
+    ///
+    pub display_wait: bool,
+    /// FX0A: complete the wait when the key is released, rather than when it's pressed.
+    ///
+    pub key_wait_release: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QuirksProfile {
+    CosmacVip,
+    Chip48,
+    SuperChip,
+    XoChip,
+}
+
+impl Quirks {
+    pub fn profile(profile: QuirksProfile) -> Quirks {
+        match profile {
+            QuirksProfile::CosmacVip => Quirks {
+                shift_uses_Vy: true,
+                load_store: IndexIncrement::XPlusOne,
+                jump_uses_Vx: false,
+                clip_sprites: true,
+                logic_resets_VF: true,
+                display_wait: true,
+                key_wait_release: true,
+            },
+            QuirksProfile::Chip48 => Quirks {
+                shift_uses_Vy: false,
+                load_store: IndexIncrement::X,
+                jump_uses_Vx: true,
+                clip_sprites: true,
+                logic_resets_VF: false,
+                display_wait: false,
+                key_wait_release: false,
+            },
+            QuirksProfile::SuperChip => Quirks {
+                shift_uses_Vy: false,
+                load_store: IndexIncrement::None,
+                jump_uses_Vx: true,
+                clip_sprites: true,
+                logic_resets_VF: false,
+                display_wait: false,
+                key_wait_release: false,
+            },
+            QuirksProfile::XoChip => Quirks {
+                shift_uses_Vy: true,
+                load_store: IndexIncrement::XPlusOne,
+                jump_uses_Vx: false,
+                clip_sprites: false,
+                logic_resets_VF: false,
+                display_wait: false,
+                key_wait_release: true,
+            },
+        }
+    }
+}
+
+/// The behavior of the emulator before the quirks were introduced, which doesn't match any
+/// profile: Vx shifted in place, I not modified by FX55/FX65, BNNN using V0, wrapping sprites, no
+/// VF reset, no display wait, and FX0A completing on the key press.
+///
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks {
+            shift_uses_Vy: false,
+            load_store: IndexIncrement::None,
+            jump_uses_Vx: false,
+            clip_sprites: false,
+            logic_resets_VF: false,
+            display_wait: false,
+            key_wait_release: false,
+        }
+    }
+}
+
+impl QuirksProfile {
+    pub const NAMES: [&'static str; 4] = ["cosmac-vip", "chip-48", "superchip", "xo-chip"];
+}
+
+impl FromStr for QuirksProfile {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "cosmac-vip" => Ok(QuirksProfile::CosmacVip),
+            "chip-48" => Ok(QuirksProfile::Chip48),
+            "superchip" => Ok(QuirksProfile::SuperChip),
+            "xo-chip" => Ok(QuirksProfile::XoChip),
+            _ => Err(format!("Unknown quirks profile: {}", name)),
+        }
+    }
+}
+
+impl fmt::Display for QuirksProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            QuirksProfile::CosmacVip => "cosmac-vip",
+            QuirksProfile::Chip48 => "chip-48",
+            QuirksProfile::SuperChip => "superchip",
+            QuirksProfile::XoChip => "xo-chip",
+        };
+
+        write!(f, "{}", name)
+    }
+}
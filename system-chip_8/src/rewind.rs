@@ -0,0 +1,285 @@
+// Rewind subsystem.
+//
+// Snapshots (save states) are taken every N frames, and stored in a bounded ring buffer. Only the
+// most recent snapshot is stored in full; each of the older ones is stored as the XOR delta against
+// its successor, run-length encoded. Since consecutive states differ in a small number of bytes
+// (registers, a few RAM locations, some pixels), the deltas are very small.
+//
+// Since XOR is symmetric, stepping back is: `previous = latest ^ delta`; dropping the oldest entry
+// when the memory budget is exceeded doesn't require any recomputation.
+
+use crate::{Byte, Chip8, Chip8Error};
+use interfaces_frontend::{events::EventCode, IoFrontend};
+
+use std::collections::VecDeque;
+use std::mem;
+
+// Memory used by each delta, in addition to its data (the `Vec` stored in the ring buffer).
+//
+const DELTA_OVERHEAD: usize = mem::size_of::<Vec<Byte>>();
+
+pub struct RewindConfig {
+    /// Snapshot frequency, in frames.
+    ///
+    pub interval_frames: u32,
+    /// Upper bound of the memory used by the snapshots, including the per-snapshot bookkeeping.
+    ///
+    pub max_memory: usize,
+    /// Key that, while held, rewinds the gameplay.
+    ///
+    pub hotkey: EventCode,
+}
+
+impl RewindConfig {
+    /// Snapshot every 2 frames, using up to 8 MiB; this is enough for several minutes of gameplay
+    /// in typical cases.
+    ///
+    pub fn new(hotkey: EventCode) -> RewindConfig {
+        RewindConfig {
+            interval_frames: 2,
+            max_memory: 8 * 1024 * 1024,
+            hotkey,
+        }
+    }
+}
+
+pub(crate) struct Rewind {
+    config: RewindConfig,
+    buffer: RewindBuffer,
+    frames_since_snapshot: u32,
+    active: bool,
+}
+
+impl Rewind {
+    pub(crate) fn new(config: RewindConfig) -> Rewind {
+        let buffer = RewindBuffer::new(config.max_memory);
+
+        Rewind {
+            config,
+            buffer,
+            frames_since_snapshot: 0,
+            active: false,
+        }
+    }
+}
+
+impl<T: IoFrontend> Chip8<T> {
+    /// Enables the rewind subsystem; holding the configured hotkey steps backwards in time, one
+    /// snapshot per frame. Rewinding is performed by `run_frame()` only; `step()` and `run_for()`
+    /// keep executing the program.
+    ///
+    pub fn enable_rewind(&mut self, config: RewindConfig) {
+        self.rewind = Some(Rewind::new(config));
+    }
+
+    // Returns true if the event has been consumed.
+    //
+    pub(crate) fn handle_rewind_event(&mut self, key_code: &EventCode, key_pressed: bool) -> bool {
+        match &mut self.rewind {
+            Some(rewind) if *key_code == rewind.config.hotkey => {
+                rewind.active = key_pressed;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn is_rewinding(&self) -> bool {
+        matches!(&self.rewind, Some(rewind) if rewind.active)
+    }
+
+    // Invoked at the end of each (non-rewound) frame, i.e. on each timers tick.
+    //
+    pub(crate) fn record_rewind_snapshot(&mut self) {
+        let snapshot_due = match &mut self.rewind {
+            Some(rewind) => {
+                rewind.frames_since_snapshot += 1;
+                rewind.frames_since_snapshot >= rewind.config.interval_frames
+            }
+            None => false,
+        };
+
+        if snapshot_due {
+            let state = self.save_state();
+            let rewind = self.rewind.as_mut().unwrap();
+
+            rewind.buffer.push(state);
+            rewind.frames_since_snapshot = 0;
+        }
+    }
+
+    // Restores the previous snapshot, if any. The keys status is not restored, since it must
+    // reflect the physical keys, which are held independently of the rewinding.
+    //
+    // The snapshots are generated internally, so a failure to load one is an emulator bug; the
+    // machine is left unchanged (see `load_state()`).
+    //
+    pub(crate) fn rewind_frame(&mut self) -> Result<(), Chip8Error> {
+        let state = self.rewind.as_mut().and_then(|rewind| rewind.buffer.pop());
+
+        if let Some(rewind) = &mut self.rewind {
+            rewind.frames_since_snapshot = 0;
+        }
+
+        if let Some(state) = state {
+            let keys_status = self.keys_status;
+
+            self.load_state(&state)
+                .map_err(|error| Chip8Error::InvalidRewindSnapshot { error })?;
+
+            self.keys_status = keys_status;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) struct RewindBuffer {
+    latest: Option<Vec<Byte>>,
+    // Each delta reconstructs the predecessor of the state it's applied to. The back is the newest.
+    //
+    deltas: VecDeque<Vec<Byte>>,
+    deltas_memory: usize,
+    max_memory: usize,
+}
+
+impl RewindBuffer {
+    pub(crate) fn new(max_memory: usize) -> RewindBuffer {
+        RewindBuffer {
+            latest: None,
+            deltas: VecDeque::new(),
+            deltas_memory: 0,
+            max_memory,
+        }
+    }
+
+    pub(crate) fn push(&mut self, state: Vec<Byte>) {
+        if let Some(latest) = self.latest.take() {
+            let mut delta = encode_delta(&latest, &state);
+            delta.shrink_to_fit();
+
+            self.deltas_memory += delta_memory(&delta);
+            self.deltas.push_back(delta);
+        }
+
+        self.latest = Some(state);
+
+        let mut used_memory = self.deltas_memory + self.latest.as_ref().unwrap().len();
+
+        while used_memory > self.max_memory {
+            match self.deltas.pop_front() {
+                Some(delta) => {
+                    self.deltas_memory -= delta_memory(&delta);
+                    used_memory -= delta_memory(&delta);
+                }
+                None => break,
+            }
+        }
+    }
+
+    // Returns the most recent state, and makes its predecessor the most recent one.
+    //
+    pub(crate) fn pop(&mut self) -> Option<Vec<Byte>> {
+        let latest = self.latest.take()?;
+
+        if let Some(delta) = self.deltas.pop_back() {
+            self.deltas_memory -= delta_memory(&delta);
+            self.latest = Some(decode_delta(&latest, &delta));
+        }
+
+        Some(latest)
+    }
+}
+
+fn delta_memory(delta: &[Byte]) -> usize {
+    DELTA_OVERHEAD + delta.len()
+}
+
+// Delta format:
+//
+//   target length: varint
+//   runs:          sequence of (zeros count: varint, literals count: varint, literals)
+//
+// where the XOR is performed on the zero-padded sources.
+//
+fn encode_delta(target: &[Byte], source: &[Byte]) -> Vec<Byte> {
+    let length = target.len().max(source.len());
+    let xor_at = |i: usize| target.get(i).unwrap_or(&0) ^ source.get(i).unwrap_or(&0);
+
+    let mut delta = Vec::new();
+    write_varint(&mut delta, target.len());
+
+    let mut i = 0;
+
+    while i < length {
+        let zeros_start = i;
+
+        while i < length && xor_at(i) == 0 {
+            i += 1;
+        }
+
+        let literals_start = i;
+
+        while i < length && xor_at(i) != 0 {
+            i += 1;
+        }
+
+        write_varint(&mut delta, literals_start - zeros_start);
+        write_varint(&mut delta, i - literals_start);
+        delta.extend((literals_start..i).map(xor_at));
+    }
+
+    delta
+}
+
+fn decode_delta(source: &[Byte], delta: &[Byte]) -> Vec<Byte> {
+    let mut delta = delta;
+
+    let target_length = read_varint(&mut delta);
+    let mut target = source.to_vec();
+    target.resize(target_length.max(source.len()), 0);
+
+    let mut i = 0;
+
+    while !delta.is_empty() {
+        i += read_varint(&mut delta);
+        let literals_count = read_varint(&mut delta);
+
+        for literal in &delta[..literals_count] {
+            target[i] ^= literal;
+            i += 1;
+        }
+
+        delta = &delta[literals_count..];
+    }
+
+    target.truncate(target_length);
+    target
+}
+
+fn write_varint(buffer: &mut Vec<Byte>, mut value: usize) {
+    while value >= 0x80 {
+        buffer.push((value as Byte) | 0x80);
+        value >>= 7;
+    }
+
+    buffer.push(value as Byte);
+}
+
+fn read_varint(buffer: &mut &[Byte]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = buffer[0];
+        *buffer = &buffer[1..];
+
+        value |= ((byte & 0x7F) as usize) << shift;
+
+        if byte & 0x80 == 0 {
+            return value;
+        }
+
+        shift += 7;
+    }
+}
@@ -0,0 +1,116 @@
+use crate::rewind::RewindBuffer;
+use crate::test_harness::{new_program_chip8, run_steps, xo_chip};
+use crate::RewindConfig;
+use interfaces_frontend::events::EventCode;
+
+use demonstrate::demonstrate;
+
+demonstrate! {
+    describe "rewind buffer" {
+        use super::*;
+
+        before {
+            let mut buffer = RewindBuffer::new(1024);
+        }
+
+        it "returns the states in reverse order" {
+            buffer.push(vec![1, 2, 3, 4]);
+            buffer.push(vec![1, 2, 5, 4]);
+            buffer.push(vec![9, 2, 5, 4, 7]);
+            buffer.push(vec![9, 2]);
+
+            assert_eq!(buffer.pop(), Some(vec![9, 2]));
+            assert_eq!(buffer.pop(), Some(vec![9, 2, 5, 4, 7]));
+            assert_eq!(buffer.pop(), Some(vec![1, 2, 5, 4]));
+            assert_eq!(buffer.pop(), Some(vec![1, 2, 3, 4]));
+            assert_eq!(buffer.pop(), None);
+        }
+
+        it "drops the oldest states when the memory budget is exceeded" {
+            for i in 0..200 {
+                let mut state = vec![0; 512];
+                state[i] = 0xFF;
+                buffer.push(state);
+            }
+
+            let mut expected_i = 199;
+
+            while let Some(state) = buffer.pop() {
+                assert_eq!(state[expected_i], 0xFF);
+                expected_i -= 1;
+            }
+
+            assert!(expected_i > 0);
+        }
+
+        it "accounts for the deltas bookkeeping in the memory budget" {
+            // Identical states produce deltas of a few bytes, so the budget is dominated by the
+            // bookkeeping.
+            //
+            for _ in 0..100 {
+                buffer.push(vec![0; 512]);
+            }
+
+            let mut states_count = 0;
+
+            while buffer.pop().is_some() {
+                states_count += 1;
+            }
+
+            let max_deltas_count = (1024 - 512) / std::mem::size_of::<Vec<u8>>();
+
+            assert!(states_count <= 1 + max_deltas_count);
+        }
+    }
+
+    describe "rewind" {
+        use super::*;
+
+        it "takes the snapshots also when the machine is stepped" {
+            // (0x200) V0 += 1; jump 0x200
+            //
+            let mut chip8 = new_program_chip8(&[0x7001, 0x1200], xo_chip());
+            chip8.enable_rewind(RewindConfig { interval_frames: 1, ..RewindConfig::new(EventCode::KeyBackspace) });
+
+            while chip8.frame_count() < 2 {
+                run_steps(&mut chip8, 1);
+            }
+
+            let snapshot_V0 = chip8.V[0];
+
+            chip8.io_frontend_mut().push_event(EventCode::KeyBackspace, true);
+            run_steps(&mut chip8, 3);
+
+            assert_ne!(chip8.V[0], snapshot_V0);
+
+            chip8.run_frame().unwrap();
+
+            assert_eq!(chip8.V[0], snapshot_V0);
+        }
+
+        it "rewinds only when running frames" {
+            // (0x200) V0 += 1; jump 0x200
+            //
+            let mut chip8 = new_program_chip8(&[0x7001, 0x1200], xo_chip());
+            chip8.enable_rewind(RewindConfig { interval_frames: 1, ..RewindConfig::new(EventCode::KeyBackspace) });
+
+            chip8.run_frame().unwrap();
+            chip8.run_frame().unwrap();
+
+            chip8.io_frontend_mut().push_event(EventCode::KeyBackspace, true);
+
+            let V0 = chip8.V[0];
+
+            chip8.run_for(2).unwrap();
+
+            assert_eq!(chip8.V[0], V0 + 1);
+            assert_eq!(chip8.io_frontend().pending_events(), 0);
+
+            // Back to the snapshot of the end of the last frame.
+            //
+            chip8.run_frame().unwrap();
+
+            assert_eq!(chip8.V[0], V0);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// Random number generation.
+//
+// The generator is owned by the machine and seeded at construction, so that, given the same seed
+// and inputs, the emulation is fully reproducible (replays, golden tests, lockstep).
+//
+// Generators are pluggable (see `Chip8::set_random_generator()`); their state is captured by the
+// save states, so it must be serializable.
+
+use crate::Byte;
+
+use std::convert::TryInto;
+
+pub trait RandomGenerator: Send {
+    fn next_byte(&mut self) -> Byte;
+
+    /// Serialized state, stored in the save states.
+    ///
+    fn state(&self) -> Vec<Byte>;
+
+    /// Restores a state produced by `state()`. Returns false if the state is invalid, in which
+    /// case the generator must be unchanged.
+    ///
+    fn set_state(&mut self, state: &[Byte]) -> bool;
+}
+
+/// Default generator: xorshift64* (see https://en.wikipedia.org/wiki/Xorshift#xorshift*).
+///
+/// Not cryptographically secure, of course, but fast, small, and with a trivially serializable
+/// state.
+///
+pub struct XorShiftGenerator {
+    state: u64,
+}
+
+impl XorShiftGenerator {
+    pub fn new(seed: u64) -> XorShiftGenerator {
+        XorShiftGenerator {
+            state: Self::scramble_seed(seed),
+        }
+    }
+
+    // The state must not be zero; additionally, similar seeds (e.g. 1 and 2) should not produce
+    // similar sequences, so the seed is scrambled (SplitMix64 finalizer).
+    //
+    fn scramble_seed(seed: u64) -> u64 {
+        let mut value = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^= value >> 31;
+
+        if value == 0 {
+            1
+        } else {
+            value
+        }
+    }
+}
+
+impl RandomGenerator for XorShiftGenerator {
+    fn next_byte(&mut self) -> Byte {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        // The high bits are the best quality ones.
+        //
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as Byte
+    }
+
+    fn state(&self) -> Vec<Byte> {
+        self.state.to_le_bytes().to_vec()
+    }
+
+    fn set_state(&mut self, state: &[Byte]) -> bool {
+        match state.try_into().map(u64::from_le_bytes) {
+            Ok(state) if state != 0 => {
+                self.state = state;
+                true
+            }
+            _ => false,
+        }
+    }
+}
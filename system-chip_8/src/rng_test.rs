@@ -0,0 +1,46 @@
+use crate::rng::{RandomGenerator, XorShiftGenerator};
+use demonstrate::demonstrate;
+
+demonstrate! {
+    describe "xorshift generator" {
+        use super::*;
+
+        before {
+            let mut generator = XorShiftGenerator::new(42);
+        }
+
+        it "is deterministic" {
+            let mut other_generator = XorShiftGenerator::new(42);
+
+            let sequence = (0..16).map(|_| generator.next_byte()).collect::<Vec<_>>();
+            let other_sequence = (0..16).map(|_| other_generator.next_byte()).collect::<Vec<_>>();
+
+            assert_eq!(sequence, other_sequence);
+        }
+
+        it "produces different sequences for different seeds" {
+            let mut other_generator = XorShiftGenerator::new(43);
+
+            let sequence = (0..16).map(|_| generator.next_byte()).collect::<Vec<_>>();
+            let other_sequence = (0..16).map(|_| other_generator.next_byte()).collect::<Vec<_>>();
+
+            assert_ne!(sequence, other_sequence);
+        }
+
+        it "restores the state" {
+            generator.next_byte();
+            let state = generator.state();
+            let expected_byte = generator.next_byte();
+
+            let mut other_generator = XorShiftGenerator::new(0);
+            assert!(other_generator.set_state(&state));
+
+            assert_eq!(other_generator.next_byte(), expected_byte);
+        }
+
+        it "rejects invalid states" {
+            assert!(!generator.set_state(&[0; 8]));
+            assert!(!generator.set_state(&[1; 7]));
+        }
+    }
+}
@@ -0,0 +1,318 @@
+// ROM database: per-program settings (quirks, clock speed, keys, palette), keyed by the ROM SHA-1.
+//
+// A database is bundled (see `extra/rom_database.txt`, which also documents the format); frontends
+// look the ROM up, and apply the settings not overridden by the user.
+
+use crate::{Byte, IndexIncrement, Quirks, QuirksProfile};
+use interfaces_frontend::video::Pixel;
+use sha1_smol::Sha1;
+
+use std::collections::HashMap;
+use std::fmt;
+
+const BUNDLED_DATABASE: &str = include_str!("../extra/rom_database.txt");
+
+pub struct RomInfo {
+    pub title: String,
+    pub platform: Option<QuirksProfile>,
+    /// The platform quirks, with the overrides applied; None if neither is specified.
+    ///
+    pub quirks: Option<Quirks>,
+    pub clock_speed: Option<u32>,
+    /// Host key name (frontend-specific, e.g. "Up") to keypad key.
+    ///
+    pub keys: Vec<(String, Byte)>,
+    pub palette: Option<[Pixel; 4]>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RomDatabaseError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Default)]
+pub struct RomDatabase {
+    entries: HashMap<[Byte; 20], RomInfo>,
+}
+
+impl RomDatabase {
+    pub fn bundled() -> RomDatabase {
+        RomDatabase::parse(BUNDLED_DATABASE).unwrap()
+    }
+
+    pub fn parse(source: &str) -> Result<RomDatabase, RomDatabaseError> {
+        let mut database = RomDatabase::default();
+        let mut current_entry: Option<([Byte; 20], EntryBuilder)> = None;
+
+        for (line_index, line) in source.lines().enumerate() {
+            let line_number = line_index + 1;
+            let line = line.trim();
+            let error = |message: String| RomDatabaseError {
+                line: line_number,
+                message,
+            };
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                if let Some((hash, entry)) = current_entry.take() {
+                    database.insert(hash, entry)?;
+                }
+
+                let hash = parse_hash(&line[1..line.len() - 1]).map_err(error)?;
+
+                if database.entries.contains_key(&hash) {
+                    return Err(error("Duplicate entry".to_string()));
+                }
+
+                current_entry = Some((hash, EntryBuilder::new(line_number)));
+            } else {
+                let (_, entry) = current_entry
+                    .as_mut()
+                    .ok_or_else(|| error("Field outside of an entry".to_string()))?;
+
+                let mut tokens = line.splitn(2, '=');
+                let key = tokens.next().unwrap().trim();
+                let value = tokens
+                    .next()
+                    .ok_or_else(|| error(format!("Invalid line: {}", line)))?
+                    .trim();
+
+                entry.set(key, value).map_err(error)?;
+            }
+        }
+
+        if let Some((hash, entry)) = current_entry {
+            database.insert(hash, entry)?;
+        }
+
+        Ok(database)
+    }
+
+    pub fn get(&self, rom_hash: &[Byte; 20]) -> Option<&RomInfo> {
+        self.entries.get(rom_hash)
+    }
+
+    pub fn lookup(&self, rom: &[Byte]) -> Option<&RomInfo> {
+        self.get(&Sha1::from(rom).digest().bytes())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn insert(&mut self, hash: [Byte; 20], entry: EntryBuilder) -> Result<(), RomDatabaseError> {
+        let rom_info = entry.build()?;
+        self.entries.insert(hash, rom_info);
+        Ok(())
+    }
+}
+
+// Fields are collected in any order; the quirks are resolved at the end, since the overrides apply
+// to the platform profile.
+//
+struct EntryBuilder {
+    line: usize,
+    title: Option<String>,
+    platform: Option<QuirksProfile>,
+    quirks_overrides: Vec<QuirkOverride>,
+    clock_speed: Option<u32>,
+    keys: Vec<(String, Byte)>,
+    palette: Option<[Pixel; 4]>,
+}
+
+impl EntryBuilder {
+    fn new(line: usize) -> EntryBuilder {
+        EntryBuilder {
+            line,
+            title: None,
+            platform: None,
+            quirks_overrides: vec![],
+            clock_speed: None,
+            keys: vec![],
+            palette: None,
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "title" => self.title = Some(value.to_string()),
+            "platform" => self.platform = Some(value.parse()?),
+            "quirks" => {
+                // Parsed here, so that the errors are reported on the right line.
+                //
+                for (name, value) in parse_assignments(value)? {
+                    self.quirks_overrides.push(parse_quirk(&name, &value)?);
+                }
+            }
+            "clock" => match value.parse() {
+                Ok(clock_speed) if clock_speed > 0 => self.clock_speed = Some(clock_speed),
+                _ => return Err(format!("Invalid clock speed: {}", value)),
+            },
+            "keys" => {
+                for (host_key, keypad_key) in parse_assignments(value)? {
+                    match Byte::from_str_radix(&keypad_key, 16) {
+                        Ok(keypad_key) if keypad_key < 16 => self.keys.push((host_key, keypad_key)),
+                        _ => return Err(format!("Invalid keypad key: {}", keypad_key)),
+                    }
+                }
+            }
+            "palette" => self.palette = Some(parse_palette(value)?),
+            _ => return Err(format!("Unknown field: {}", key)),
+        }
+
+        Ok(())
+    }
+
+    fn build(self) -> Result<RomInfo, RomDatabaseError> {
+        let line = self.line;
+        let title = self.title.ok_or_else(|| RomDatabaseError {
+            line,
+            message: "Missing title".to_string(),
+        })?;
+
+        let quirks = if self.platform.is_some() || !self.quirks_overrides.is_empty() {
+            let mut quirks = Quirks::profile(self.platform.unwrap_or(QuirksProfile::CosmacVip));
+
+            for quirk_override in &self.quirks_overrides {
+                quirk_override.apply(&mut quirks);
+            }
+
+            Some(quirks)
+        } else {
+            None
+        };
+
+        Ok(RomInfo {
+            title,
+            platform: self.platform,
+            quirks,
+            clock_speed: self.clock_speed,
+            keys: self.keys,
+            palette: self.palette,
+        })
+    }
+}
+
+fn parse_hash(value: &str) -> Result<[Byte; 20], String> {
+    let invalid_hash_error = || format!("Invalid SHA-1: {}", value);
+
+    if value.len() != 40 || !value.is_ascii() {
+        return Err(invalid_hash_error());
+    }
+
+    let mut hash = [0; 20];
+
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte =
+            Byte::from_str_radix(&value[2 * i..2 * i + 2], 16).map_err(|_| invalid_hash_error())?;
+    }
+
+    Ok(hash)
+}
+
+// Parses whitespace-separated `name=value` pairs.
+//
+fn parse_assignments(value: &str) -> Result<Vec<(String, String)>, String> {
+    value
+        .split_whitespace()
+        .map(|assignment| {
+            let mut tokens = assignment.splitn(2, '=');
+
+            match (tokens.next(), tokens.next()) {
+                (Some(name), Some(value)) if !name.is_empty() && !value.is_empty() => {
+                    Ok((name.to_string(), value.to_string()))
+                }
+                _ => Err(format!("Invalid assignment: {}", assignment)),
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy)]
+enum QuirkOverride {
+    ShiftUsesVy(bool),
+    LoadStore(IndexIncrement),
+    JumpUsesVx(bool),
+    ClipSprites(bool),
+    LogicResetsVf(bool),
+    DisplayWait(bool),
+    KeyWaitRelease(bool),
+}
+
+impl QuirkOverride {
+    fn apply(self, quirks: &mut Quirks) {
+        match self {
+            QuirkOverride::ShiftUsesVy(value) => quirks.shift_uses_Vy = value,
+            QuirkOverride::LoadStore(value) => quirks.load_store = value,
+            QuirkOverride::JumpUsesVx(value) => quirks.jump_uses_Vx = value,
+            QuirkOverride::ClipSprites(value) => quirks.clip_sprites = value,
+            QuirkOverride::LogicResetsVf(value) => quirks.logic_resets_VF = value,
+            QuirkOverride::DisplayWait(value) => quirks.display_wait = value,
+            QuirkOverride::KeyWaitRelease(value) => quirks.key_wait_release = value,
+        }
+    }
+}
+
+fn parse_quirk(name: &str, value: &str) -> Result<QuirkOverride, String> {
+    if name == "load_store" {
+        let load_store = match value {
+            "none" => IndexIncrement::None,
+            "x" => IndexIncrement::X,
+            "x+1" => IndexIncrement::XPlusOne,
+            _ => return Err(format!("Invalid load_store value: {}", value)),
+        };
+
+        return Ok(QuirkOverride::LoadStore(load_store));
+    }
+
+    let quirk_override: fn(bool) -> QuirkOverride = match name {
+        "shift_uses_vy" => QuirkOverride::ShiftUsesVy,
+        "jump_uses_vx" => QuirkOverride::JumpUsesVx,
+        "clip_sprites" => QuirkOverride::ClipSprites,
+        "logic_resets_vf" => QuirkOverride::LogicResetsVf,
+        "display_wait" => QuirkOverride::DisplayWait,
+        "key_wait_release" => QuirkOverride::KeyWaitRelease,
+        _ => return Err(format!("Unknown quirk: {}", name)),
+    };
+
+    let value = value
+        .parse()
+        .map_err(|_| format!("Invalid {} value: {}", name, value))?;
+
+    Ok(quirk_override(value))
+}
+
+fn parse_palette(value: &str) -> Result<[Pixel; 4], String> {
+    let colors = value
+        .split_whitespace()
+        .map(|color| match u32::from_str_radix(color, 16) {
+            Ok(rgb) if color.len() == 6 => {
+                Ok(Pixel((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+            }
+            _ => Err(format!("Invalid color: {}", color)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match colors[..] {
+        [background, plane_1, plane_2, both_planes] => {
+            Ok([background, plane_1, plane_2, both_planes])
+        }
+        _ => Err("The palette must have 4 colors".to_string()),
+    }
+}
+
+impl fmt::Display for RomDatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for RomDatabaseError {}
@@ -0,0 +1,89 @@
+use crate::rom_database::{RomDatabase, RomDatabaseError};
+use crate::{IndexIncrement, Quirks, QuirksProfile};
+use demonstrate::demonstrate;
+
+const HASH: &str = "0123456789abcdef0123456789abcdef01234567";
+const HASH_BYTES: [u8; 20] = [
+    0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF,
+    0x01, 0x23, 0x45, 0x67,
+];
+
+fn parse_error(source: &str) -> RomDatabaseError {
+    match RomDatabase::parse(source) {
+        Err(error) => error,
+        Ok(_) => panic!("Parsing succeeded"),
+    }
+}
+
+demonstrate! {
+    describe "ROM database" {
+        use super::*;
+
+        it "parses all the fields" {
+            let source = format!(
+                "# Comment\n\n[{}]\ntitle = Test\nplatform = superchip\nquirks = clip_sprites=false load_store=x+1\nclock = 1000\nkeys = Up=5 Space=a\npalette = 000000 FFFFFF 808080 FF0000\n",
+                HASH
+            );
+
+            let database = RomDatabase::parse(&source).unwrap();
+            let rom_info = database.get(&HASH_BYTES).unwrap();
+
+            let mut expected_quirks = Quirks::profile(QuirksProfile::SuperChip);
+            expected_quirks.clip_sprites = false;
+            expected_quirks.load_store = IndexIncrement::XPlusOne;
+
+            assert_eq!(rom_info.title, "Test");
+            assert_eq!(rom_info.platform, Some(QuirksProfile::SuperChip));
+            assert_eq!(rom_info.quirks, Some(expected_quirks));
+            assert_eq!(rom_info.clock_speed, Some(1000));
+            assert_eq!(rom_info.keys, vec![("Up".to_string(), 5), ("Space".to_string(), 10)]);
+
+            let palette = rom_info.palette.unwrap();
+
+            assert_eq!((palette[3].0, palette[3].1, palette[3].2), (0xFF, 0x00, 0x00));
+        }
+
+        it "leaves the unspecified fields unset" {
+            let database = RomDatabase::parse(&format!("[{}]\ntitle = Test", HASH)).unwrap();
+            let rom_info = database.get(&HASH_BYTES).unwrap();
+
+            assert!(rom_info.quirks.is_none());
+            assert!(rom_info.clock_speed.is_none());
+            assert!(rom_info.palette.is_none());
+        }
+
+        it "reports the errors location" {
+            assert_eq!(parse_error(&format!("[{}]\ntitle = Test\n\nclock = fast", HASH)).line, 4);
+            assert_eq!(parse_error(&format!("[{}]\nquirks = jump_uses_vx=maybe", HASH)).line, 2);
+            assert_eq!(parse_error(&format!("\n[{}]\nclock = 500", HASH)), RomDatabaseError { line: 2, message: "Missing title".to_string() });
+            assert_eq!(parse_error("[0123]").message, "Invalid SHA-1: 0123");
+        }
+
+        it "includes the sample ROMs in the bundled database" {
+            let database = RomDatabase::bundled();
+
+            let flightrunner = include_bytes!("../extra/flightrunner.ch8");
+            let tombstontipp = include_bytes!("../extra/tombstontipp.ch8");
+
+            let flightrunner_info = database.lookup(flightrunner).unwrap();
+
+            assert_eq!(flightrunner_info.title, "Flight Runner");
+            assert_eq!(flightrunner_info.platform, Some(QuirksProfile::CosmacVip));
+            assert_eq!(flightrunner_info.clock_speed, Some(1200));
+            assert_eq!(flightrunner_info.keys.len(), 4);
+
+            let background = flightrunner_info.palette.unwrap()[0];
+
+            assert_eq!((background.0, background.1, background.2), (0x99, 0x66, 0x00));
+
+            let mut expected_quirks = Quirks::profile(QuirksProfile::CosmacVip);
+            expected_quirks.clip_sprites = false;
+            expected_quirks.logic_resets_VF = false;
+            expected_quirks.display_wait = false;
+
+            assert_eq!(flightrunner_info.quirks, Some(expected_quirks));
+            assert_eq!(database.lookup(tombstontipp).unwrap().quirks, Some(expected_quirks));
+            assert!(database.lookup(&[0x12, 0x00]).is_none());
+        }
+    }
+}
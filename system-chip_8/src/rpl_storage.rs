@@ -0,0 +1,67 @@
+// Persistence of the HP-48 RPL user flags (FX75/FX85).
+//
+// On the HP-48, the flags survive the program termination, and games use them e.g. for the high
+// scores; the storage makes them survive across sessions. The flags are keyed by ROM (SHA-1), so
+// that each program sees its own.
+//
+// The storage is pluggable (see `Chip8::set_rpl_flags_storage()`); it's written on each FX75, and
+// read when set. Storage failures are not emulation errors, so the trait is infallible; the
+// implementations handle (e.g. report) them.
+
+use crate::{Byte, Chip8, RPL_FLAGS_COUNT};
+use interfaces_frontend::IoFrontend;
+
+use std::collections::HashMap;
+
+pub trait RplFlagsStorage: Send {
+    /// None if no flags have been stored for the ROM.
+    ///
+    fn load(&mut self, rom_hash: &[Byte; 20]) -> Option<Vec<Byte>>;
+
+    fn store(&mut self, rom_hash: &[Byte; 20], flags: &[Byte]);
+}
+
+/// Keeps the flags for the lifetime of the instance; useful for tests.
+///
+#[derive(Default)]
+pub struct MemoryRplFlagsStorage {
+    flags: HashMap<[Byte; 20], Vec<Byte>>,
+}
+
+impl MemoryRplFlagsStorage {
+    pub fn new() -> MemoryRplFlagsStorage {
+        MemoryRplFlagsStorage::default()
+    }
+}
+
+impl RplFlagsStorage for MemoryRplFlagsStorage {
+    fn load(&mut self, rom_hash: &[Byte; 20]) -> Option<Vec<Byte>> {
+        self.flags.get(rom_hash).cloned()
+    }
+
+    fn store(&mut self, rom_hash: &[Byte; 20], flags: &[Byte]) {
+        self.flags.insert(*rom_hash, flags.to_vec());
+    }
+}
+
+impl<T: IoFrontend> Chip8<T> {
+    /// Sets the storage, and loads the flags of the current ROM from it. Stored flags of a
+    /// different size (e.g. written by another emulator) are truncated/zero-padded.
+    ///
+    pub fn set_rpl_flags_storage(&mut self, mut storage: Box<dyn RplFlagsStorage>) {
+        if let Some(flags) = storage.load(&self.rom_hash) {
+            let length = flags.len().min(RPL_FLAGS_COUNT);
+
+            self.rpl_flags = [0; RPL_FLAGS_COUNT];
+            self.rpl_flags[..length].copy_from_slice(&flags[..length]);
+        }
+
+        self.rpl_flags_storage = Some(storage);
+    }
+
+    pub(crate) fn persist_rpl_flags(&mut self) {
+        if let Some(storage) = &mut self.rpl_flags_storage {
+            storage.store(&self.rom_hash, &self.rpl_flags);
+        }
+    }
+}
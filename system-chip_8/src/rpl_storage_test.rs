@@ -0,0 +1,127 @@
+use crate::rpl_storage::{MemoryRplFlagsStorage, RplFlagsStorage};
+use crate::test_harness::{new_program_chip8, run_steps, xo_chip};
+use crate::{Byte, Word};
+use demonstrate::demonstrate;
+use sha1_smol::Sha1;
+
+use std::sync::{Arc, Mutex};
+
+// Storage shared with the test, so that it can be inspected after being moved into the machine.
+//
+#[derive(Clone, Default)]
+struct SharedStorage(Arc<Mutex<MemoryRplFlagsStorage>>);
+
+impl RplFlagsStorage for SharedStorage {
+    fn load(&mut self, rom_hash: &[Byte; 20]) -> Option<Vec<Byte>> {
+        self.0.lock().unwrap().load(rom_hash)
+    }
+
+    fn store(&mut self, rom_hash: &[Byte; 20], flags: &[Byte]) {
+        self.0.lock().unwrap().store(rom_hash, flags)
+    }
+}
+
+fn rom_hash(program: &[Word]) -> [Byte; 20] {
+    let rom = program
+        .iter()
+        .flat_map(|word| word.to_be_bytes().to_vec())
+        .collect::<Vec<_>>();
+
+    Sha1::from(rom).digest().bytes()
+}
+
+demonstrate! {
+    describe "memory RPL flags storage" {
+        use super::*;
+
+        before {
+            let mut storage = MemoryRplFlagsStorage::new();
+        }
+
+        it "returns nothing for unknown ROMs" {
+            assert_eq!(storage.load(&[1; 20]), None);
+        }
+
+        it "keeps the flags of each ROM separately" {
+            storage.store(&[1; 20], &[1, 2, 3]);
+            storage.store(&[2; 20], &[4, 5, 6]);
+            storage.store(&[1; 20], &[7, 8, 9]);
+
+            assert_eq!(storage.load(&[1; 20]), Some(vec![7, 8, 9]));
+            assert_eq!(storage.load(&[2; 20]), Some(vec![4, 5, 6]));
+        }
+    }
+
+    describe "machine RPL flags storage" {
+        use super::*;
+
+        before {
+            let storage = SharedStorage::default();
+        }
+
+        it "stores the flags on FX75, keyed by the ROM hash" {
+            // V0 := 0x11; V1 := 0x22; V2 := 0x33; saveflags V2
+            //
+            let program = [0x6011, 0x6122, 0x6233, 0xF275];
+            let mut chip8 = new_program_chip8(&program, xo_chip());
+
+            chip8.set_rpl_flags_storage(Box::new(storage.clone()));
+
+            run_steps(&mut chip8, 4);
+
+            let mut expected_flags = vec![0; 16];
+            expected_flags[0..3].copy_from_slice(&[0x11, 0x22, 0x33]);
+
+            let mut inner_storage = storage.0.lock().unwrap();
+
+            assert_eq!(inner_storage.load(&rom_hash(&program)), Some(expected_flags));
+            assert_eq!(inner_storage.load(&[0; 20]), None);
+        }
+
+        it "loads the stored flags, truncating them, for FX85" {
+            // loadflags VF
+            //
+            let program = [0xFF85];
+
+            storage.0.lock().unwrap().store(&rom_hash(&program), &(1..=20).collect::<Vec<_>>());
+
+            let mut chip8 = new_program_chip8(&program, xo_chip());
+
+            chip8.set_rpl_flags_storage(Box::new(storage));
+
+            run_steps(&mut chip8, 1);
+
+            assert_eq!(chip8.V.to_vec(), (1..=16).collect::<Vec<_>>());
+        }
+
+        it "loads the stored flags, zero-padding them, for FX85" {
+            let program = [0xFF85];
+
+            storage.0.lock().unwrap().store(&rom_hash(&program), &[9, 8]);
+
+            let mut chip8 = new_program_chip8(&program, xo_chip());
+
+            chip8.V = [0xFF; 16];
+            chip8.set_rpl_flags_storage(Box::new(storage));
+
+            run_steps(&mut chip8, 1);
+
+            let mut expected_registers = [0; 16];
+            expected_registers[0..2].copy_from_slice(&[9, 8]);
+
+            assert_eq!(chip8.V, expected_registers);
+        }
+
+        it "ignores the flags stored for other ROMs" {
+            storage.0.lock().unwrap().store(&[0; 20], &[9; 16]);
+
+            let mut chip8 = new_program_chip8(&[0xFF85], xo_chip());
+
+            chip8.set_rpl_flags_storage(Box::new(storage));
+
+            run_steps(&mut chip8, 1);
+
+            assert_eq!(chip8.V, [0; 16]);
+        }
+    }
+}
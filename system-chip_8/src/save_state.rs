@@ -0,0 +1,462 @@
+// Save states.
+//
+// The format is a sequence of tagged chunks (IFF-style), wrapped by a header and a checksum:
+//
+//   magic:          4 bytes; "CH8S"
+//   version:        u16
+//   payload length: u32
+//   payload:        chunks; each is: tag (4 bytes), data length (u32), data
+//   checksum:       u32; CRC-32 (IEEE) of all the preceding bytes
+//
+// All the integers are little endian. Loaders skip the chunks they don't know, so that adding
+// data doesn't necessarily require a version bump; the version changes only when the meaning of
+// existing chunks changes.
+//
+// Chunks (version 1):
+//
+//   "RAM ": the whole RAM
+//   "REGS": V0..VF (16 bytes), I (u32), PC (u32), SP (u8)
+//   "STCK": 16 * u32
+//   "TIMR": delay timer, sound timer, timers accumulator (u32), vblank occurred (u8)
+//   "KEYS": 16 bytes; 1/0 for pressed/released
+//   "SCRN": width (u16), height (u16), hires (u8), then one byte per pixel (bitmask of the planes
+//           set; for non-XO-CHIP programs, 1/0 for on/off); only the standard (64x32, not hires)
+//           and hires (128x64) modes are valid
+//   "RPL ": 16 bytes; optional (defaults to zeros)
+//   "XOCH": selected planes (u8; bitmask of the 2 planes), pitch (u8), audio pattern loaded (u8),
+//           audio pattern (16 bytes); optional (defaults to the power-on values)
+//   "RNG ": random generator state (generator-specific); optional (the current state is kept)
+//   "KWAT": FX0A wait: waiting (u8), X (u8), key pressed during the wait (u8; 0xFF if none), keys
+//           status at the previous cycle (16 bytes); optional (defaults to not waiting)
+//
+// I is stored saturated: any value past the RAM end behaves the same (the accesses fail), so
+// u32::MAX is equivalent to any larger value.
+
+use crate::audio::{AudioState, AUDIO_PATTERN_SIZE};
+use crate::{
+    Byte, Chip8, KeyWait, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH, PLANES_COUNT, RAM_SIZE,
+    RPL_FLAGS_COUNT, STANDARD_SCREEN_HEIGHT, STANDARD_SCREEN_WIDTH,
+};
+use interfaces_frontend::IoFrontend;
+
+use std::convert::TryInto;
+use std::fmt;
+
+const MAGIC: &[u8; 4] = b"CH8S";
+const VERSION: u16 = 1;
+
+const HEADER_SIZE: usize = 10;
+const CHECKSUM_SIZE: usize = 4;
+
+// "KWAT" value of the pressed key, when there is none.
+//
+const NO_KEY: u8 = 0xFF;
+
+#[derive(Debug, PartialEq)]
+pub enum SaveStateError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    ChecksumMismatch,
+    MissingChunk(String),
+    InvalidChunk(String),
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveStateError::InvalidMagic => write!(f, "Not a save state (invalid magic)"),
+            SaveStateError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported save state version: {}", version)
+            }
+            SaveStateError::Truncated => write!(f, "Truncated save state"),
+            SaveStateError::ChecksumMismatch => write!(f, "Save state checksum mismatch"),
+            SaveStateError::MissingChunk(tag) => write!(f, "Missing save state chunk: {:?}", tag),
+            SaveStateError::InvalidChunk(tag) => write!(f, "Invalid save state chunk: {:?}", tag),
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+impl<T: IoFrontend> Chip8<T> {
+    /// Snapshots the complete machine state; see the module comment for the format.
+    ///
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = ChunksWriter::new();
+
+        writer.chunk(b"RAM ", &self.ram[..]);
+
+        let mut registers = self.V.to_vec();
+        registers.extend_from_slice(&(self.I.min(u32::MAX as usize) as u32).to_le_bytes());
+        registers.extend_from_slice(&(self.PC as u32).to_le_bytes());
+        registers.push(self.SP as u8);
+        writer.chunk(b"REGS", &registers);
+
+        let stack = self
+            .stack
+            .iter()
+            .flat_map(|address| (*address as u32).to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+        writer.chunk(b"STCK", &stack);
+
+        let mut timers = vec![self.delay_timer, self.sound_timer];
+        timers.extend_from_slice(&self.timers_accumulator.to_le_bytes());
+        timers.push(self.vblank_occurred as u8);
+        writer.chunk(b"TIMR", &timers);
+
+        let keys = self
+            .keys_status
+            .iter()
+            .map(|pressed| *pressed as u8)
+            .collect::<Vec<_>>();
+        writer.chunk(b"KEYS", &keys);
+
+        let mut screen = Vec::with_capacity(5 + self.screen.len());
+        screen.extend_from_slice(&(self.screen_width as u16).to_le_bytes());
+        screen.extend_from_slice(&(self.screen_height as u16).to_le_bytes());
+        screen.push((self.screen_width == HIRES_SCREEN_WIDTH) as u8);
+        screen.extend_from_slice(&self.screen);
+        writer.chunk(b"SCRN", &screen);
+
+        writer.chunk(b"RPL ", &self.rpl_flags);
+
+        let audio_state = *self.audio_state.lock().unwrap();
+        let mut xo_chip = vec![
+            self.selected_planes,
+            audio_state.pitch,
+            audio_state.pattern.is_some() as u8,
+        ];
+        xo_chip.extend_from_slice(&audio_state.pattern.unwrap_or([0; AUDIO_PATTERN_SIZE]));
+        writer.chunk(b"XOCH", &xo_chip);
+
+        writer.chunk(b"RNG ", &self.random_generator.state());
+
+        let mut key_wait = match self.key_wait {
+            Some(key_wait) => vec![
+                1,
+                key_wait.Vx as u8,
+                key_wait
+                    .pressed_key
+                    .map_or(NO_KEY, |key_index| key_index as u8),
+            ],
+            None => vec![0, 0, NO_KEY],
+        };
+        let previous_keys = self
+            .key_wait
+            .map_or([false; 16], |key_wait| key_wait.previous_keys);
+        key_wait.extend(previous_keys.iter().map(|pressed| *pressed as u8));
+        writer.chunk(b"KWAT", &key_wait);
+
+        writer.finish()
+    }
+
+    /// Restores a state produced by `save_state()`.
+    ///
+    /// The data is fully validated before being applied, so on error, the machine is unchanged.
+    ///
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SaveStateError> {
+        let chunks = ChunksReader::new(data)?;
+
+        let ram = chunks.get(b"RAM ", Some(RAM_SIZE))?;
+
+        let registers = chunks.get(b"REGS", Some(16 + 4 + 4 + 1))?;
+        let I = read_u32(&registers[16..20]) as usize;
+        let PC = read_u32(&registers[20..24]) as usize;
+        let SP = registers[24] as usize;
+
+        if SP > self.stack.len() {
+            return Err(SaveStateError::InvalidChunk("REGS".to_string()));
+        }
+
+        let stack = chunks.get(b"STCK", Some(4 * self.stack.len()))?;
+        let timers = chunks.get(b"TIMR", Some(2 + 4 + 1))?;
+        let keys = chunks.get(b"KEYS", Some(self.keys_status.len()))?;
+
+        let screen = chunks.get(b"SCRN", None)?;
+
+        if screen.len() < 5 {
+            return Err(SaveStateError::InvalidChunk("SCRN".to_string()));
+        }
+
+        let screen_width = u16::from_le_bytes([screen[0], screen[1]]) as usize;
+        let screen_height = u16::from_le_bytes([screen[2], screen[3]]) as usize;
+        let hires = screen[4];
+        let pixels = &screen[5..];
+
+        let valid_mode = matches!(
+            (screen_width, screen_height, hires),
+            (STANDARD_SCREEN_WIDTH, STANDARD_SCREEN_HEIGHT, 0)
+                | (HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT, 1)
+        );
+
+        let max_pixel_value = (1 << PLANES_COUNT) - 1;
+
+        if !valid_mode
+            || pixels.len() != screen_width * screen_height
+            || pixels.iter().any(|pixel| *pixel > max_pixel_value)
+        {
+            return Err(SaveStateError::InvalidChunk("SCRN".to_string()));
+        }
+
+        let rpl_flags = chunks.get_optional(b"RPL ", Some(RPL_FLAGS_COUNT))?;
+        let xo_chip = chunks.get_optional(b"XOCH", Some(3 + AUDIO_PATTERN_SIZE))?;
+
+        if let Some(xo_chip) = xo_chip {
+            if xo_chip[0] > (1 << PLANES_COUNT) - 1 {
+                return Err(SaveStateError::InvalidChunk("XOCH".to_string()));
+            }
+        }
+
+        let key_wait = match chunks.get_optional(b"KWAT", Some(3 + 16))? {
+            Some(key_wait) if key_wait[0] != 0 => {
+                let pressed_key = match key_wait[2] {
+                    NO_KEY => None,
+                    key_index if key_index < 16 => Some(key_index as usize),
+                    _ => return Err(SaveStateError::InvalidChunk("KWAT".to_string())),
+                };
+
+                if key_wait[1] >= 16 {
+                    return Err(SaveStateError::InvalidChunk("KWAT".to_string()));
+                }
+
+                let mut previous_keys = [false; 16];
+
+                for (pressed, value) in previous_keys.iter_mut().zip(key_wait[3..].iter()) {
+                    *pressed = *value != 0;
+                }
+
+                Some(KeyWait {
+                    Vx: key_wait[1] as usize,
+                    previous_keys,
+                    pressed_key,
+                })
+            }
+            _ => None,
+        };
+
+        // The generator validates the state on restore, leaving itself unchanged on error, so this
+        // must be the last validation step.
+        //
+        if let Some(random_generator_state) = chunks.get_optional(b"RNG ", None)? {
+            if !self.random_generator.set_state(random_generator_state) {
+                return Err(SaveStateError::InvalidChunk("RNG ".to_string()));
+            }
+        }
+
+        // Validation is complete; apply the state.
+
+        self.ram.copy_from_slice(ram);
+
+        self.V.copy_from_slice(&registers[0..16]);
+        self.I = I;
+        self.PC = PC;
+        self.SP = SP;
+
+        for (address, bytes) in self.stack.iter_mut().zip(stack.chunks(4)) {
+            *address = read_u32(bytes) as usize;
+        }
+
+        let previous_sound_timer = self.sound_timer;
+
+        self.delay_timer = timers[0];
+        self.sound_timer = timers[1];
+        self.timers_accumulator = read_u32(&timers[2..6]);
+        self.vblank_occurred = timers[6] != 0;
+
+        for (pressed, value) in self.keys_status.iter_mut().zip(keys.iter()) {
+            *pressed = *value != 0;
+        }
+
+        self.key_wait = key_wait;
+
+        match rpl_flags {
+            Some(rpl_flags) => self.rpl_flags.copy_from_slice(rpl_flags),
+            None => self.rpl_flags = [0; RPL_FLAGS_COUNT],
+        }
+
+        if screen_width != self.screen_width || screen_height != self.screen_height {
+            self.screen_width = screen_width;
+            self.screen_height = screen_height;
+            self.setup_graphics();
+        }
+
+        self.screen = pixels.to_vec();
+        self.screen_changed = true;
+
+        match xo_chip {
+            Some(xo_chip) => {
+                self.selected_planes = xo_chip[0];
+
+                let mut audio_state = self.audio_state.lock().unwrap();
+                audio_state.pitch = xo_chip[1];
+                audio_state.pattern = if xo_chip[2] != 0 {
+                    let mut pattern = [0; AUDIO_PATTERN_SIZE];
+                    pattern.copy_from_slice(&xo_chip[3..]);
+                    Some(pattern)
+                } else {
+                    None
+                };
+            }
+            None => {
+                self.selected_planes = 1;
+                *self.audio_state.lock().unwrap() = AudioState::new();
+            }
+        }
+
+        self.handle_sound_playback(previous_sound_timer);
+        self.update_frontend_screen(true);
+
+        Ok(())
+    }
+}
+
+pub(crate) struct ChunksWriter {
+    buffer: Vec<Byte>,
+}
+
+impl ChunksWriter {
+    pub(crate) fn new() -> ChunksWriter {
+        let mut buffer = Vec::new();
+
+        buffer.extend_from_slice(MAGIC);
+        buffer.extend_from_slice(&VERSION.to_le_bytes());
+        // Payload length placeholder; set on finish.
+        buffer.extend_from_slice(&[0; 4]);
+
+        ChunksWriter { buffer }
+    }
+
+    pub(crate) fn chunk(&mut self, tag: &[u8; 4], data: &[Byte]) {
+        self.buffer.extend_from_slice(tag);
+        self.buffer
+            .extend_from_slice(&(data.len() as u32).to_le_bytes());
+        self.buffer.extend_from_slice(data);
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<Byte> {
+        let payload_length = (self.buffer.len() - HEADER_SIZE) as u32;
+        self.buffer[6..HEADER_SIZE].copy_from_slice(&payload_length.to_le_bytes());
+
+        let checksum = crc32(&self.buffer);
+        self.buffer.extend_from_slice(&checksum.to_le_bytes());
+
+        self.buffer
+    }
+}
+
+pub(crate) struct ChunksReader<'a> {
+    chunks: Vec<([u8; 4], &'a [Byte])>,
+}
+
+impl<'a> ChunksReader<'a> {
+    pub(crate) fn new(data: &'a [Byte]) -> Result<ChunksReader<'a>, SaveStateError> {
+        if data.len() < 4 || &data[0..4] != MAGIC {
+            return Err(SaveStateError::InvalidMagic);
+        }
+
+        if data.len() < HEADER_SIZE + CHECKSUM_SIZE {
+            return Err(SaveStateError::Truncated);
+        }
+
+        let version = u16::from_le_bytes([data[4], data[5]]);
+
+        if version != VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+
+        let payload_length = read_u32(&data[6..HEADER_SIZE]) as usize;
+
+        if data.len() != HEADER_SIZE + payload_length + CHECKSUM_SIZE {
+            return Err(SaveStateError::Truncated);
+        }
+
+        let (checked_data, checksum) = data.split_at(HEADER_SIZE + payload_length);
+
+        if crc32(checked_data) != read_u32(checksum) {
+            return Err(SaveStateError::ChecksumMismatch);
+        }
+
+        let mut payload = &checked_data[HEADER_SIZE..];
+        let mut chunks = Vec::new();
+
+        while !payload.is_empty() {
+            if payload.len() < 8 {
+                return Err(SaveStateError::Truncated);
+            }
+
+            let tag = payload[0..4].try_into().unwrap();
+            let length = read_u32(&payload[4..8]) as usize;
+
+            if payload.len() < 8 + length {
+                return Err(SaveStateError::Truncated);
+            }
+
+            chunks.push((tag, &payload[8..8 + length]));
+            payload = &payload[8 + length..];
+        }
+
+        Ok(ChunksReader { chunks })
+    }
+
+    // If `expected_length` is specified, it's enforced.
+    //
+    pub(crate) fn get(
+        &self,
+        tag: &[u8; 4],
+        expected_length: Option<usize>,
+    ) -> Result<&'a [Byte], SaveStateError> {
+        self.get_optional(tag, expected_length)?
+            .ok_or_else(|| SaveStateError::MissingChunk(String::from_utf8_lossy(tag).to_string()))
+    }
+
+    // For chunks added after the format introduction; a missing chunk is not an error.
+    //
+    pub(crate) fn get_optional(
+        &self,
+        tag: &[u8; 4],
+        expected_length: Option<usize>,
+    ) -> Result<Option<&'a [Byte]>, SaveStateError> {
+        let data = self
+            .chunks
+            .iter()
+            .find(|(chunk_tag, _)| chunk_tag == tag)
+            .map(|(_, data)| *data);
+
+        match (data, expected_length) {
+            (Some(data), Some(expected_length)) if data.len() != expected_length => Err(
+                SaveStateError::InvalidChunk(String::from_utf8_lossy(tag).to_string()),
+            ),
+            _ => Ok(data),
+        }
+    }
+}
+
+// The checksum stored in the trailer (i.e. the CRC-32 of the state data); note that the CRC-32 of
+// a whole state is a constant, since it includes the checksum.
+//
+pub(crate) fn state_checksum(state: &[Byte]) -> u32 {
+    read_u32(&state[state.len() - CHECKSUM_SIZE..])
+}
+
+fn read_u32(bytes: &[Byte]) -> u32 {
+    u32::from_le_bytes(bytes[0..4].try_into().unwrap())
+}
+
+// Bitwise (tableless) CRC-32, IEEE polynomial (reversed). Speed is not a concern, given the data
+// size.
+//
+pub(crate) fn crc32(data: &[Byte]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
@@ -0,0 +1,139 @@
+use crate::save_state::{crc32, ChunksReader, ChunksWriter, SaveStateError};
+use crate::test_harness::{new_program_chip8, run_steps, xo_chip};
+use demonstrate::demonstrate;
+
+use std::convert::TryInto;
+
+// Rewrites a save state, replacing the data of the given chunk.
+//
+fn replace_chunk(state: &[u8], tag: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut payload = &state[10..state.len() - 4];
+    let mut writer = ChunksWriter::new();
+
+    while !payload.is_empty() {
+        let chunk_tag: &[u8; 4] = payload[0..4].try_into().unwrap();
+        let length = u32::from_le_bytes(payload[4..8].try_into().unwrap()) as usize;
+
+        if chunk_tag == tag {
+            writer.chunk(tag, data);
+        } else {
+            writer.chunk(chunk_tag, &payload[8..8 + length]);
+        }
+
+        payload = &payload[8 + length..];
+    }
+
+    writer.finish()
+}
+
+demonstrate! {
+    describe "save state" {
+        use super::*;
+
+        it "computes the CRC-32 IEEE check value" {
+            assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        }
+
+        context "machine" {
+            it "restores a state that continues like the original machine" {
+                // hires; V0 := 0x10; delay := V0; I := 0x200; sprite V0 V1 5; (0x20A) jump 0x20A
+                //
+                let mut chip8 = new_program_chip8(&[0x00FF, 0x6010, 0xF015, 0xA200, 0xD015, 0x120A], xo_chip());
+                run_steps(&mut chip8, 7);
+
+                // Out of the 16 bits range, reachable via FX1E.
+                //
+                chip8.I = 0x1_2345;
+
+                let state = chip8.save_state();
+
+                let mut restored = new_program_chip8(&[0x1200], xo_chip());
+                restored.load_state(&state).unwrap();
+
+                assert_eq!(restored.save_state(), state);
+                assert_eq!(restored.I, 0x1_2345);
+                assert_eq!(restored.timers_accumulator, chip8.timers_accumulator);
+                assert_ne!(restored.timers_accumulator, 0);
+
+                for _ in 0..3 {
+                    chip8.run_frame().unwrap();
+                    restored.run_frame().unwrap();
+
+                    assert_eq!(restored.save_state(), chip8.save_state());
+                }
+            }
+
+            it "rejects the screen modes other than the standard and hires ones" {
+                let mut chip8 = new_program_chip8(&[0x1200], xo_chip());
+                let state = chip8.save_state();
+
+                for (width, height, hires) in &[(0_u16, 0_u16, 0), (1, 2048, 0), (64, 32, 1), (128, 64, 0)] {
+                    let mut screen = width.to_le_bytes().to_vec();
+                    screen.extend_from_slice(&height.to_le_bytes());
+                    screen.push(*hires);
+                    screen.resize(5 + *width as usize * *height as usize, 0);
+
+                    let invalid_state = replace_chunk(&state, b"SCRN", &screen);
+
+                    assert_eq!(chip8.load_state(&invalid_state), Err(SaveStateError::InvalidChunk("SCRN".to_string())));
+                    assert_eq!(chip8.save_state(), state);
+                }
+            }
+            it "rejects the selection of planes that do not exist" {
+                let mut chip8 = new_program_chip8(&[0x1200], xo_chip());
+                let state = chip8.save_state();
+
+                let mut xo_chip_data = vec![0b100, 64, 0];
+                xo_chip_data.resize(3 + 16, 0);
+
+                let invalid_state = replace_chunk(&state, b"XOCH", &xo_chip_data);
+
+                assert_eq!(chip8.load_state(&invalid_state), Err(SaveStateError::InvalidChunk("XOCH".to_string())));
+                assert_eq!(chip8.save_state(), state);
+            }
+        }
+
+        context "chunks" {
+            before {
+                let mut writer = ChunksWriter::new();
+                writer.chunk(b"ABCD", &[1, 2, 3]);
+                writer.chunk(b"EFGH", &[]);
+
+                #[allow(unused_mut)]
+                let mut data = writer.finish();
+            }
+
+            it "reads back the written chunks" {
+                let reader = ChunksReader::new(&data).unwrap();
+
+                assert_eq!(reader.get(b"ABCD", Some(3)).unwrap(), &[1, 2, 3]);
+                assert_eq!(reader.get(b"EFGH", None).unwrap(), &[] as &[u8]);
+            }
+
+            it "reports missing and invalid chunks" {
+                let reader = ChunksReader::new(&data).unwrap();
+
+                assert_eq!(reader.get(b"IJKL", None), Err(SaveStateError::MissingChunk("IJKL".to_string())));
+                assert_eq!(reader.get(b"ABCD", Some(4)), Err(SaveStateError::InvalidChunk("ABCD".to_string())));
+            }
+
+            it "detects corruption" {
+                data[12] ^= 0xFF;
+
+                assert_eq!(ChunksReader::new(&data).err(), Some(SaveStateError::ChecksumMismatch));
+            }
+
+            it "detects truncation" {
+                data.pop();
+
+                assert_eq!(ChunksReader::new(&data).err(), Some(SaveStateError::Truncated));
+            }
+
+            it "rejects other formats" {
+                data[0] = b'X';
+
+                assert_eq!(ChunksReader::new(&data).err(), Some(SaveStateError::InvalidMagic));
+            }
+        }
+    }
+}
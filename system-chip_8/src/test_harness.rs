@@ -0,0 +1,131 @@
+// Support for the tests running programs against the headless frontend: machine construction and
+// stepping helpers, and whole ROMs runs compared against golden frames.
+//
+// The golden files (`extra/goldens/<name>.txt`) store the SHA-1 of a frame (RGB bytes). In order
+// to (re)generate them, e.g. after an intended rendering change, run the tests with the
+// `UPDATE_GOLDENS` environment variable set.
+
+use crate::{Byte, Chip8, Quirks, QuirksProfile, Word, DEFAULT_CLOCK_SPEED};
+use interfaces_frontend::{events::EventCode, headless::HeadlessFrontend, video::Pixel};
+use sha1_smol::Sha1;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const GOLDENS_DIRECTORY: &str = "extra/goldens";
+const UPDATE_GOLDENS_VARIABLE: &str = "UPDATE_GOLDENS";
+
+const SEED: u64 = 0;
+
+pub(crate) fn new_chip8(rom: &[Byte], quirks: Quirks) -> Chip8<HeadlessFrontend> {
+    Chip8::new(
+        HeadlessFrontend::new(),
+        rom,
+        quirks,
+        DEFAULT_CLOCK_SPEED,
+        SEED,
+    )
+    .unwrap()
+}
+
+// The program is given as words (instructions or data), loaded at the programs location.
+//
+pub(crate) fn new_program_chip8(program: &[Word], quirks: Quirks) -> Chip8<HeadlessFrontend> {
+    let rom = program
+        .iter()
+        .flat_map(|word| word.to_be_bytes().to_vec())
+        .collect::<Vec<_>>();
+
+    new_chip8(&rom, quirks)
+}
+
+pub(crate) fn run_steps(chip8: &mut Chip8<HeadlessFrontend>, steps: usize) {
+    for _ in 0..steps {
+        chip8.step().unwrap();
+    }
+}
+
+// The XO-CHIP profile is the most permissive: no display wait, and wrapping sprites.
+//
+pub(crate) fn xo_chip() -> Quirks {
+    Quirks::profile(QuirksProfile::XoChip)
+}
+
+/// Frame at which the key event is sent, key, and pressed (true) or released (false).
+///
+pub(crate) type ScriptedKey = (u64, EventCode, bool);
+
+// Runs the ROM for the given number of frames; the scripted keys are sent before the frame.
+//
+pub(crate) fn run_rom(
+    rom: &[Byte],
+    quirks: Quirks,
+    frames: u64,
+    key_script: &[ScriptedKey],
+) -> Chip8<HeadlessFrontend> {
+    let mut chip8 = new_chip8(rom, quirks);
+
+    run_frames(&mut chip8, frames, key_script);
+
+    chip8
+}
+
+// Runs the given number of frames; the key script frames are relative to the first one.
+//
+pub(crate) fn run_frames(
+    chip8: &mut Chip8<HeadlessFrontend>,
+    frames: u64,
+    key_script: &[ScriptedKey],
+) {
+    for frame in 0..frames {
+        for (_, key, pressed) in key_script
+            .iter()
+            .filter(|(key_frame, ..)| *key_frame == frame)
+        {
+            chip8.io_frontend_mut().push_event(key.clone(), *pressed);
+        }
+
+        chip8.run_frame().unwrap();
+    }
+}
+
+pub(crate) fn frame_hash(frame: &[Pixel]) -> String {
+    let mut hasher = Sha1::new();
+
+    for Pixel(r, g, b) in frame {
+        hasher.update(&[*r, *g, *b]);
+    }
+
+    hasher.digest().to_string()
+}
+
+pub(crate) fn assert_golden_frame(name: &str, frame: &[Pixel]) {
+    let golden_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join(GOLDENS_DIRECTORY)
+        .join(format!("{}.txt", name));
+
+    let hash = frame_hash(frame);
+
+    if env::var_os(UPDATE_GOLDENS_VARIABLE).is_some() {
+        fs::create_dir_all(golden_path.parent().unwrap()).unwrap();
+        fs::write(&golden_path, format!("{}\n", hash)).unwrap();
+        return;
+    }
+
+    let golden_hash = fs::read_to_string(&golden_path).unwrap_or_else(|_| {
+        panic!(
+            "Missing golden file {}; run with {} set to create it",
+            golden_path.display(),
+            UPDATE_GOLDENS_VARIABLE
+        )
+    });
+
+    assert_eq!(
+        golden_hash.trim(),
+        hash,
+        "Frame mismatch for {}; if the change is intended, run with {} set",
+        name,
+        UPDATE_GOLDENS_VARIABLE
+    );
+}
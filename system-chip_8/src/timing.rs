@@ -0,0 +1,137 @@
+// Instruction timing models.
+//
+// By default, each instruction takes the same time (1 / clock speed). The COSMAC VIP model instead
+// charges each instruction the machine cycles taken by the routine of the original CDP1802
+// interpreter, so that programs relying on the authentic speed (e.g. the ones sized around the
+// slow DXYN) behave like on the real hardware.
+//
+// The VIP CDP1802 runs at 1.7609 MHz, with 8 clock cycles per machine cycle, so that there are
+// 3668 machine cycles per frame (60 Hz). On each frame, the display DMA steals 1024 cycles (128
+// scanlines of 8 bytes), and the interrupt routine (which also decrements the timers) 46 more.
+//
+// The costs include the interpreter fetch/decode loop. They're averaged from the analysis of the
+// interpreter routines (see https://jackson-s.me/2019/07/13/Chip-8-Instruction-Scheduling-and-Frequency.html);
+// for the instructions whose routine has a data-dependent number of iterations, the iterations are
+// counted.
+
+use crate::instruction::Instruction;
+use crate::Byte;
+
+use std::fmt;
+use std::str::FromStr;
+
+const FRAME_CYCLES: u32 = 3668;
+const VBLANK_CYCLES: u32 = 1024 + 46;
+
+/// Machine cycles available to the interpreter on each frame.
+///
+pub(crate) const VIP_FRAME_CYCLES: u32 = FRAME_CYCLES - VBLANK_CYCLES;
+
+/// Cost of a cycle in which no instruction completes (waiting for a key or for the vblank); the
+/// interpreter loops on a short polling routine.
+///
+pub(crate) const VIP_IDLE_CYCLES: u32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timing {
+    /// Each instruction takes 1 / clock speed.
+    ///
+    Fixed,
+    /// Each instruction takes the CDP1802 machine cycles of the COSMAC VIP interpreter; the clock
+    /// speed is ignored.
+    ///
+    CosmacVip,
+}
+
+impl Timing {
+    pub const NAMES: [&'static str; 2] = ["fixed", "cosmac-vip"];
+}
+
+impl FromStr for Timing {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "fixed" => Ok(Timing::Fixed),
+            "cosmac-vip" => Ok(Timing::CosmacVip),
+            _ => Err(format!("Unknown timing: {}", name)),
+        }
+    }
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Timing::Fixed => "fixed",
+            Timing::CosmacVip => "cosmac-vip",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Machine cycles taken by the instruction on the COSMAC VIP; `V` are the registers before the
+/// execution. Instructions not supported by the VIP are charged as the closest VIP equivalent.
+///
+pub(crate) fn vip_cycles(instruction: &Instruction, V: &[Byte; 16]) -> u32 {
+    match *instruction {
+        Instruction::ClearScreen => 24,
+        Instruction::Return => 23,
+        Instruction::Jump(_) | Instruction::Call(_) | Instruction::JumpPlusV0(..) => 23,
+        Instruction::SkipIfEqualByte(..) | Instruction::SkipIfNotEqualByte(..) => 12,
+        Instruction::SkipIfEqual(..) | Instruction::SkipIfNotEqual(..) => 16,
+        Instruction::SetByte(..) => 6,
+        Instruction::AddByte(..) => 10,
+        Instruction::Set(..)
+        | Instruction::Or(..)
+        | Instruction::And(..)
+        | Instruction::Xor(..)
+        | Instruction::Add(..)
+        | Instruction::Subtract(..)
+        | Instruction::ShiftRight(..)
+        | Instruction::SubtractReverse(..)
+        | Instruction::ShiftLeft(..) => 44,
+        Instruction::SetI(_) | Instruction::SetILong => 12,
+        Instruction::Random(..) => 36,
+        // Each line is shifted to the X position within the byte, bit by bit, then XORed (with
+        // collision check) on the two screen bytes it spans.
+        //
+        Instruction::Draw(Vx, _, lines) => {
+            let lines = if lines == 0 { 16 } else { lines as u32 };
+            let shift = (V[Vx] % 8) as u32;
+
+            68 + lines * (46 + 4 * shift)
+        }
+        Instruction::SkipIfKeyPressed(_) | Instruction::SkipIfKeyNotPressed(_) => 16,
+        Instruction::GetDelayTimer(_)
+        | Instruction::SetDelayTimer(_)
+        | Instruction::SetSoundTimer(_) => 10,
+        Instruction::WaitKey(_) => VIP_IDLE_CYCLES,
+        Instruction::AddToI(_) => 19,
+        Instruction::SetIToFont(_) | Instruction::SetIToBigFont(_) => 20,
+        // The digits are computed by repeated subtraction.
+        //
+        Instruction::StoreBcd(Vx) => {
+            let value = V[Vx];
+            let digits_sum = (value / 100 + value / 10 % 10 + value % 10) as u32;
+
+            40 + 16 * digits_sum
+        }
+        Instruction::Store(Vx)
+        | Instruction::Load(Vx)
+        | Instruction::StoreRplFlags(Vx)
+        | Instruction::LoadRplFlags(Vx) => 14 + 14 * (Vx as u32 + 1),
+        Instruction::StoreRange(Vx, Vy) | Instruction::LoadRange(Vx, Vy) => {
+            14 + 14 * ((Vx.max(Vy) - Vx.min(Vy)) as u32 + 1)
+        }
+        Instruction::ScrollDown(_)
+        | Instruction::ScrollUp(_)
+        | Instruction::ScrollRight
+        | Instruction::ScrollLeft
+        | Instruction::LoresMode
+        | Instruction::HiresMode => 24,
+        Instruction::Exit | Instruction::MachineCall(_) => 23,
+        Instruction::SelectPlanes(_) | Instruction::SetPitch(_) => 10,
+        Instruction::LoadAudioPattern => 14 + 14 * 16,
+    }
+}
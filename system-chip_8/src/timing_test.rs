@@ -0,0 +1,134 @@
+use crate::instruction::Instruction;
+use crate::test_harness::{new_chip8, new_program_chip8, run_steps, xo_chip};
+use crate::timing::{vip_cycles, Timing, VIP_FRAME_CYCLES, VIP_IDLE_CYCLES};
+use crate::Chip8;
+use interfaces_frontend::headless::HeadlessFrontend;
+
+use demonstrate::demonstrate;
+
+// Instructions executed in the next frame.
+//
+fn frame_instructions(chip8: &mut Chip8<HeadlessFrontend>) -> u64 {
+    chip8.start_profiling();
+    chip8.run_frame().unwrap();
+    chip8.stop_profiling().unwrap().instructions()
+}
+
+// Steps (instructions or idle cycles) executed until the timers tick.
+//
+fn frame_steps(chip8: &mut Chip8<HeadlessFrontend>) -> u32 {
+    let start_frame = chip8.frame_count();
+    let mut steps = 0;
+
+    while chip8.frame_count() == start_frame {
+        run_steps(chip8, 1);
+        steps += 1;
+    }
+
+    steps
+}
+
+demonstrate! {
+    describe "COSMAC VIP timing" {
+        use super::*;
+
+        it "charges DXYN according to the lines and the shift" {
+            let mut V = [0; 16];
+
+            let aligned_cost = vip_cycles(&Instruction::Draw(0, 1, 5), &V);
+
+            V[0] = 3;
+
+            let shifted_cost = vip_cycles(&Instruction::Draw(0, 1, 5), &V);
+
+            assert_eq!(aligned_cost, 68 + 5 * 46);
+            assert_eq!(shifted_cost, 68 + 5 * (46 + 4 * 3));
+            assert!(vip_cycles(&Instruction::Draw(0, 1, 15), &V) > shifted_cost);
+        }
+
+        it "charges FX33 according to the digits" {
+            let mut V = [0; 16];
+
+            V[2] = 100;
+            let low_cost = vip_cycles(&Instruction::StoreBcd(2), &V);
+
+            V[2] = 199;
+            let high_cost = vip_cycles(&Instruction::StoreBcd(2), &V);
+
+            assert!(high_cost > low_cost);
+        }
+
+        it "fits a full sprite drawing in a frame" {
+            assert!(vip_cycles(&Instruction::Draw(0, 1, 0), &[7; 16]) < VIP_FRAME_CYCLES);
+        }
+
+        it "parses the timing names" {
+            for name in Timing::NAMES.iter() {
+                assert_eq!(name.parse::<Timing>().unwrap().to_string(), *name);
+            }
+        }
+
+        context "machine" {
+            it "executes the instructions fitting the VIP frame cycles" {
+                // (0x200) V0 += 1; jump 0x200
+                //
+                let program = [0x7001, 0x1200];
+
+                let mut fixed_chip8 = new_program_chip8(&program, xo_chip());
+                let fixed_instructions = frame_instructions(&mut fixed_chip8);
+
+                let mut vip_chip8 = new_program_chip8(&program, xo_chip());
+                vip_chip8.set_timing(Timing::CosmacVip);
+                let vip_instructions = frame_instructions(&mut vip_chip8);
+
+                // The instruction crossing the frame boundary is executed in the frame.
+                //
+                let mut expected_instructions = 0;
+                let mut cycles = 0;
+
+                while cycles < VIP_FRAME_CYCLES {
+                    cycles += [10, 23][expected_instructions as usize % 2];
+                    expected_instructions += 1;
+                }
+
+                assert_eq!(vip_instructions, expected_instructions);
+                assert_ne!(vip_instructions, fixed_instructions);
+            }
+
+            it "charges the idle cost while waiting for a key" {
+                // V0 := key
+                //
+                let mut chip8 = new_program_chip8(&[0xF00A], xo_chip());
+                chip8.set_timing(Timing::CosmacVip);
+
+                let expected_steps = VIP_FRAME_CYCLES.div_ceil(VIP_IDLE_CYCLES);
+
+                assert_eq!(frame_steps(&mut chip8), expected_steps);
+                assert!(chip8.is_waiting_for_key());
+            }
+
+            it "prevents a cycle from spanning more than a frame" {
+                // (0x200) native 0x300; (0x202) jump 0x202
+                //
+                let mut rom = vec![0x03, 0x00, 0x12, 0x02];
+                rom.resize(0x100, 0);
+
+                // (0x300) LDI 0x08; PHI RD; (0x303) DEC RD; GHI RD; BNZ 0x03; SEP R4: about 10000
+                // machine cycles, i.e. several frames.
+                //
+                rom.extend_from_slice(&[0xF8, 0x08, 0xBD, 0x2D, 0x9D, 0x3A, 0x03, 0xD4]);
+
+                let mut chip8 = new_chip8(&rom, xo_chip());
+                chip8.set_timing(Timing::CosmacVip);
+                chip8.set_hybrid_mode(true);
+
+                run_steps(&mut chip8, 1);
+
+                assert_eq!(chip8.frame_count(), 1);
+                assert_eq!(chip8.timers_accumulator, VIP_FRAME_CYCLES - 1);
+
+                assert_eq!(frame_steps(&mut chip8), 1);
+            }
+        }
+    }
+}
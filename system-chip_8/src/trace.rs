@@ -0,0 +1,415 @@
+// Execution tracing.
+//
+// When a tracer is set (see `Chip8::set_tracer()`), an event is emitted for each executed
+// instruction, describing its effects; without a tracer, nothing is collected, so the tracing has
+// no cost.
+//
+// The register changes don't include the PC (the PC of the next event is its new value), and the
+// timers decrements, which are not caused by the instructions. The memory writes of the 0NNN
+// machine code routines are not traced; the registers changed by them are. A DXYN waiting for the
+// vblank (display wait quirk) is traced only when it's executed.
+//
+// Sinks:
+//
+// - `LogTracer`: human-readable messages (disassembly and effects), sent to a `Logger`;
+// - `LineTraceWriter`: one line per instruction, in a minimal format intended to be diffed against
+//   the traces of other emulators:
+//
+//     PPPP OOOO[ REG=VALUE...][ [AAAA]=VV...]
+//
+//   PC, opcode, changed registers (new values), and memory writes; all uppercase hexadecimal,
+//   zero-padded to 4 digits for I, PC and the addresses, and to 2 digits otherwise;
+// - `BinaryTraceWriter`: compact binary format, read back by `read_binary_trace()`:
+//
+//     magic: 4 bytes; "C8TR"
+//     version: u8
+//     events: pc (u16), opcode (u16), changes count (u8), changes: register (u8; 0x0-0xF for
+//             V0-VF, then I, SP, DT, ST), previous value (u16), value (u16); writes count (u16),
+//             writes: address (u16), value (u8)
+//
+//   All the integers are little endian.
+//
+// Writing failures are not emulation errors, so the trait is infallible; the writers stop at the
+// first error, which can be inspected via `error()`.
+
+use crate::{Byte, Chip8, Instruction, Register, Registers, Word};
+use interfaces_frontend::logging::Logger;
+use interfaces_frontend::IoFrontend;
+
+use std::fmt;
+use std::io::{self, Write};
+
+const MAGIC: &[u8; 4] = b"C8TR";
+const VERSION: u8 = 1;
+
+// Registers that can be changed by the instructions, in the encoding order.
+//
+const TRACED_REGISTERS: [Register; 20] = [
+    Register::V(0x0),
+    Register::V(0x1),
+    Register::V(0x2),
+    Register::V(0x3),
+    Register::V(0x4),
+    Register::V(0x5),
+    Register::V(0x6),
+    Register::V(0x7),
+    Register::V(0x8),
+    Register::V(0x9),
+    Register::V(0xA),
+    Register::V(0xB),
+    Register::V(0xC),
+    Register::V(0xD),
+    Register::V(0xE),
+    Register::V(0xF),
+    Register::I,
+    Register::SP,
+    Register::DelayTimer,
+    Register::SoundTimer,
+];
+
+pub trait Tracer: Send {
+    fn trace(&mut self, event: &TraceEvent);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEvent {
+    pub pc: usize,
+    pub opcode: Word,
+    pub instruction: Instruction,
+    pub register_changes: Vec<RegisterChange>,
+    pub memory_writes: Vec<MemoryWrite>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RegisterChange {
+    pub register: Register,
+    pub previous_value: usize,
+    pub value: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryWrite {
+    pub address: usize,
+    pub value: Byte,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TraceError {
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    InvalidRegister(u8),
+    InvalidOpcode(Word),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraceError::InvalidMagic => write!(f, "Not a trace (invalid magic)"),
+            TraceError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported trace version: {}", version)
+            }
+            TraceError::Truncated => write!(f, "Truncated trace"),
+            TraceError::InvalidRegister(code) => write!(f, "Invalid trace register: {}", code),
+            TraceError::InvalidOpcode(opcode) => write!(f, "Invalid trace opcode: {:04X}", opcode),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+// SINKS ///////////////////////////////////////////////////////////////////////////////////////////
+
+/// Logs each event as `[PC] DISASSEMBLY`, followed by the effects, if any.
+///
+pub struct LogTracer<L: Logger + Send> {
+    logger: L,
+}
+
+impl<L: Logger + Send> LogTracer<L> {
+    pub fn new(logger: L) -> LogTracer<L> {
+        LogTracer { logger }
+    }
+}
+
+impl<L: Logger + Send> Tracer for LogTracer<L> {
+    fn trace(&mut self, event: &TraceEvent) {
+        let mut message = format!("[{:X}] {}", event.pc, event.instruction);
+
+        let effects = event
+            .register_changes
+            .iter()
+            .map(|change| {
+                format!(
+                    "{}: {:X} -> {:X}",
+                    change.register, change.previous_value, change.value
+                )
+            })
+            .chain(
+                event
+                    .memory_writes
+                    .iter()
+                    .map(|write| format!("[{:X}] <- {:X}", write.address, write.value)),
+            )
+            .collect::<Vec<_>>();
+
+        if !effects.is_empty() {
+            message += &format!(" ; {}", effects.join(", "));
+        }
+
+        self.logger.log(message);
+    }
+}
+
+/// Writes the diff-friendly line format; see the module comment.
+///
+pub struct LineTraceWriter<W: Write + Send> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write + Send> LineTraceWriter<W> {
+    pub fn new(writer: W) -> LineTraceWriter<W> {
+        LineTraceWriter {
+            writer,
+            error: None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+}
+
+impl<W: Write + Send> Tracer for LineTraceWriter<W> {
+    fn trace(&mut self, event: &TraceEvent) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let mut line = format!("{:04X} {:04X}", event.pc, event.opcode);
+
+        for change in &event.register_changes {
+            let width = match change.register {
+                Register::I | Register::PC => 4,
+                _ => 2,
+            };
+
+            line += &format!(" {}={:02$X}", change.register, change.value, width);
+        }
+
+        for write in &event.memory_writes {
+            line += &format!(" [{:04X}]={:02X}", write.address, write.value);
+        }
+
+        if let Err(error) = writeln!(self.writer, "{}", line) {
+            self.error = Some(error);
+        }
+    }
+}
+
+/// Writes the binary format; see the module comment.
+///
+pub struct BinaryTraceWriter<W: Write + Send> {
+    writer: W,
+    header_written: bool,
+    error: Option<io::Error>,
+}
+
+impl<W: Write + Send> BinaryTraceWriter<W> {
+    pub fn new(writer: W) -> BinaryTraceWriter<W> {
+        BinaryTraceWriter {
+            writer,
+            header_written: false,
+            error: None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+}
+
+impl<W: Write + Send> Tracer for BinaryTraceWriter<W> {
+    fn trace(&mut self, event: &TraceEvent) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let mut data = vec![];
+
+        if !self.header_written {
+            data.extend_from_slice(MAGIC);
+            data.push(VERSION);
+            self.header_written = true;
+        }
+
+        data.extend_from_slice(&(event.pc as u16).to_le_bytes());
+        data.extend_from_slice(&event.opcode.to_le_bytes());
+
+        data.push(event.register_changes.len() as u8);
+
+        for change in &event.register_changes {
+            data.push(encode_register(change.register));
+            data.extend_from_slice(&(change.previous_value as u16).to_le_bytes());
+            data.extend_from_slice(&(change.value as u16).to_le_bytes());
+        }
+
+        data.extend_from_slice(&(event.memory_writes.len() as u16).to_le_bytes());
+
+        for write in &event.memory_writes {
+            data.extend_from_slice(&(write.address as u16).to_le_bytes());
+            data.push(write.value);
+        }
+
+        if let Err(error) = self.writer.write_all(&data) {
+            self.error = Some(error);
+        }
+    }
+}
+
+/// Reads a trace written by `BinaryTraceWriter`; an empty trace (no events) is valid.
+///
+pub fn read_binary_trace(data: &[u8]) -> Result<Vec<TraceEvent>, TraceError> {
+    let mut events = vec![];
+
+    if data.is_empty() {
+        return Ok(events);
+    }
+
+    let mut reader = Reader { data, position: 0 };
+
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(TraceError::InvalidMagic);
+    }
+
+    let version = reader.u8()?;
+
+    if version != VERSION {
+        return Err(TraceError::UnsupportedVersion(version));
+    }
+
+    while reader.position < data.len() {
+        let pc = reader.u16()? as usize;
+        let opcode = reader.u16()?;
+        let instruction =
+            Instruction::decode(opcode).map_err(|_| TraceError::InvalidOpcode(opcode))?;
+
+        let register_changes = (0..reader.u8()?)
+            .map(|_| {
+                let code = reader.u8()?;
+                let register = *TRACED_REGISTERS
+                    .get(code as usize)
+                    .ok_or(TraceError::InvalidRegister(code))?;
+
+                Ok(RegisterChange {
+                    register,
+                    previous_value: reader.u16()? as usize,
+                    value: reader.u16()? as usize,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let memory_writes = (0..reader.u16()?)
+            .map(|_| {
+                Ok(MemoryWrite {
+                    address: reader.u16()? as usize,
+                    value: reader.u8()?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        events.push(TraceEvent {
+            pc,
+            opcode,
+            instruction,
+            register_changes,
+            memory_writes,
+        });
+    }
+
+    Ok(events)
+}
+
+fn encode_register(register: Register) -> u8 {
+    TRACED_REGISTERS
+        .iter()
+        .position(|traced_register| *traced_register == register)
+        .unwrap() as u8
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], TraceError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + length)
+            .ok_or(TraceError::Truncated)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, TraceError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, TraceError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+// MACHINE /////////////////////////////////////////////////////////////////////////////////////////
+
+impl<T: IoFrontend> Chip8<T> {
+    /// Sets the tracer, which receives an event for each executed instruction.
+    ///
+    pub fn set_tracer(&mut self, tracer: Box<dyn Tracer>) {
+        self.tracer = Some(tracer);
+    }
+
+    pub fn remove_tracer(&mut self) {
+        self.tracer = None;
+        self.traced_memory_writes.clear();
+    }
+
+    pub(crate) fn is_tracing(&self) -> bool {
+        self.tracer.is_some()
+    }
+
+    pub(crate) fn trace_instruction(
+        &mut self,
+        pc: usize,
+        opcode: Word,
+        instruction: Instruction,
+        previous_registers: &Registers,
+    ) {
+        let registers = self.registers();
+
+        let register_changes = TRACED_REGISTERS
+            .iter()
+            .filter(|register| registers.get(**register) != previous_registers.get(**register))
+            .map(|register| RegisterChange {
+                register: *register,
+                previous_value: previous_registers.get(*register),
+                value: registers.get(*register),
+            })
+            .collect();
+
+        let event = TraceEvent {
+            pc,
+            opcode,
+            instruction,
+            register_changes,
+            memory_writes: std::mem::take(&mut self.traced_memory_writes),
+        };
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.trace(&event);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::test_harness::{new_chip8, run_steps, xo_chip};
+use crate::{
+    read_binary_trace, BinaryTraceWriter, Instruction, LineTraceWriter, LogTracer, MemoryWrite,
+    Quirks, QuirksProfile, Register, RegisterChange, TraceError, TraceEvent, Tracer,
+};
+use interfaces_frontend::logging::Logger;
+
+use demonstrate::demonstrate;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+// V0 := 0xFE; I := 0x300; bcd V0; V0 += 2
+//
+const ROM: [u8; 8] = [0x60, 0xFE, 0xA3, 0x00, 0xF0, 0x33, 0x70, 0x02];
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(data)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+struct RecordingTracer(Arc<Mutex<Vec<TraceEvent>>>);
+
+impl Tracer for RecordingTracer {
+    fn trace(&mut self, event: &TraceEvent) {
+        self.0.lock().unwrap().push(event.clone());
+    }
+}
+
+#[derive(Clone, Default)]
+struct RecordingLogger(Arc<Mutex<Vec<String>>>);
+
+impl Logger for RecordingLogger {
+    fn log(&mut self, message: String) {
+        self.0.lock().unwrap().push(message);
+    }
+}
+
+fn run_traced(rom: &[u8], quirks: Quirks, steps: usize, tracer: Box<dyn Tracer>) {
+    let mut chip8 = new_chip8(rom, quirks);
+    chip8.set_tracer(tracer);
+
+    run_steps(&mut chip8, steps);
+}
+
+demonstrate! {
+    describe "tracing" {
+        use super::*;
+
+        it "emits the instruction effects" {
+            let tracer = RecordingTracer::default();
+            run_traced(&ROM, xo_chip(), 4, Box::new(tracer.clone()));
+
+            let events = tracer.0.lock().unwrap();
+
+            assert_eq!(events.len(), 4);
+
+            assert_eq!(
+                events[0],
+                TraceEvent {
+                    pc: 0x200,
+                    opcode: 0x60FE,
+                    instruction: Instruction::SetByte(0, 0xFE),
+                    register_changes: vec![RegisterChange { register: Register::V(0), previous_value: 0, value: 0xFE }],
+                    memory_writes: vec![],
+                }
+            );
+
+            assert_eq!((events[2].pc, events[2].register_changes.len()), (0x204, 0));
+            assert_eq!(
+                events[2].memory_writes,
+                vec![
+                    MemoryWrite { address: 0x300, value: 2 },
+                    MemoryWrite { address: 0x301, value: 5 },
+                    MemoryWrite { address: 0x302, value: 4 },
+                ]
+            );
+
+            // 7XNN doesn't affect VF.
+            //
+            assert_eq!(
+                events[3].register_changes,
+                vec![RegisterChange { register: Register::V(0), previous_value: 0xFE, value: 0x00 }]
+            );
+        }
+
+        it "skips the DXYN waiting for the vblank" {
+            // sprite V0 V0 1; jump 0x202
+            //
+            let rom = [0xD0, 0x01, 0x12, 0x02];
+            let tracer = RecordingTracer::default();
+
+            // A frame is about 8 steps, so the sprite is drawn once, after the first vblank.
+            //
+            run_traced(&rom, Quirks::profile(QuirksProfile::CosmacVip), 20, Box::new(tracer.clone()));
+
+            let events = tracer.0.lock().unwrap();
+
+            assert_eq!(events[0].instruction, Instruction::Draw(0, 0, 1));
+            assert!(events[1..].iter().all(|event| event.instruction == Instruction::Jump(0x202)));
+        }
+
+        it "logs the events" {
+            let logger = RecordingLogger::default();
+            run_traced(&ROM, xo_chip(), 3, Box::new(LogTracer::new(logger.clone())));
+
+            let messages = logger.0.lock().unwrap();
+
+            assert_eq!(messages[0], "[200] LD V0, #FE ; V0: 0 -> FE");
+            assert_eq!(messages[2], "[204] LD B, V0 ; [300] <- 2, [301] <- 5, [302] <- 4");
+        }
+
+        it "writes the line format" {
+            let buffer = SharedBuffer::default();
+            run_traced(&ROM, xo_chip(), 4, Box::new(LineTraceWriter::new(buffer.clone())));
+
+            let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+
+            assert_eq!(
+                output,
+                "0200 60FE V0=FE\n\
+                 0202 A300 I=0300\n\
+                 0204 F033 [0300]=02 [0301]=05 [0302]=04\n\
+                 0206 7002 V0=00\n"
+            );
+        }
+
+        it "writes and reads the binary format" {
+            let buffer = SharedBuffer::default();
+            let tracer = RecordingTracer::default();
+
+            run_traced(&ROM, xo_chip(), 4, Box::new(BinaryTraceWriter::new(buffer.clone())));
+            run_traced(&ROM, xo_chip(), 4, Box::new(tracer.clone()));
+
+            let data = buffer.0.lock().unwrap().clone();
+
+            assert_eq!(read_binary_trace(&data).unwrap(), *tracer.0.lock().unwrap());
+            assert_eq!(read_binary_trace(&[]), Ok(vec![]));
+            assert_eq!(read_binary_trace(&data[..data.len() - 1]), Err(TraceError::Truncated));
+            assert_eq!(read_binary_trace(b"C8TS\x01"), Err(TraceError::InvalidMagic));
+        }
+    }
+}
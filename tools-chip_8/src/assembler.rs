@@ -0,0 +1,743 @@
+// Assembler, for a subset of the Octo language (see https://github.com/JohnEarnest/Octo/blob/gh-pages/docs/Manual.md).
+//
+// Supported:
+//
+// - labels (`: name`), `:alias`, `:const`, `:byte`, `:call`, `:org`;
+// - all the CHIP-8/SCHIP/XO-CHIP statements;
+// - `if ... then`, `if ... begin ... else ... end`, `loop ... while ... again`;
+// - data, as bare numbers.
+//
+// Not supported, and rejected with an explicit error: the other directives (`:macro`, `:calc`,
+// `:unpack`, `:next`, `:pointer`, `:stringmode`, `:assert`, and the debugger ones), the comparison
+// pseudo-ops (`<`, `>`, `<=`, `>=`) and the string literals. Octo programs using them must be
+// exported as ROMs.
+//
+// Like Octo, the program starts with a jump to `main`, which is removed if `main` immediately
+// follows it.
+
+use std::collections::HashMap;
+use std::fmt;
+
+const PROGRAMS_LOCATION: usize = system_chip_8::PROGRAMS_LOCATION;
+const MAIN_LABEL: &str = "main";
+
+const UNSUPPORTED_DIRECTIVES: [&str; 9] = [
+    ":macro",
+    ":calc",
+    ":unpack",
+    ":next",
+    ":pointer",
+    ":stringmode",
+    ":assert",
+    ":breakpoint",
+    ":monitor",
+];
+
+#[derive(Debug, PartialEq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FixupKind {
+    // Lower 12 bits of the instruction.
+    //
+    Address,
+    // Whole word (`i := long`).
+    //
+    LongAddress,
+}
+
+struct Fixup<'a> {
+    offset: usize,
+    kind: FixupKind,
+    token: Token<'a>,
+}
+
+// Jumps whose target is not known at emission time (control structures).
+//
+struct Loop<'a> {
+    address: usize,
+    break_offsets: Vec<usize>,
+    token: Token<'a>,
+}
+
+struct Conditional<'a> {
+    jump_offset: usize,
+    token: Token<'a>,
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+
+    // Starts at PROGRAMS_LOCATION.
+    //
+    output: Vec<u8>,
+
+    labels: HashMap<&'a str, usize>,
+    constants: HashMap<&'a str, i64>,
+    aliases: HashMap<&'a str, usize>,
+
+    fixups: Vec<Fixup<'a>>,
+    loops: Vec<Loop<'a>>,
+    conditionals: Vec<Conditional<'a>>,
+}
+
+/// Assembles the source into a binary, to be loaded at the standard programs location.
+///
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    let mut assembler = Assembler {
+        tokens: tokenize(source),
+        position: 0,
+        output: vec![],
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: vec![],
+        loops: vec![],
+        conditionals: vec![],
+    };
+
+    assembler.assemble()?;
+
+    Ok(assembler.output)
+}
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap();
+        let mut token_start = None;
+
+        for (char_index, char) in line
+            .char_indices()
+            .chain(std::iter::once((line.len(), ' ')))
+        {
+            match (char.is_whitespace(), token_start) {
+                (false, None) => token_start = Some(char_index),
+                (true, Some(start)) => {
+                    tokens.push(Token {
+                        text: &line[start..char_index],
+                        line: line_index + 1,
+                        column: line[..start].chars().count() + 1,
+                    });
+                    token_start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    tokens
+}
+
+impl<'a> Assembler<'a> {
+    fn assemble(&mut self) -> Result<(), AssembleError> {
+        let start_token = Token {
+            text: MAIN_LABEL,
+            line: 1,
+            column: 1,
+        };
+
+        self.emit_address_fixup(0x1000, start_token, FixupKind::Address);
+
+        while let Some(token) = self.next_token() {
+            self.statement(token)?;
+        }
+
+        if let Some(loop_) = self.loops.first() {
+            return Err(error(loop_.token, "`loop` without `again`"));
+        }
+
+        if let Some(conditional) = self.conditionals.first() {
+            return Err(error(conditional.token, "`begin` without `end`"));
+        }
+
+        for fixup in &self.fixups {
+            let address = match self.labels.get(fixup.token.text) {
+                Some(address) => *address,
+                None => {
+                    let message = format!("Undefined label: {}", fixup.token.text);
+                    return Err(error(fixup.token, &message));
+                }
+            };
+
+            match fixup.kind {
+                FixupKind::Address => {
+                    if address > 0xFFF {
+                        return Err(error(fixup.token, "Address out of range (use `i := long`)"));
+                    }
+
+                    self.output[fixup.offset] |= (address >> 8) as u8;
+                    self.output[fixup.offset + 1] = address as u8;
+                }
+                FixupKind::LongAddress => {
+                    self.output[fixup.offset] = (address >> 8) as u8;
+                    self.output[fixup.offset + 1] = address as u8;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn statement(&mut self, token: Token<'a>) -> Result<(), AssembleError> {
+        match token.text {
+            ":" => {
+                let name = self.expect_token()?;
+                self.define_label(name)?;
+            }
+            ":alias" => {
+                let name = self.expect_token()?;
+                let register = self.register()?;
+                self.aliases.insert(name.text, register);
+            }
+            ":const" => {
+                let name = self.expect_token()?;
+                let value = self.value()?;
+                self.constants.insert(name.text, value);
+            }
+            ":byte" => {
+                let value = self.byte()?;
+                self.output.push(value);
+            }
+            ":call" => self.address_instruction(0x2000)?,
+            ":org" => {
+                let value_token = self.expect_token()?;
+                let address = self.resolve_value(value_token)?;
+
+                if !(0..=0xFFFF).contains(&address) {
+                    return Err(error(value_token, "Address out of range"));
+                }
+
+                let address = address as usize;
+
+                if address < self.current_address() {
+                    return Err(error(value_token, "`:org` can't move backwards"));
+                }
+
+                self.output.resize(address - PROGRAMS_LOCATION, 0);
+            }
+            ";" | "return" => self.emit(0x00EE),
+            "clear" => self.emit(0x00E0),
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-left" => self.emit(0x00FC),
+            "exit" => self.emit(0x00FD),
+            "lores" => self.emit(0x00FE),
+            "hires" => self.emit(0x00FF),
+            "audio" => self.emit(0xF002),
+            "scroll-down" => {
+                let lines = self.nibble()?;
+                self.emit(0x00C0 | lines);
+            }
+            "scroll-up" => {
+                let lines = self.nibble()?;
+                self.emit(0x00D0 | lines);
+            }
+            "plane" => {
+                let planes = self.nibble()?;
+                self.emit(0xF001 | planes << 8);
+            }
+            "bcd" => self.register_instruction(0xF033)?,
+            "saveflags" => self.register_instruction(0xF075)?,
+            "loadflags" => self.register_instruction(0xF085)?,
+            "save" | "load" => {
+                let Vx = self.register()? as u16;
+
+                if self.peek_token().map(|next| next.text) == Some("-") {
+                    self.position += 1;
+                    let Vy = self.register()? as u16;
+                    let opcode = if token.text == "save" { 0x5002 } else { 0x5003 };
+                    self.emit(opcode | Vx << 8 | Vy << 4);
+                } else {
+                    let opcode = if token.text == "save" { 0xF055 } else { 0xF065 };
+                    self.emit(opcode | Vx << 8);
+                }
+            }
+            "sprite" => {
+                let Vx = self.register()? as u16;
+                let Vy = self.register()? as u16;
+                let lines = self.nibble()?;
+                self.emit(0xD000 | Vx << 8 | Vy << 4 | lines);
+            }
+            "jump" => self.address_instruction(0x1000)?,
+            "jump0" => self.address_instruction(0xB000)?,
+            "i" => self.index_statement()?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect_text(":=")?;
+                let opcode = match token.text {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A,
+                };
+                self.register_instruction(opcode)?;
+            }
+            "loop" => self.loops.push(Loop {
+                address: self.current_address(),
+                break_offsets: vec![],
+                token,
+            }),
+            "while" => {
+                if self.loops.is_empty() {
+                    return Err(error(token, "`while` outside a loop"));
+                }
+
+                let (_, skip_if_true) = self.condition()?;
+                self.emit(skip_if_true);
+
+                let break_offset = self.output.len();
+                self.emit(0x1000);
+                self.loops
+                    .last_mut()
+                    .unwrap()
+                    .break_offsets
+                    .push(break_offset);
+            }
+            "again" => {
+                let loop_ = self
+                    .loops
+                    .pop()
+                    .ok_or_else(|| error(token, "`again` without `loop`"))?;
+
+                let again_offset = self.output.len();
+                self.emit(0x1000);
+                self.patch_jump(again_offset, loop_.address, token)?;
+
+                let end_address = self.current_address();
+
+                for break_offset in loop_.break_offsets {
+                    self.patch_jump(break_offset, end_address, token)?;
+                }
+            }
+            "if" => {
+                let (skip_if_false, skip_if_true) = self.condition()?;
+                let keyword = self.expect_token()?;
+
+                match keyword.text {
+                    "then" => self.emit(skip_if_false),
+                    "begin" => {
+                        self.emit(skip_if_true);
+                        self.conditionals.push(Conditional {
+                            jump_offset: self.output.len(),
+                            token,
+                        });
+                        self.emit(0x1000);
+                    }
+                    _ => return Err(error(keyword, "Expected `then` or `begin`")),
+                }
+            }
+            "else" => {
+                let conditional = self
+                    .conditionals
+                    .pop()
+                    .ok_or_else(|| error(token, "`else` without `begin`"))?;
+
+                let jump_offset = self.output.len();
+                self.emit(0x1000);
+
+                self.patch_jump(conditional.jump_offset, self.current_address(), token)?;
+                self.conditionals.push(Conditional { jump_offset, token });
+            }
+            "end" => {
+                let conditional = self
+                    .conditionals
+                    .pop()
+                    .ok_or_else(|| error(token, "`end` without `begin`"))?;
+
+                self.patch_jump(conditional.jump_offset, self.current_address(), token)?;
+            }
+            directive if UNSUPPORTED_DIRECTIVES.contains(&directive) => {
+                let message = format!("Unsupported directive: {}", directive);
+                return Err(error(token, &message));
+            }
+            text if text.starts_with('"') => {
+                return Err(error(token, "Unsupported string literal"));
+            }
+            _ => {
+                if let Some(Vx) = self.parse_register(token) {
+                    self.register_statement(Vx)?;
+                } else if self.parse_number(token).is_some() {
+                    let value = self.to_byte(token)?;
+                    self.output.push(value);
+                } else if is_identifier(token.text) {
+                    self.emit_address_fixup(0x2000, token, FixupKind::Address);
+                } else {
+                    return Err(error(token, &format!("Unexpected token: {}", token.text)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // `i := ...`/`i += ...`
+    //
+    fn index_statement(&mut self) -> Result<(), AssembleError> {
+        let operator = self.expect_token()?;
+
+        match operator.text {
+            ":=" => {
+                let operand = self.expect_token()?;
+
+                match operand.text {
+                    "hex" => self.register_instruction(0xF029),
+                    "bighex" => self.register_instruction(0xF030),
+                    "long" => {
+                        self.emit(0xF000);
+
+                        let address_token = self.expect_token()?;
+
+                        match self.parse_value(address_token) {
+                            Some(address) if (0..=0xFFFF).contains(&address) => {
+                                self.emit(address as u16);
+                                Ok(())
+                            }
+                            Some(_) => Err(error(address_token, "Address out of range")),
+                            None => {
+                                self.check_identifier(address_token)?;
+                                self.emit_address_fixup(0, address_token, FixupKind::LongAddress);
+                                Ok(())
+                            }
+                        }
+                    }
+                    _ => {
+                        self.position -= 1;
+                        self.address_instruction(0xA000)
+                    }
+                }
+            }
+            "+=" => self.register_instruction(0xF01E),
+            _ => Err(error(operator, "Expected `:=` or `+=`")),
+        }
+    }
+
+    // `vx <operator> <operand>`
+    //
+    fn register_statement(&mut self, Vx: usize) -> Result<(), AssembleError> {
+        let Vx = Vx as u16;
+        let operator = self.expect_token()?;
+        let operand = self.expect_token()?;
+
+        if let Some(Vy) = self.parse_register(operand) {
+            let Vy = Vy as u16;
+
+            let nibble3 = match operator.text {
+                ":=" => 0x0,
+                "|=" => 0x1,
+                "&=" => 0x2,
+                "^=" => 0x3,
+                "+=" => 0x4,
+                "-=" => 0x5,
+                ">>=" => 0x6,
+                "=-" => 0x7,
+                "<<=" => 0xE,
+                _ => return Err(error(operator, "Invalid operator")),
+            };
+
+            self.emit(0x8000 | Vx << 8 | Vy << 4 | nibble3);
+
+            return Ok(());
+        }
+
+        match (operator.text, operand.text) {
+            (":=", "delay") => self.emit(0xF007 | Vx << 8),
+            (":=", "key") => self.emit(0xF00A | Vx << 8),
+            (":=", "random") => {
+                let mask = self.byte()? as u16;
+                self.emit(0xC000 | Vx << 8 | mask);
+            }
+            (":=", _) => {
+                let value = self.to_byte(operand)? as u16;
+                self.emit(0x6000 | Vx << 8 | value);
+            }
+            ("+=", _) => {
+                let value = self.to_byte(operand)? as u16;
+                self.emit(0x7000 | Vx << 8 | value);
+            }
+            ("-=", _) => {
+                let value = self.to_byte(operand)?.wrapping_neg() as u16;
+                self.emit(0x7000 | Vx << 8 | value);
+            }
+            _ => return Err(error(operator, "Invalid operator")),
+        }
+
+        Ok(())
+    }
+
+    // Returns the skip opcodes (skip if the condition is false, skip if it's true).
+    //
+    fn condition(&mut self) -> Result<(u16, u16), AssembleError> {
+        let Vx = self.register()? as u16;
+        let operator = self.expect_token()?;
+
+        let (skip_if_false, skip_if_true) = match operator.text {
+            "key" => (0xE0A1 | Vx << 8, 0xE09E | Vx << 8),
+            "-key" => (0xE09E | Vx << 8, 0xE0A1 | Vx << 8),
+            "==" | "!=" => {
+                let operand = self.expect_token()?;
+
+                let (skip_if_not_equal, skip_if_equal) = match self.parse_register(operand) {
+                    Some(Vy) => (
+                        0x9000 | Vx << 8 | (Vy as u16) << 4,
+                        0x5000 | Vx << 8 | (Vy as u16) << 4,
+                    ),
+                    None => {
+                        let value = self.to_byte(operand)? as u16;
+                        (0x4000 | Vx << 8 | value, 0x3000 | Vx << 8 | value)
+                    }
+                };
+
+                if operator.text == "==" {
+                    (skip_if_not_equal, skip_if_equal)
+                } else {
+                    (skip_if_equal, skip_if_not_equal)
+                }
+            }
+            "<" | ">" | "<=" | ">=" => {
+                let message = format!("Unsupported comparison: {}", operator.text);
+                return Err(error(operator, &message));
+            }
+            _ => return Err(error(operator, "Invalid condition")),
+        };
+
+        Ok((skip_if_false, skip_if_true))
+    }
+
+    fn define_label(&mut self, name: Token<'a>) -> Result<(), AssembleError> {
+        self.check_identifier(name)?;
+
+        if self.labels.contains_key(name.text) {
+            return Err(error(name, &format!("Label redefined: {}", name.text)));
+        }
+
+        // Remove the initial jump, if main immediately follows it.
+        //
+        if name.text == MAIN_LABEL
+            && self.output.len() == 2
+            && self.fixups.len() == 1
+            && self.labels.is_empty()
+        {
+            self.output.clear();
+            self.fixups.clear();
+        }
+
+        self.labels.insert(name.text, self.current_address());
+
+        Ok(())
+    }
+
+    // EMISSION HELPERS ////////////////////////////////////////////////////////////////////////////
+
+    fn current_address(&self) -> usize {
+        PROGRAMS_LOCATION + self.output.len()
+    }
+
+    fn emit(&mut self, word: u16) {
+        self.output.extend_from_slice(&word.to_be_bytes());
+    }
+
+    fn emit_address_fixup(&mut self, opcode: u16, token: Token<'a>, kind: FixupKind) {
+        self.fixups.push(Fixup {
+            offset: self.output.len(),
+            kind,
+            token,
+        });
+        self.emit(opcode);
+    }
+
+    // Like `jump`, the target must be in the 12-bit range; the error is reported at the token
+    // closing the control structure.
+    //
+    fn patch_jump(
+        &mut self,
+        offset: usize,
+        address: usize,
+        token: Token<'a>,
+    ) -> Result<(), AssembleError> {
+        if address > 0xFFF {
+            return Err(error(token, "Address out of range"));
+        }
+
+        let opcode = 0x1000 | address as u16;
+        self.output[offset..offset + 2].copy_from_slice(&opcode.to_be_bytes());
+
+        Ok(())
+    }
+
+    // Instruction with a 12-bit address operand; labels are resolved at the end.
+    //
+    fn address_instruction(&mut self, opcode: u16) -> Result<(), AssembleError> {
+        let token = self.expect_token()?;
+
+        match self.parse_value(token) {
+            Some(address) if (0..=0xFFF).contains(&address) => {
+                self.emit(opcode | address as u16);
+                Ok(())
+            }
+            Some(_) => Err(error(token, "Address out of range")),
+            None => {
+                self.check_identifier(token)?;
+                self.emit_address_fixup(opcode, token, FixupKind::Address);
+                Ok(())
+            }
+        }
+    }
+
+    fn register_instruction(&mut self, opcode: u16) -> Result<(), AssembleError> {
+        let Vx = self.register()? as u16;
+        self.emit(opcode | Vx << 8);
+        Ok(())
+    }
+
+    // PARSING HELPERS /////////////////////////////////////////////////////////////////////////////
+
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.get(self.position).copied();
+        self.position += 1;
+        token
+    }
+
+    fn peek_token(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn expect_token(&mut self) -> Result<Token<'a>, AssembleError> {
+        match self.next_token() {
+            Some(token) => Ok(token),
+            None => {
+                let last_token = self.tokens.last().copied().unwrap();
+                Err(error(last_token, "Unexpected end of source"))
+            }
+        }
+    }
+
+    fn expect_text(&mut self, text: &str) -> Result<(), AssembleError> {
+        let token = self.expect_token()?;
+
+        if token.text == text {
+            Ok(())
+        } else {
+            Err(error(token, &format!("Expected `{}`", text)))
+        }
+    }
+
+    fn register(&mut self) -> Result<usize, AssembleError> {
+        let token = self.expect_token()?;
+
+        self.parse_register(token)
+            .ok_or_else(|| error(token, &format!("Expected register: {}", token.text)))
+    }
+
+    fn parse_register(&self, token: Token) -> Option<usize> {
+        if let Some(register) = self.aliases.get(token.text) {
+            return Some(*register);
+        }
+
+        let text = token.text.to_ascii_lowercase();
+
+        match text.strip_prefix('v') {
+            Some(index) if index.len() == 1 => usize::from_str_radix(index, 16).ok(),
+            _ => None,
+        }
+    }
+
+    fn value(&mut self) -> Result<i64, AssembleError> {
+        let token = self.expect_token()?;
+        self.resolve_value(token)
+    }
+
+    fn byte(&mut self) -> Result<u8, AssembleError> {
+        let token = self.expect_token()?;
+        self.to_byte(token)
+    }
+
+    fn nibble(&mut self) -> Result<u16, AssembleError> {
+        let token = self.expect_token()?;
+
+        match self.resolve_value(token)? {
+            value @ 0..=15 => Ok(value as u16),
+            _ => Err(error(token, "Value out of range (0-15)")),
+        }
+    }
+
+    fn to_byte(&self, token: Token) -> Result<u8, AssembleError> {
+        match self.resolve_value(token)? {
+            value @ -128..=255 => Ok(value as u8),
+            _ => Err(error(token, "Value out of range (-128-255)")),
+        }
+    }
+
+    fn resolve_value(&self, token: Token) -> Result<i64, AssembleError> {
+        self.parse_value(token)
+            .ok_or_else(|| error(token, &format!("Expected number: {}", token.text)))
+    }
+
+    // Numbers, constants, and the labels already defined.
+    //
+    fn parse_value(&self, token: Token) -> Option<i64> {
+        self.parse_number(token)
+            .or_else(|| self.constants.get(token.text).copied())
+            .or_else(|| self.labels.get(token.text).map(|address| *address as i64))
+    }
+
+    fn parse_number(&self, token: Token) -> Option<i64> {
+        let (negative, text) = match token.text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, token.text),
+        };
+
+        let value = if let Some(digits) = text.strip_prefix("0x") {
+            i64::from_str_radix(digits, 16).ok()?
+        } else if let Some(digits) = text.strip_prefix("0b") {
+            i64::from_str_radix(digits, 2).ok()?
+        } else {
+            text.parse::<i64>().ok()?
+        };
+
+        Some(if negative { -value } else { value })
+    }
+
+    fn check_identifier(&self, token: Token) -> Result<(), AssembleError> {
+        if is_identifier(token.text) && self.parse_register(token).is_none() {
+            Ok(())
+        } else {
+            Err(error(token, &format!("Invalid name: {}", token.text)))
+        }
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+
+    chars
+        .next()
+        .is_some_and(|first_char| first_char.is_ascii_alphabetic() || first_char == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_' || char == '-')
+}
+
+fn error(token: Token, message: &str) -> AssembleError {
+    AssembleError {
+        line: token.line,
+        column: token.column,
+        message: message.to_string(),
+    }
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for AssembleError {}
@@ -0,0 +1,124 @@
+use crate::assembler::{assemble, AssembleError};
+use crate::disassembler::{disassemble, Syntax};
+use demonstrate::demonstrate;
+
+demonstrate! {
+    describe "assembler" {
+        use super::*;
+
+        it "assembles the statements" {
+            let source = "
+                :alias counter v3
+                :const LIMIT 10
+
+                : main
+                  clear
+                  counter := 0
+                  i := sprite_data
+                  loop
+                    sprite v0 v1 4
+                    counter += 1
+                    if counter == LIMIT then exit
+                    v2 := key
+                    if v2 != v3 begin
+                      v0 <<= v1
+                    else
+                      i := long sprite_data
+                    end
+                  again
+
+                : sprite_data
+                  0xF0 0b1001 -1
+            ";
+
+            let expected_rom = [
+                0x00, 0xE0, // clear
+                0x63, 0x00, // counter := 0
+                0xA2, 0x1E, // i := sprite_data
+                0xD0, 0x14, // sprite
+                0x73, 0x01, // counter += 1
+                0x43, 0x0A, // if counter == LIMIT then
+                0x00, 0xFD, // exit
+                0xF2, 0x0A, // v2 := key
+                0x92, 0x30, // if v2 != v3 begin
+                0x12, 0x18, //   (jump to else)
+                0x80, 0x1E, // v0 <<= v1
+                0x12, 0x1C, // else (jump to end)
+                0xF0, 0x00, // i := long sprite_data
+                0x02, 0x1E,
+                0x12, 0x06, // again
+                0xF0, 0x09, 0xFF,
+            ];
+
+            assert_eq!(assemble(source), Ok(expected_rom.to_vec()));
+        }
+
+        it "jumps to main, when not at the program start" {
+            let source = ": data 0xAA : main jump main";
+
+            assert_eq!(assemble(source), Ok(vec![0x12, 0x03, 0xAA, 0x12, 0x03]));
+        }
+
+        it "reports the errors location" {
+            let source = ": main\n  v0 := 256\n";
+
+            assert_eq!(assemble(source), Err(AssembleError {
+                line: 2,
+                column: 9,
+                message: "Value out of range (-128-255)".to_string(),
+            }));
+
+            let source = ": main\n  jump nowhere";
+
+            assert_eq!(assemble(source).unwrap_err().to_string(), "2:8: Undefined label: nowhere");
+        }
+
+        it "rejects the addresses out of range" {
+            let sources = [
+                ":org -1",
+                ":org 0x100000000",
+                ": main :org 0x1000 loop again",
+                ": main loop while v0 == 0 :org 0xFFE again",
+                ": main if v0 == 0 begin :org 0x1000 end",
+            ];
+
+            for source in sources.iter() {
+                assert_eq!(assemble(source).unwrap_err().message, "Address out of range", "{}", source);
+            }
+        }
+
+        it "rejects the unsupported Octo constructs explicitly" {
+            let sources = [
+                (": main\n  :macro twice X { X X }", "2:3: Unsupported directive: :macro"),
+                (":calc half { 4 / 2 }", "1:1: Unsupported directive: :calc"),
+                (": main :unpack 0xA data", "1:8: Unsupported directive: :unpack"),
+                (": main\n  :stringmode text \"abc\" { v0 := CHAR }", "2:3: Unsupported directive: :stringmode"),
+                (": main text \"Hello\"", "1:13: Unsupported string literal"),
+                (": main if v0 < 5 then v1 := 1", "1:14: Unsupported comparison: <"),
+            ];
+
+            for (source, expected_error) in sources.iter() {
+                assert_eq!(assemble(source).unwrap_err().to_string(), *expected_error, "{}", source);
+            }
+        }
+
+        it "round-trips with the disassembler" {
+            let rom = [
+                0xA2, 0x0C, 0x22, 0x0A, 0x3A, 0x01, 0xF0, 0x00, 0x02, 0x0C, 0x00, 0xEE, 0xFF, 0x81,
+            ];
+
+            let source = disassemble(&rom, Syntax::Octo);
+
+            assert_eq!(assemble(&source), Ok(rom.to_vec()));
+        }
+
+        it "round-trips the machine code routine calls with the disassembler" {
+            let rom = [0x03, 0x00, 0x12, 0x02];
+
+            let source = disassemble(&rom, Syntax::Octo);
+
+            assert!(source.contains("0x03 0x00"));
+            assert_eq!(assemble(&source), Ok(rom.to_vec()));
+        }
+    }
+}
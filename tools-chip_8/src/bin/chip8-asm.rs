@@ -0,0 +1,32 @@
+use clap::{self, App, Arg};
+
+use tools_chip_8::assembler;
+
+use std::fs;
+
+fn exit_with_error(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+fn main() {
+    let matches = App::new("chip8-asm")
+        .about("Assembles an Octo-syntax source into a CHIP-8/SCHIP/XO-CHIP ROM")
+        .arg(Arg::with_name("SOURCE").required(true).index(1))
+        .arg(Arg::with_name("OUTPUT").required(true).index(2))
+        .get_matches();
+
+    let source_filename = matches.value_of("SOURCE").unwrap();
+    let output_filename = matches.value_of("OUTPUT").unwrap();
+
+    let source = fs::read_to_string(source_filename).unwrap_or_else(|error| {
+        exit_with_error(format!("Error reading {}: {}", source_filename, error))
+    });
+
+    let rom = assembler::assemble(&source)
+        .unwrap_or_else(|error| exit_with_error(format!("{}:{}", source_filename, error)));
+
+    fs::write(output_filename, rom).unwrap_or_else(|error| {
+        exit_with_error(format!("Error writing {}: {}", output_filename, error))
+    });
+}
@@ -0,0 +1,32 @@
+use clap::{self, App, Arg};
+
+use tools_chip_8::disassembler::{self, Syntax};
+
+use std::fs;
+
+fn main() {
+    let matches = App::new("chip8-disasm")
+        .about("Disassembles a CHIP-8/SCHIP/XO-CHIP ROM to stdout")
+        .arg(Arg::with_name("ROM").required(true).index(1))
+        .arg(
+            Arg::with_name("OCTO")
+                .short("o")
+                .long("octo")
+                .help("Use the Octo syntax (can be reassembled with chip8-asm)"),
+        )
+        .get_matches();
+
+    let rom_filename = matches.value_of("ROM").unwrap();
+    let syntax = if matches.is_present("OCTO") {
+        Syntax::Octo
+    } else {
+        Syntax::Cowgod
+    };
+
+    let rom = fs::read(rom_filename).unwrap_or_else(|error| {
+        eprintln!("Error reading {}: {}", rom_filename, error);
+        std::process::exit(1);
+    });
+
+    print!("{}", disassembler::disassemble(&rom, syntax));
+}
@@ -0,0 +1,228 @@
+// Loader for the Octo cartridges (see https://github.com/JohnEarnest/Octo/blob/gh-pages/docs/SharingGuide.md).
+//
+// A cartridge is a GIF, whose pixels carry, in the two lower bits of the palette indices, a payload
+// (4 pixels per byte, most significant bits first); the frames are concatenated. The payload is
+// a 32-bit big endian length, followed by a UTF-8 JSON object:
+//
+//   { "program": "<Octo source>", "options": { "tickrate": 20, "shiftQuirks": false, ... } }
+//
+// The program is assembled, so it must use the subset supported by the assembler (see
+// `assembler`); the other constructs are reported as `InvalidProgram`. Options not specified take
+// the Octo defaults; those not relevant to the emulation (buzzer colors, screen rotation, font
+// style...) are ignored.
+//
+// The key layout (`touchInputMode`) is out of scope: it selects the Octo on-screen touch controls,
+// which have no counterpart in the keyboard-driven frontends; the keys are mapped by the frontend.
+
+use crate::assembler::{assemble, AssembleError};
+use interfaces_frontend::video::Pixel;
+use system_chip_8::{IndexIncrement, Quirks};
+
+use serde_json::{Map, Value};
+use std::fmt;
+
+const DEFAULT_TICKRATE: u64 = 20; // Instructions per frame
+const FRAMES_PER_SECOND: u64 = 60;
+
+const DEFAULT_BACKGROUND_COLOR: &str = "#996600";
+const DEFAULT_FILL_COLOR: &str = "#FFCC00";
+const DEFAULT_FILL_COLOR_2: &str = "#FF6600";
+const DEFAULT_BLEND_COLOR: &str = "#662200";
+
+pub struct Cartridge {
+    pub rom: Vec<u8>,
+    pub config: CartridgeConfig,
+}
+
+/// Settings to run the cartridge ROM with; the quirks and the clock speed are intended for
+/// `Chip8::new()`, and the palette for `Chip8::set_palette()`.
+///
+pub struct CartridgeConfig {
+    pub quirks: Quirks,
+    pub clock_speed: u32,
+    pub palette: [Pixel; 4],
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CartridgeError {
+    InvalidImage(String),
+    TruncatedPayload { length: usize, available: usize },
+    InvalidPayload(String),
+    InvalidOption { name: String, message: String },
+    InvalidProgram(AssembleError),
+}
+
+pub fn load_cartridge(data: &[u8]) -> Result<Cartridge, CartridgeError> {
+    let payload = extract_payload(data)?;
+
+    let payload = serde_json::from_slice::<Value>(&payload)
+        .map_err(|error| CartridgeError::InvalidPayload(error.to_string()))?;
+
+    let source = payload
+        .get("program")
+        .and_then(Value::as_str)
+        .ok_or_else(|| CartridgeError::InvalidPayload("Missing program".to_string()))?;
+
+    let empty_options = Map::new();
+
+    let options = match payload.get("options") {
+        Some(Value::Object(options)) => options,
+        Some(_) => {
+            return Err(CartridgeError::InvalidPayload(
+                "The options are not an object".to_string(),
+            ))
+        }
+        None => &empty_options,
+    };
+
+    let config = decode_config(options)?;
+    let rom = assemble(source).map_err(CartridgeError::InvalidProgram)?;
+
+    Ok(Cartridge { rom, config })
+}
+
+fn extract_payload(data: &[u8]) -> Result<Vec<u8>, CartridgeError> {
+    let image_error = |error: gif::DecodingError| CartridgeError::InvalidImage(error.to_string());
+
+    let mut decode_options = gif::DecodeOptions::new();
+    decode_options.set_color_output(gif::ColorOutput::Indexed);
+
+    let mut decoder = decode_options.read_info(data).map_err(image_error)?;
+    let mut pixels = vec![];
+
+    while let Some(frame) = decoder.read_next_frame().map_err(image_error)? {
+        pixels.extend_from_slice(&frame.buffer);
+    }
+
+    let bytes = pixels
+        .chunks_exact(4)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .fold(0, |byte, pixel| (byte << 2) | (pixel & 0b11))
+        })
+        .collect::<Vec<u8>>();
+
+    if bytes.len() < 4 {
+        return Err(CartridgeError::TruncatedPayload {
+            length: 4,
+            available: bytes.len(),
+        });
+    }
+
+    let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+    let payload = &bytes[4..];
+
+    if payload.len() < length {
+        return Err(CartridgeError::TruncatedPayload {
+            length,
+            available: payload.len(),
+        });
+    }
+
+    Ok(payload[..length].to_vec())
+}
+
+// The Octo quirks are deviations from its own (XO-CHIP) behavior, which is the default.
+//
+fn decode_config(options: &Map<String, Value>) -> Result<CartridgeConfig, CartridgeError> {
+    let tickrate = match options.get("tickrate") {
+        None => DEFAULT_TICKRATE,
+        Some(value) => match value.as_u64() {
+            Some(tickrate) if tickrate > 0 => tickrate,
+            _ => return Err(invalid_option("tickrate", "must be a positive integer")),
+        },
+    };
+
+    let clock_speed = tickrate
+        .saturating_mul(FRAMES_PER_SECOND)
+        .min(u32::MAX as u64) as u32;
+
+    let quirks = Quirks {
+        shift_uses_Vy: !bool_option(options, "shiftQuirks")?,
+        load_store: if bool_option(options, "loadStoreQuirks")? {
+            IndexIncrement::None
+        } else {
+            IndexIncrement::XPlusOne
+        },
+        jump_uses_Vx: bool_option(options, "jumpQuirks")?,
+        clip_sprites: bool_option(options, "clipQuirks")?,
+        logic_resets_VF: bool_option(options, "logicQuirks")?,
+        display_wait: bool_option(options, "vBlankQuirks")?,
+        // Octo completes the key wait on release, and has no option for it.
+        //
+        key_wait_release: true,
+    };
+
+    let palette = [
+        color_option(options, "backgroundColor", DEFAULT_BACKGROUND_COLOR)?,
+        color_option(options, "fillColor", DEFAULT_FILL_COLOR)?,
+        color_option(options, "fillColor2", DEFAULT_FILL_COLOR_2)?,
+        color_option(options, "blendColor", DEFAULT_BLEND_COLOR)?,
+    ];
+
+    Ok(CartridgeConfig {
+        quirks,
+        clock_speed,
+        palette,
+    })
+}
+
+fn bool_option(options: &Map<String, Value>, name: &str) -> Result<bool, CartridgeError> {
+    match options.get(name) {
+        None => Ok(false),
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| invalid_option(name, "must be a boolean")),
+    }
+}
+
+// Colors are in the `#RRGGBB` format.
+//
+fn color_option(
+    options: &Map<String, Value>,
+    name: &str,
+    default: &str,
+) -> Result<Pixel, CartridgeError> {
+    let color = match options.get(name) {
+        None => default,
+        Some(value) => value
+            .as_str()
+            .ok_or_else(|| invalid_option(name, "must be a string"))?,
+    };
+
+    let rgb = match color.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 => u32::from_str_radix(hex, 16).ok(),
+        _ => None,
+    }
+    .ok_or_else(|| invalid_option(name, &format!("invalid color: {}", color)))?;
+
+    Ok(Pixel((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+}
+
+fn invalid_option(name: &str, message: &str) -> CartridgeError {
+    CartridgeError::InvalidOption {
+        name: name.to_string(),
+        message: message.to_string(),
+    }
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::InvalidImage(message) => write!(f, "Invalid GIF: {}", message),
+            CartridgeError::TruncatedPayload { length, available } => write!(
+                f,
+                "Truncated payload: {} bytes expected, {} available",
+                length, available
+            ),
+            CartridgeError::InvalidPayload(message) => write!(f, "Invalid payload: {}", message),
+            CartridgeError::InvalidOption { name, message } => {
+                write!(f, "Invalid option {}: {}", name, message)
+            }
+            CartridgeError::InvalidProgram(error) => write!(f, "Invalid program: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {}
@@ -0,0 +1,148 @@
+use crate::cartridge::{load_cartridge, CartridgeError};
+use system_chip_8::IndexIncrement;
+
+use demonstrate::demonstrate;
+use std::borrow::Cow;
+
+const IMAGE_WIDTH: u16 = 64;
+
+// The cartridges are synthesized, following the Octo layout (payload length and JSON, 2 bits per
+// pixel).
+
+fn encode_cartridge(payload: &str) -> Vec<u8> {
+    let mut bytes = (payload.len() as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(payload.as_bytes());
+
+    encode_image(&bytes)
+}
+
+// Encodes the bytes in the pixels of a single frame GIF.
+//
+fn encode_image(bytes: &[u8]) -> Vec<u8> {
+    let mut pixels = bytes
+        .iter()
+        .flat_map(|byte| (0..4).rev().map(move |shift| (byte >> (2 * shift)) & 0b11))
+        .collect::<Vec<u8>>();
+
+    let height = (pixels.len() as u16).div_ceil(IMAGE_WIDTH);
+    pixels.resize((IMAGE_WIDTH * height) as usize, 0);
+
+    let palette = [0, 0, 0, 85, 85, 85, 170, 170, 170, 255, 255, 255];
+    let mut data = vec![];
+
+    {
+        let mut encoder = gif::Encoder::new(&mut data, IMAGE_WIDTH, height, &palette).unwrap();
+
+        let frame = gif::Frame {
+            width: IMAGE_WIDTH,
+            height,
+            buffer: Cow::Owned(pixels),
+            ..gif::Frame::default()
+        };
+
+        encoder.write_frame(&frame).unwrap();
+    }
+
+    data
+}
+
+demonstrate! {
+    describe "cartridge" {
+        use super::*;
+
+        it "loads the program and the options" {
+            let data = encode_cartridge(r##"{
+                "program": ": main\n  v0 := 1\n",
+                "options": {
+                    "tickrate": 500,
+                    "shiftQuirks": true,
+                    "loadStoreQuirks": true,
+                    "vBlankQuirks": true,
+                    "fillColor": "#FF0080",
+                    "screenRotation": 0
+                }
+            }"##);
+
+            let cartridge = load_cartridge(&data).unwrap();
+            let config = cartridge.config;
+
+            assert_eq!(cartridge.rom, vec![0x60, 0x01]);
+            assert_eq!(config.clock_speed, 500 * 60);
+
+            assert!(!config.quirks.shift_uses_Vy);
+            assert_eq!(config.quirks.load_store, IndexIncrement::None);
+            assert!(config.quirks.display_wait);
+            assert!(!config.quirks.jump_uses_Vx);
+
+            let fill_color = config.palette[1];
+            assert_eq!((fill_color.0, fill_color.1, fill_color.2), (0xFF, 0x00, 0x80));
+        }
+
+        it "uses the Octo defaults for the missing options" {
+            let data = encode_cartridge(r#"{"program": ": main\n  clear\n"}"#);
+
+            let config = load_cartridge(&data).unwrap().config;
+
+            assert_eq!(config.clock_speed, 20 * 60);
+            assert!(config.quirks.shift_uses_Vy);
+            assert_eq!(config.quirks.load_store, IndexIncrement::XPlusOne);
+            assert!(!config.quirks.clip_sprites);
+        }
+
+        it "reports the malformed cartridges" {
+            assert!(matches!(
+                load_cartridge(b"GIF89a"),
+                Err(CartridgeError::InvalidImage(_))
+            ));
+
+            let data = encode_image(&[0x00, 0x00, 0x10, 0x00, b'{']);
+
+            assert!(matches!(
+                load_cartridge(&data),
+                Err(CartridgeError::TruncatedPayload { length: 0x1000, .. })
+            ));
+
+            let data = encode_cartridge("{");
+
+            assert!(matches!(
+                load_cartridge(&data),
+                Err(CartridgeError::InvalidPayload(_))
+            ));
+
+            let data = encode_cartridge(r#"{"options": {}}"#);
+
+            assert_eq!(
+                load_cartridge(&data).err(),
+                Some(CartridgeError::InvalidPayload("Missing program".to_string()))
+            );
+
+            let data = encode_cartridge(r#"{"program": "", "options": {"fillColor": "red"}}"#);
+
+            assert_eq!(
+                load_cartridge(&data).err(),
+                Some(CartridgeError::InvalidOption {
+                    name: "fillColor".to_string(),
+                    message: "invalid color: red".to_string()
+                })
+            );
+        }
+
+        it "reports the assembly errors" {
+            let data = encode_cartridge(r#"{"program": ": main\n  v0 := bogus\n"}"#);
+
+            match load_cartridge(&data) {
+                Err(CartridgeError::InvalidProgram(error)) => assert_eq!(error.line, 2),
+                _ => panic!("Assembly error expected"),
+            }
+        }
+
+        it "reports the unsupported Octo constructs" {
+            let data = encode_cartridge(r#"{"program": ": main\n  :calc speed { 2 * 3 }\n"}"#);
+
+            assert_eq!(
+                load_cartridge(&data).err().map(|error| error.to_string()),
+                Some("Invalid program: 2:3: Unsupported directive: :calc".to_string())
+            );
+        }
+    }
+}
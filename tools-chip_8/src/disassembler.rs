@@ -0,0 +1,353 @@
+// Disassembler.
+//
+// Code and data are separated via reachability analysis: starting from the program entry point,
+// all the control flow paths are followed; what is not reached is considered data.
+//
+// Limitations: computed jumps (BNNN) can't be statically followed, so only their base address is
+// considered; self-modifying code is not detected.
+//
+// The Octo syntax output can be reassembled (see `assembler`), as long as there are no
+// overlapping instructions.
+
+use system_chip_8::{Instruction, PROGRAMS_LOCATION};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+const DATA_BYTES_PER_LINE: usize = 8;
+
+// In the Octo syntax, the program must start with the `main` label.
+//
+const OCTO_MAIN_LABEL: &str = "main";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Syntax {
+    Cowgod,
+    Octo,
+}
+
+// Column where the address/bytes comment starts.
+//
+const COMMENT_COLUMN: usize = 28;
+
+enum Line {
+    Code(Instruction),
+    Data(usize),
+}
+
+/// Disassembles a ROM, loaded at the standard programs location.
+///
+pub fn disassemble(rom: &[u8], syntax: Syntax) -> String {
+    let (instructions, mut labels) = analyze(rom);
+
+    if syntax == Syntax::Octo {
+        labels.insert(PROGRAMS_LOCATION);
+    }
+
+    // The lines are generated before formatting, so that the labels pointing inside instructions
+    // (which can't be printed) are known in advance.
+    //
+    let mut lines = BTreeMap::new();
+    let mut address = PROGRAMS_LOCATION;
+    let end_address = PROGRAMS_LOCATION + rom.len();
+
+    while address < end_address {
+        if let Some(instruction) = instructions.get(&address) {
+            lines.insert(address, Line::Code(*instruction));
+            address += instruction.length();
+        } else {
+            let mut length = 1;
+
+            while address + length < end_address
+                && length < DATA_BYTES_PER_LINE
+                && !instructions.contains_key(&(address + length))
+                && !labels.contains(&(address + length))
+            {
+                length += 1;
+            }
+
+            lines.insert(address, Line::Data(length));
+            address += length;
+        }
+    }
+
+    labels.retain(|label| lines.contains_key(label));
+
+    let mut output = String::new();
+
+    for (address, line) in lines {
+        if labels.contains(&address) {
+            match syntax {
+                Syntax::Cowgod => output.push_str(&format!("{}:\n", label_name(address))),
+                Syntax::Octo => output.push_str(&format!(": {}\n", octo_label_name(address))),
+            }
+        }
+
+        let offset = address - PROGRAMS_LOCATION;
+
+        let (text, length) = match line {
+            Line::Code(instruction) => {
+                let text = match syntax {
+                    Syntax::Cowgod => format_instruction(instruction, &rom[offset..], &labels),
+                    Syntax::Octo => format_octo_instruction(instruction, &rom[offset..], &labels),
+                };
+
+                (text, instruction.length())
+            }
+            Line::Data(length) => {
+                let data = &rom[offset..offset + length];
+
+                let text = match syntax {
+                    Syntax::Cowgod => format_data(data),
+                    Syntax::Octo => format_octo_data(data),
+                };
+
+                (text, length)
+            }
+        };
+
+        let comment_prefix = match syntax {
+            Syntax::Cowgod => ';',
+            Syntax::Octo => '#',
+        };
+
+        let bytes = rom[offset..(offset + length).min(rom.len())]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<String>();
+
+        output.push_str(&format!(
+            "    {:width$} {} {:03X}: {}\n",
+            text,
+            comment_prefix,
+            address,
+            bytes,
+            width = COMMENT_COLUMN - 5
+        ));
+    }
+
+    output
+}
+
+// Returns the instructions reached, and the referenced addresses (jump/call targets and data
+// pointers).
+//
+fn analyze(rom: &[u8]) -> (BTreeMap<usize, Instruction>, BTreeSet<usize>) {
+    let mut instructions = BTreeMap::new();
+    let mut labels = BTreeSet::new();
+    let mut pending_addresses = vec![PROGRAMS_LOCATION];
+
+    while let Some(address) = pending_addresses.pop() {
+        if instructions.contains_key(&address) {
+            continue;
+        }
+
+        let instruction = match decode_at(rom, address) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+
+        instructions.insert(address, instruction);
+
+        let next_address = address + instruction.length();
+
+        match instruction {
+            Instruction::Jump(target) | Instruction::JumpPlusV0(target, _) => {
+                labels.insert(target);
+                pending_addresses.push(target);
+            }
+            Instruction::Call(target) => {
+                labels.insert(target);
+                pending_addresses.push(target);
+                pending_addresses.push(next_address);
+            }
+            Instruction::Return | Instruction::Exit => {}
+            Instruction::SkipIfEqualByte(_, _)
+            | Instruction::SkipIfNotEqualByte(_, _)
+            | Instruction::SkipIfEqual(_, _)
+            | Instruction::SkipIfNotEqual(_, _)
+            | Instruction::SkipIfKeyPressed(_)
+            | Instruction::SkipIfKeyNotPressed(_) => {
+                pending_addresses.push(next_address);
+
+                // The skipped instruction may be double-length (F000 NNNN).
+                //
+                let skipped_length = decode_at(rom, next_address).map_or(2, |i| i.length());
+                pending_addresses.push(next_address + skipped_length);
+            }
+            Instruction::SetI(target) => {
+                labels.insert(target);
+                pending_addresses.push(next_address);
+            }
+            Instruction::SetILong => {
+                if let Some(target) = read_word(rom, address + 2) {
+                    labels.insert(target as usize);
+                }
+                pending_addresses.push(next_address);
+            }
+            _ => {
+                pending_addresses.push(next_address);
+            }
+        }
+    }
+
+    (instructions, labels)
+}
+
+fn decode_at(rom: &[u8], address: usize) -> Option<Instruction> {
+    let opcode = read_word(rom, address)?;
+    let instruction = Instruction::decode(opcode).ok()?;
+
+    // Make sure that the operand word is present.
+    //
+    if address + instruction.length() > PROGRAMS_LOCATION + rom.len() {
+        return None;
+    }
+
+    Some(instruction)
+}
+
+fn read_word(rom: &[u8], address: usize) -> Option<u16> {
+    let offset = address.checked_sub(PROGRAMS_LOCATION)?;
+
+    match (rom.get(offset), rom.get(offset + 1)) {
+        (Some(hi_byte), Some(lo_byte)) => Some(((*hi_byte as u16) << 8) + *lo_byte as u16),
+        _ => None,
+    }
+}
+
+// Replaces the address operands with the labels, where available.
+//
+fn format_instruction(instruction: Instruction, bytes: &[u8], labels: &BTreeSet<usize>) -> String {
+    let format_address = |address: usize| {
+        if labels.contains(&address) {
+            label_name(address)
+        } else {
+            format!("#{:03X}", address)
+        }
+    };
+
+    match instruction {
+        Instruction::Jump(address) => format!("JP {}", format_address(address)),
+        Instruction::Call(address) => format!("CALL {}", format_address(address)),
+        Instruction::SetI(address) => format!("LD I, {}", format_address(address)),
+        Instruction::JumpPlusV0(address, _) => format!("JP V0, {}", format_address(address)),
+        Instruction::SetILong => {
+            let address = ((bytes[2] as usize) << 8) + bytes[3] as usize;
+            format!("LD I, {}", format_address(address))
+        }
+        _ => instruction.to_string(),
+    }
+}
+
+fn format_data(bytes: &[u8]) -> String {
+    let values = bytes
+        .iter()
+        .map(|byte| format!("#{:02X}", byte))
+        .collect::<Vec<_>>();
+
+    format!("DB {}", values.join(", "))
+}
+
+// See http://johnearnest.github.io/Octo/docs/Manual.html.
+//
+fn format_octo_instruction(
+    instruction: Instruction,
+    bytes: &[u8],
+    labels: &BTreeSet<usize>,
+) -> String {
+    let format_address = |address: usize| {
+        if labels.contains(&address) {
+            octo_label_name(address)
+        } else {
+            format!("0x{:03X}", address)
+        }
+    };
+
+    match instruction {
+        Instruction::ScrollDown(n) => format!("scroll-down {}", n),
+        Instruction::ScrollUp(n) => format!("scroll-up {}", n),
+        Instruction::ClearScreen => "clear".to_string(),
+        Instruction::Return => "return".to_string(),
+        Instruction::ScrollRight => "scroll-right".to_string(),
+        Instruction::ScrollLeft => "scroll-left".to_string(),
+        Instruction::Exit => "exit".to_string(),
+        Instruction::LoresMode => "lores".to_string(),
+        Instruction::HiresMode => "hires".to_string(),
+        // Octo has no statement for the machine code routines, so they're emitted as data.
+        //
+        Instruction::MachineCall(_) => format_octo_data(&bytes[0..2]),
+        Instruction::Jump(address) => format!("jump {}", format_address(address)),
+        Instruction::Call(address) => format!(":call {}", format_address(address)),
+        // The skip instructions are expressed as the (negated) conditions of `if ... then`; the
+        // skipped instruction follows on the next line.
+        //
+        Instruction::SkipIfEqualByte(Vx, n) => format!("if v{:x} != 0x{:02X} then", Vx, n),
+        Instruction::SkipIfNotEqualByte(Vx, n) => format!("if v{:x} == 0x{:02X} then", Vx, n),
+        Instruction::SkipIfEqual(Vx, Vy) => format!("if v{:x} != v{:x} then", Vx, Vy),
+        Instruction::SkipIfNotEqual(Vx, Vy) => format!("if v{:x} == v{:x} then", Vx, Vy),
+        Instruction::SkipIfKeyPressed(Vx) => format!("if v{:x} -key then", Vx),
+        Instruction::SkipIfKeyNotPressed(Vx) => format!("if v{:x} key then", Vx),
+        Instruction::StoreRange(Vx, Vy) => format!("save v{:x} - v{:x}", Vx, Vy),
+        Instruction::LoadRange(Vx, Vy) => format!("load v{:x} - v{:x}", Vx, Vy),
+        Instruction::SetByte(Vx, n) => format!("v{:x} := 0x{:02X}", Vx, n),
+        Instruction::AddByte(Vx, n) => format!("v{:x} += 0x{:02X}", Vx, n),
+        Instruction::Set(Vx, Vy) => format!("v{:x} := v{:x}", Vx, Vy),
+        Instruction::Or(Vx, Vy) => format!("v{:x} |= v{:x}", Vx, Vy),
+        Instruction::And(Vx, Vy) => format!("v{:x} &= v{:x}", Vx, Vy),
+        Instruction::Xor(Vx, Vy) => format!("v{:x} ^= v{:x}", Vx, Vy),
+        Instruction::Add(Vx, Vy) => format!("v{:x} += v{:x}", Vx, Vy),
+        Instruction::Subtract(Vx, Vy) => format!("v{:x} -= v{:x}", Vx, Vy),
+        Instruction::ShiftRight(Vx, Vy) => format!("v{:x} >>= v{:x}", Vx, Vy),
+        Instruction::SubtractReverse(Vx, Vy) => format!("v{:x} =- v{:x}", Vx, Vy),
+        Instruction::ShiftLeft(Vx, Vy) => format!("v{:x} <<= v{:x}", Vx, Vy),
+        Instruction::SetI(address) => format!("i := {}", format_address(address)),
+        Instruction::JumpPlusV0(address, _) => format!("jump0 {}", format_address(address)),
+        Instruction::Random(Vx, n) => format!("v{:x} := random 0x{:02X}", Vx, n),
+        Instruction::Draw(Vx, Vy, n) => format!("sprite v{:x} v{:x} {}", Vx, Vy, n),
+        Instruction::SetILong => {
+            let address = ((bytes[2] as usize) << 8) + bytes[3] as usize;
+
+            if labels.contains(&address) {
+                format!("i := long {}", octo_label_name(address))
+            } else {
+                format!("i := long 0x{:04X}", address)
+            }
+        }
+        Instruction::SelectPlanes(n) => format!("plane {}", n),
+        Instruction::LoadAudioPattern => "audio".to_string(),
+        Instruction::GetDelayTimer(Vx) => format!("v{:x} := delay", Vx),
+        Instruction::WaitKey(Vx) => format!("v{:x} := key", Vx),
+        Instruction::SetDelayTimer(Vx) => format!("delay := v{:x}", Vx),
+        Instruction::SetSoundTimer(Vx) => format!("buzzer := v{:x}", Vx),
+        Instruction::AddToI(Vx) => format!("i += v{:x}", Vx),
+        Instruction::SetIToFont(Vx) => format!("i := hex v{:x}", Vx),
+        Instruction::SetIToBigFont(Vx) => format!("i := bighex v{:x}", Vx),
+        Instruction::StoreBcd(Vx) => format!("bcd v{:x}", Vx),
+        Instruction::SetPitch(Vx) => format!("pitch := v{:x}", Vx),
+        Instruction::Store(Vx) => format!("save v{:x}", Vx),
+        Instruction::Load(Vx) => format!("load v{:x}", Vx),
+        Instruction::StoreRplFlags(Vx) => format!("saveflags v{:x}", Vx),
+        Instruction::LoadRplFlags(Vx) => format!("loadflags v{:x}", Vx),
+    }
+}
+
+fn format_octo_data(bytes: &[u8]) -> String {
+    let values = bytes
+        .iter()
+        .map(|byte| format!("0x{:02X}", byte))
+        .collect::<Vec<_>>();
+
+    values.join(" ")
+}
+
+fn label_name(address: usize) -> String {
+    format!("L{:03X}", address)
+}
+
+fn octo_label_name(address: usize) -> String {
+    if address == PROGRAMS_LOCATION {
+        OCTO_MAIN_LABEL.to_string()
+    } else {
+        label_name(address)
+    }
+}
@@ -0,0 +1,46 @@
+use crate::disassembler::{disassemble, Syntax};
+use demonstrate::demonstrate;
+
+demonstrate! {
+    describe "disassembler" {
+        use super::*;
+
+        it "separates code and data, and labels the references" {
+            let rom = [
+                0xA2, 0x08, // 200: LD I, L208
+                0x22, 0x06, // 202: CALL L206
+                0x12, 0x04, // 204: JP L204
+                0x00, 0xEE, // 206: RET
+                0xFF, 0x81, // 208: data
+            ];
+
+            let expected_output = "\
+\x20   LD I, L208              ; 200: A208
+\x20   CALL L206               ; 202: 2206
+L204:
+\x20   JP L204                 ; 204: 1204
+L206:
+\x20   RET                     ; 206: 00EE
+L208:
+\x20   DB #FF, #81             ; 208: FF81
+";
+
+            assert_eq!(disassemble(&rom, Syntax::Cowgod), expected_output);
+        }
+
+        it "follows both paths of the skip instructions" {
+            let rom = [
+                0x30, 0x01, // 200: SE V0, #01
+                0xF0, 0x00, // 202: LD I, #0300
+                0x03, 0x00,
+                0x00, 0xFD, // 206: EXIT
+            ];
+
+            let output = disassemble(&rom, Syntax::Cowgod);
+
+            assert!(output.contains("LD I, #300"));
+            assert!(output.contains("EXIT"));
+            assert!(!output.contains("DB"));
+        }
+    }
+}
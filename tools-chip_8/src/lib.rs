@@ -0,0 +1,13 @@
+// For clarity, any register reference is upper case.
+#![allow(non_snake_case)]
+
+pub mod assembler;
+pub mod cartridge;
+pub mod disassembler;
+
+#[cfg(test)]
+mod assembler_test;
+#[cfg(test)]
+mod cartridge_test;
+#[cfg(test)]
+mod disassembler_test;